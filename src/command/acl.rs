@@ -0,0 +1,687 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use crate::command::auth::constant_time_eq;
+use crate::command::table::{self, CATEGORIES, COMMANDS, CommandSpec, KeyAccess, SUBCOMMANDS};
+use crate::util::{glob_match, write_atomic};
+
+pub static ACL: LazyLock<RwLock<Acl>> = LazyLock::new(|| RwLock::new(Acl::new()));
+
+/// ACL LOG 最多保留的条目数
+pub static ACLLOG_MAX_LEN: AtomicUsize = AtomicUsize::new(128);
+
+/// 保存用户的文件，为空时 ACL LOAD/SAVE 不可用
+pub static ACLFILE: RwLock<String> = RwLock::new(String::new());
+
+/// 相同的拒绝在这段时间内合并为一条日志
+const LOG_GROUPING_MS: u128 = 60_000;
+
+pub fn hash_password(password: &str) -> String {
+    Sha256::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// key 模式及其读写权限
+#[derive(Debug, Clone, PartialEq)]
+struct KeyPattern {
+    pattern: String,
+    read: bool,
+    write: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct User {
+    pub name: String,
+    pub enabled: bool,
+    pub nopass: bool,
+    /// 密码的 SHA-256 十六进制摘要
+    pub passwords: Vec<String>,
+    keys: Vec<KeyPattern>,
+    channels: Vec<String>,
+    /// 按设置顺序保存的命令规则，用于描述用户
+    command_rules: Vec<String>,
+    allowed: HashSet<&'static str>,
+}
+
+impl User {
+    /// 新用户默认禁用，没有密码，也没有任何权限
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            enabled: false,
+            nopass: false,
+            passwords: Vec::new(),
+            keys: Vec::new(),
+            channels: Vec::new(),
+            command_rules: vec!["-@all".to_string()],
+            allowed: HashSet::new(),
+        }
+    }
+
+    fn default_user() -> Self {
+        let mut user = Self::new("default");
+        for rule in ["on", "nopass", "~*", "&*", "+@all"] {
+            user.apply(rule).unwrap();
+        }
+        user
+    }
+
+    pub fn apply(&mut self, rule: &str) -> Result<(), String> {
+        let lower = rule.to_lowercase();
+        match lower.as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => return self.apply("~*"),
+            "resetkeys" => self.keys.clear(),
+            "allchannels" => return self.apply("&*"),
+            "resetchannels" => self.channels.clear(),
+            "allcommands" => return self.apply("+@all"),
+            "nocommands" => return self.apply("-@all"),
+            "reset" => {
+                for rule in ["resetpass", "resetkeys", "resetchannels", "off", "-@all"] {
+                    self.apply(rule)?;
+                }
+            }
+            _ => return self.apply_pattern(rule),
+        }
+        Ok(())
+    }
+
+    fn apply_pattern(&mut self, rule: &str) -> Result<(), String> {
+        if let Some(password) = rule.strip_prefix('>') {
+            self.add_password(hash_password(password));
+        } else if let Some(password) = rule.strip_prefix('<') {
+            self.remove_password(&hash_password(password))?;
+        } else if let Some(hash) = rule.strip_prefix('#') {
+            if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err("The password hash must be exactly 64 characters and contain only lowercase hexadecimal characters".to_string());
+            }
+            self.add_password(hash.to_lowercase());
+        } else if let Some(hash) = rule.strip_prefix('!') {
+            self.remove_password(&hash.to_lowercase())?;
+        } else if let Some(pattern) = rule.strip_prefix('~') {
+            self.add_keys(pattern, true, true);
+        } else if let Some(rest) = rule.strip_prefix('%') {
+            let (perms, pattern) = rest.split_once('~').ok_or("Syntax error")?;
+            let perms = perms.to_uppercase();
+            if perms.is_empty() || !perms.chars().all(|c| c == 'R' || c == 'W') {
+                return Err("Syntax error".to_string());
+            }
+            self.add_keys(pattern, perms.contains('R'), perms.contains('W'));
+        } else if let Some(pattern) = rule.strip_prefix('&') {
+            if pattern == "*" {
+                self.channels.clear();
+            }
+            if !self.channels.iter().any(|c| c == pattern || c == "*") {
+                self.channels.push(pattern.to_string());
+            }
+        } else if let Some(name) = rule.strip_prefix('+') {
+            self.apply_command(name, true)?;
+        } else if let Some(name) = rule.strip_prefix('-') {
+            self.apply_command(name, false)?;
+        } else {
+            return Err("Syntax error".to_string());
+        }
+        Ok(())
+    }
+
+    fn add_password(&mut self, hash: String) {
+        self.nopass = false;
+        if !self.passwords.contains(&hash) {
+            self.passwords.push(hash);
+        }
+    }
+
+    fn remove_password(&mut self, hash: &str) -> Result<(), String> {
+        let before = self.passwords.len();
+        self.passwords.retain(|p| p != hash);
+        if self.passwords.len() == before {
+            return Err(
+                "The password you are trying to remove from the user does not exist".to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    fn add_keys(&mut self, pattern: &str, read: bool, write: bool) {
+        if pattern == "*" && read && write {
+            self.keys.clear();
+        }
+        let key = KeyPattern {
+            pattern: pattern.to_string(),
+            read,
+            write,
+        };
+        if !self.keys.contains(&key) {
+            self.keys.push(key);
+        }
+    }
+
+    fn apply_command(&mut self, name: &str, allow: bool) -> Result<(), String> {
+        let name = name.to_lowercase();
+        // 命令名与需要单独授权的子命令名
+        let names: Vec<&'static str> = match name.strip_prefix('@') {
+            Some("all") => COMMANDS
+                .iter()
+                .map(|spec| spec.name)
+                .chain(SUBCOMMANDS.iter().copied())
+                .collect(),
+            Some(category) if CATEGORIES.contains(&category) => COMMANDS
+                .iter()
+                .filter(|spec| spec.categories().contains(&category))
+                .map(|spec| spec.name)
+                .chain(
+                    SUBCOMMANDS
+                        .iter()
+                        .copied()
+                        .filter(|sub| table::subcommand_categories(sub).contains(&category)),
+                )
+                .collect(),
+            Some(_) => {
+                return Err("Unknown command or category name in ACL".to_string());
+            }
+            None => match SUBCOMMANDS.iter().find(|&&sub| sub == name) {
+                Some(sub) => vec![sub],
+                None => {
+                    let spec = table::lookup(&name.to_uppercase())
+                        .ok_or("Unknown command or category name in ACL")?;
+                    let prefix = format!("{}|", name);
+                    std::iter::once(spec.name)
+                        .chain(
+                            SUBCOMMANDS
+                                .iter()
+                                .copied()
+                                .filter(|sub| sub.starts_with(&prefix)),
+                        )
+                        .collect()
+                }
+            },
+        };
+        for name in names {
+            if allow {
+                self.allowed.insert(name);
+            } else {
+                self.allowed.remove(name);
+            }
+        }
+        let rule = format!("{}{}", if allow { '+' } else { '-' }, name);
+        if name == "@all" {
+            self.command_rules.clear();
+        }
+        self.command_rules.push(rule);
+        Ok(())
+    }
+
+    pub fn check_password(&self, password: &str) -> bool {
+        if self.nopass {
+            return true;
+        }
+        let hash = hash_password(password);
+        self.passwords.iter().fold(false, |found, p| {
+            found | constant_time_eq(p.as_bytes(), hash.as_bytes())
+        })
+    }
+
+    pub fn can_run(&self, spec: &CommandSpec) -> bool {
+        self.allowed.contains(spec.name)
+    }
+
+    /// CLIENT KILL 等子命令按子命令授权，与所属命令是否允许无关
+    pub fn can_run_with(&self, spec: &CommandSpec, args: &[String]) -> bool {
+        match spec.subcommand(args) {
+            Some(name) => self.allowed.contains(name),
+            None => self.can_run(spec),
+        }
+    }
+
+    /// 写命令需要写权限，其他命令需要读权限
+    /// 同一条 key 规则需要同时满足所需的读写权限
+    pub fn can_access_key(&self, key: &str, access: KeyAccess) -> bool {
+        self.keys.iter().any(|k| {
+            (k.read || !access.read())
+                && (k.write || !access.write())
+                && glob_match(&k.pattern, key)
+        })
+    }
+
+    /// 订阅模式时，模式本身必须与允许的模式完全一致
+    pub fn can_access_channel(&self, channel: &str, is_pattern: bool) -> bool {
+        self.channels.iter().any(|c| {
+            c == "*"
+                || if is_pattern {
+                    c == channel
+                } else {
+                    glob_match(c, channel)
+                }
+        })
+    }
+
+    pub fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec![if self.enabled { "on" } else { "off" }];
+        if self.nopass {
+            flags.push("nopass");
+        }
+        flags
+    }
+
+    pub fn describe_keys(&self) -> String {
+        self.keys
+            .iter()
+            .map(|k| match (k.read, k.write) {
+                (true, true) => format!("~{}", k.pattern),
+                (true, false) => format!("%R~{}", k.pattern),
+                _ => format!("%W~{}", k.pattern),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn describe_channels(&self) -> String {
+        if self.channels.is_empty() {
+            return "resetchannels".to_string();
+        }
+        self.channels
+            .iter()
+            .map(|c| format!("&{}", c))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn describe_commands(&self) -> String {
+        self.command_rules.join(" ")
+    }
+
+    /// ACL LIST 与 aclfile 使用的一行描述
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("user {}", self.name)];
+        parts.extend(self.flags().iter().map(|f| f.to_string()));
+        parts.extend(self.passwords.iter().map(|p| format!("#{}", p)));
+        let keys = self.describe_keys();
+        if !keys.is_empty() {
+            parts.push(keys);
+        }
+        parts.push(self.describe_channels());
+        parts.push(self.describe_commands());
+        parts.join(" ")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub count: u64,
+    pub reason: &'static str,
+    pub context: &'static str,
+    pub object: String,
+    pub username: String,
+    pub client_info: String,
+    pub entry_id: u64,
+    pub created: u128,
+    pub updated: u128,
+}
+
+impl LogEntry {
+    pub fn age_seconds(&self) -> f64 {
+        now_ms().saturating_sub(self.created) as f64 / 1000.0
+    }
+}
+
+#[derive(Debug)]
+pub struct Acl {
+    pub users: BTreeMap<String, User>,
+    /// 最新的条目在前
+    pub log: VecDeque<LogEntry>,
+    next_entry_id: u64,
+}
+
+impl Acl {
+    fn new() -> Self {
+        let mut users = BTreeMap::new();
+        users.insert("default".to_string(), User::default_user());
+        Self {
+            users,
+            log: VecDeque::new(),
+            next_entry_id: 0,
+        }
+    }
+
+    pub fn user(&self, name: &str) -> Option<&User> {
+        self.users.get(name)
+    }
+
+    /// 规则全部合法时才修改用户，返回出错的规则与原因
+    pub fn set_user(&mut self, name: &str, rules: &[String]) -> Result<(), (String, String)> {
+        let mut user = self
+            .users
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| User::new(name));
+        for rule in rules {
+            user.apply(rule).map_err(|e| (rule.clone(), e))?;
+        }
+        self.users.insert(name.to_string(), user);
+        Ok(())
+    }
+
+    /// 解析 aclfile 内容，成功后替换全部用户
+    pub fn load(&mut self, content: &str) -> Result<(), String> {
+        let mut users = BTreeMap::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() < 2 || words[0] != "user" {
+                return Err(format!("line {}: should start with user keyword", i + 1));
+            }
+            if users.contains_key(words[1]) {
+                return Err(format!(
+                    "line {}: duplicate user '{}' found",
+                    i + 1,
+                    words[1]
+                ));
+            }
+            let mut user = User::new(words[1]);
+            for rule in &words[2..] {
+                user.apply(rule)
+                    .map_err(|e| format!("line {}: error in ACL rule '{}': {}", i + 1, rule, e))?;
+            }
+            users.insert(words[1].to_string(), user);
+        }
+        users
+            .entry("default".to_string())
+            .or_insert_with(User::default_user);
+        self.users = users;
+        Ok(())
+    }
+
+    pub fn dump(&self) -> String {
+        self.users.values().map(|u| u.describe() + "\n").collect()
+    }
+
+    /// 记录一次拒绝，相同的拒绝在短时间内只增加计数
+    pub fn log(
+        &mut self,
+        reason: &'static str,
+        context: &'static str,
+        object: &str,
+        username: &str,
+        client_info: String,
+    ) {
+        let now = now_ms();
+        if let Some(entry) = self.log.iter_mut().find(|e| {
+            e.reason == reason
+                && e.context == context
+                && e.object == object
+                && e.username == username
+                && now.saturating_sub(e.updated) < LOG_GROUPING_MS
+        }) {
+            entry.count += 1;
+            entry.updated = now;
+            entry.client_info = client_info;
+            return;
+        }
+        self.log.push_front(LogEntry {
+            count: 1,
+            reason,
+            context,
+            object: object.to_string(),
+            username: username.to_string(),
+            client_info,
+            entry_id: self.next_entry_id,
+            created: now,
+            updated: now,
+        });
+        self.next_entry_id += 1;
+        self.log.truncate(ACLLOG_MAX_LEN.load(Ordering::Relaxed));
+    }
+}
+
+fn aclfile() -> Result<String, String> {
+    let path = ACLFILE.read().unwrap().clone();
+    if path.is_empty() {
+        return Err("This Redis instance is not configured to use an ACL file. You may want to specify users via the ACL SETUSER command and then issue a CONFIG REWRITE (assuming you have a Redis configuration file set) in order to store users in the Redis configuration.".to_string());
+    }
+    Ok(path)
+}
+
+/// 从 aclfile 重新加载全部用户，出错时保留原有用户
+pub fn load_file() -> Result<(), String> {
+    let path = aclfile()?;
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+    ACL.write()
+        .unwrap()
+        .load(&content)
+        .map_err(|e| format!("{}:{}", path, e))
+}
+
+/// 把全部用户写入 aclfile
+pub fn save_file() -> Result<(), String> {
+    let path = aclfile()?;
+    let content = ACL.read().unwrap().dump();
+    write_atomic(&path, &content)
+}
+
+/// 命令被 ACL 拒绝的原因
+#[derive(Debug, PartialEq)]
+pub enum Denial {
+    Command,
+    Key(String),
+    Channel(String),
+}
+
+impl Denial {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Denial::Command => "command",
+            Denial::Key(_) => "key",
+            Denial::Channel(_) => "channel",
+        }
+    }
+
+    pub fn message(&self, username: &str, command: &str) -> String {
+        match self {
+            Denial::Command => format!(
+                "NOPERM User {} has no permissions to run the '{}' command",
+                username,
+                command.to_lowercase()
+            ),
+            Denial::Key(_) => "NOPERM No permissions to access a key".to_string(),
+            Denial::Channel(_) => "NOPERM No permissions to access a channel".to_string(),
+        }
+    }
+}
+
+/// 检查用户能否以这些参数执行命令
+pub fn check_command(user: &User, spec: &CommandSpec, args: &[String]) -> Result<(), Denial> {
+    if !user.can_run_with(spec, args) {
+        return Err(Denial::Command);
+    }
+    for (key, access) in spec.keys_with_access(args) {
+        if !user.can_access_key(key, access) {
+            return Err(Denial::Key(key.to_string()));
+        }
+    }
+    let (channels, is_pattern): (&[String], bool) = match spec.name {
+        "SUBSCRIBE" | "SSUBSCRIBE" => (args, false),
+        "PSUBSCRIBE" => (args, true),
+        "PUBLISH" | "SPUBLISH" => (&args[..1], false),
+        _ => (&[], false),
+    };
+    for channel in channels {
+        if !user.can_access_channel(channel, is_pattern) {
+            return Err(Denial::Channel(channel.clone()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn f1_rules() {
+        let mut user = User::new("alice");
+        for rule in ["on", ">p1", "+@read", "-hgetall", "%R~cache:*", "&news.*"] {
+            user.apply(rule).unwrap();
+        }
+        assert!(user.check_password("p1"));
+        assert!(!user.check_password("p2"));
+
+        let get = table::lookup("GET").unwrap();
+        let set = table::lookup("SET").unwrap();
+        assert_eq!(check_command(&user, get, &args(&["cache:1"])), Ok(()));
+        assert_eq!(
+            check_command(&user, get, &args(&["other"])),
+            Err(Denial::Key("other".to_string()))
+        );
+        assert_eq!(
+            check_command(&user, set, &args(&["cache:1", "v"])),
+            Err(Denial::Command)
+        );
+        let hgetall = table::lookup("HGETALL").unwrap();
+        assert_eq!(
+            check_command(&user, hgetall, &args(&["cache:1"])),
+            Err(Denial::Command)
+        );
+        // 源 key 只读、目标 key 只写
+        let mut bob = User::new("bob");
+        for rule in ["on", "nopass", "+@all", "%R~src*", "%W~dst*"] {
+            bob.apply(rule).unwrap();
+        }
+        let sinterstore = table::lookup("SINTERSTORE").unwrap();
+        assert_eq!(
+            check_command(&bob, sinterstore, &args(&["dst", "src1", "src2"])),
+            Ok(())
+        );
+        assert_eq!(
+            check_command(&bob, sinterstore, &args(&["src1", "dst"])),
+            Err(Denial::Key("src1".to_string()))
+        );
+        let lmove = table::lookup("LMOVE").unwrap();
+        assert_eq!(
+            check_command(&bob, lmove, &args(&["src", "dst", "LEFT", "LEFT"])),
+            Err(Denial::Key("src".to_string()))
+        );
+        // 弹出、INCR 等会返回内容的命令同样需要读权限
+        for (name, argv) in [
+            ("LPOP", &["dst"][..]),
+            ("BLMPOP", &["0", "1", "dst", "LEFT"][..]),
+            ("SPOP", &["dst"][..]),
+            ("INCR", &["dst"][..]),
+            ("HGETEX", &["dst", "FIELDS", "1", "f"][..]),
+            ("SMOVE", &["dst", "dst2", "m"][..]),
+        ] {
+            let spec = table::lookup(name).unwrap();
+            assert_eq!(
+                check_command(&bob, spec, &args(argv)),
+                Err(Denial::Key("dst".to_string())),
+                "{}",
+                name
+            );
+        }
+        let smove = table::lookup("SMOVE").unwrap();
+        assert_eq!(
+            check_command(&bob, smove, &args(&["src", "dst", "m"])),
+            Err(Denial::Key("src".to_string()))
+        );
+        bob.apply("~src*").unwrap();
+        assert_eq!(
+            check_command(&bob, lmove, &args(&["src", "dst", "LEFT", "LEFT"])),
+            Ok(())
+        );
+        assert_eq!(
+            check_command(&bob, smove, &args(&["src", "dst", "m"])),
+            Ok(())
+        );
+        assert!(user.can_access_channel("news.tech", false));
+        assert!(user.can_access_channel("news.*", true));
+        assert!(!user.can_access_channel("news.t*", true));
+
+        assert_eq!(
+            user.describe(),
+            format!(
+                "user alice on #{} %R~cache:* &news.* -@all +@read -hgetall",
+                hash_password("p1")
+            )
+        );
+        assert!(user.apply("+nosuchcommand").is_err());
+        assert!(user.apply("<p2").is_err());
+    }
+
+    #[test]
+    fn f2_load() {
+        let mut acl = Acl::new();
+        acl.load("user bob on nopass ~* &* +@all -@dangerous\n")
+            .unwrap();
+        let bob = acl.user("bob").unwrap();
+        assert!(bob.can_run(table::lookup("GET").unwrap()));
+        assert!(!bob.can_run(table::lookup("FLUSHDB").unwrap()));
+        // 文件中没有 default 用户时使用默认设置
+        assert!(acl.user("default").unwrap().nopass);
+        assert!(acl.load("user bob on\nuser bob off\n").is_err());
+        assert!(acl.user("bob").is_some());
+        assert_eq!(
+            acl.dump(),
+            "user bob on nopass ~* &* +@all -@dangerous\nuser default on nopass ~* &* +@all\n"
+        );
+    }
+
+    #[test]
+    fn f3_admin_subcommands() {
+        let mut user = User::new("carol");
+        for rule in ["on", "nopass", "+@all", "-@admin", "-@dangerous"] {
+            user.apply(rule).unwrap();
+        }
+        let client = table::lookup("CLIENT").unwrap();
+        let run = |user: &User, argv: &[&str]| check_command(user, client, &args(argv));
+        assert_eq!(run(&user, &["SETNAME", "c"]), Ok(()));
+        for sub in ["KILL", "pause", "UNPAUSE", "NO-EVICT", "LIST", "info"] {
+            assert_eq!(run(&user, &[sub, "1"]), Err(Denial::Command), "{}", sub);
+        }
+        // 子命令可以单独授权，+client 同时允许全部子命令
+        user.apply("+client|kill").unwrap();
+        assert_eq!(run(&user, &["KILL", "ID", "1"]), Ok(()));
+        assert_eq!(run(&user, &["PAUSE", "10"]), Err(Denial::Command));
+        user.apply("+client").unwrap();
+        assert_eq!(run(&user, &["PAUSE", "10"]), Ok(()));
+        user.apply("-client").unwrap();
+        assert_eq!(run(&user, &["KILL", "ID", "1"]), Err(Denial::Command));
+        assert_eq!(run(&user, &["ID"]), Err(Denial::Command));
+        assert!(user.apply("+client|setname").is_err());
+
+        // ACL WHOAMI 与 ACL CAT 不属于 @admin
+        let acl = table::lookup("ACL").unwrap();
+        let run = |user: &User, argv: &[&str]| check_command(user, acl, &args(argv));
+        assert_eq!(run(&user, &["WHOAMI"]), Ok(()));
+        assert_eq!(run(&user, &["cat", "read"]), Ok(()));
+        assert_eq!(run(&user, &["LIST"]), Err(Denial::Command));
+        assert!(!table::subcommand_categories("acl|whoami").contains(&"admin"));
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::command::acl::{ACL, User};
+
+/// requirepass 的原始值，设置时同步修改 default 用户的密码
+static REQUIREPASS: RwLock<String> = RwLock::new(String::new());
+
+/// 没有设置密码时拒绝非本机客户端
+pub static PROTECTED_MODE: AtomicBool = AtomicBool::new(true);
+
+pub fn requirepass() -> String {
+    REQUIREPASS.read().unwrap().clone()
+}
+
+/// 空字符串表示取消密码
+pub fn set_requirepass(password: &str) {
+    let rules = if password.is_empty() {
+        vec!["nopass".to_string()]
+    } else {
+        vec!["resetpass".to_string(), format!(">{}", password)]
+    };
+    ACL.write()
+        .unwrap()
+        .set_user("default", &rules)
+        .expect("password rules are always valid");
+    *REQUIREPASS.write().unwrap() = password.to_string();
+}
+
+/// requirepass 与 default 用户的状态，CONFIG SET 失败时用于恢复
+pub struct DefaultUser {
+    requirepass: String,
+    user: Option<User>,
+}
+
+pub fn save_default_user() -> DefaultUser {
+    DefaultUser {
+        requirepass: requirepass(),
+        user: ACL.read().unwrap().user("default").cloned(),
+    }
+}
+
+/// 恢复 default 用户原有的全部密码与权限，而不是按 requirepass 重新设置
+pub fn restore_default_user(saved: &DefaultUser) {
+    let mut acl = ACL.write().unwrap();
+    match &saved.user {
+        Some(user) => acl.users.insert("default".to_string(), user.clone()),
+        None => acl.users.remove("default"),
+    };
+    *REQUIREPASS.write().unwrap() = saved.requirepass.clone();
+}
+
+pub fn protected_mode() -> bool {
+    PROTECTED_MODE.load(Ordering::Relaxed)
+}
+
+/// default 用户启用且不需要密码时，新连接自动以 default 身份登录
+pub fn default_user_nopass() -> bool {
+    ACL.read()
+        .unwrap()
+        .user("default")
+        .is_some_and(|user| user.enabled && user.nopass)
+}
+
+/// 逐字节比较全部内容，避免耗时暴露密码前缀
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 校验用户名与密码，用户需要处于启用状态
+pub fn check_credentials(username: &str, password: &str) -> bool {
+    ACL.read()
+        .unwrap()
+        .user(username)
+        .is_some_and(|user| user.enabled && user.check_password(password))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret!"));
+    }
+}
@@ -0,0 +1,204 @@
+use std::path::Path;
+
+use crate::util::write_atomic;
+
+/// 配置文件中的一条指令
+#[derive(Debug, PartialEq)]
+pub struct Directive {
+    /// 出错时提示的位置，格式为 "文件:行号"
+    pub location: String,
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// 按 redis.conf 的规则拆分一行：空白分隔，支持双引号（含转义）与单引号
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            return Ok(args);
+        };
+        let mut arg = String::new();
+        if first == '"' || first == '\'' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    None => return Err("unbalanced quotes in configuration line".to_string()),
+                    Some(c) if c == first => break,
+                    Some('\\') if first == '"' => match chars.next() {
+                        Some('n') => arg.push('\n'),
+                        Some('r') => arg.push('\r'),
+                        Some('t') => arg.push('\t'),
+                        Some(c) => arg.push(c),
+                        None => {
+                            return Err("unbalanced quotes in configuration line".to_string());
+                        }
+                    },
+                    Some(c) => arg.push(c),
+                }
+            }
+            // 闭合引号之后必须是空白
+            if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                return Err("closing quote must be followed by a space".to_string());
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+}
+
+/// 写回配置文件时，空值或含特殊字符的值需要加引号
+pub fn quote(value: &str) -> String {
+    if !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\'' || c == '\\')
+    {
+        return value.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// 读取配置文件，include 的文件按出现的位置展开
+pub fn read(path: &str) -> Result<Vec<Directive>, String> {
+    let mut directives = Vec::new();
+    read_into(path, &mut directives, 0)?;
+    Ok(directives)
+}
+
+fn read_into(path: &str, directives: &mut Vec<Directive>, depth: usize) -> Result<(), String> {
+    if depth > 16 {
+        return Err(format!("{}: too many nested includes", path));
+    }
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    for (i, line) in content.lines().enumerate() {
+        let location = format!("{}:{}", path, i + 1);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut args = split_args(line).map_err(|e| format!("{}: {}", location, e))?;
+        let name = args.remove(0).to_lowercase();
+        if name == "include" {
+            let [include] = args.as_slice() else {
+                return Err(format!("{}: include needs exactly one path", location));
+            };
+            // 相对路径以当前配置文件所在目录为准
+            let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+            let include = dir.join(include).to_string_lossy().into_owned();
+            read_into(&include, directives, depth + 1)?;
+            continue;
+        }
+        directives.push(Directive {
+            location,
+            name,
+            args,
+        });
+    }
+    Ok(())
+}
+
+/// 用当前值改写配置文件，保留注释与无法识别的行
+///
+/// `values` 为 (配置名, 写入文件的参数, 是否为默认值)。已出现在文件中的配置在原位置更新，
+/// 重复出现的行被删除；文件中没有且不是默认值的配置追加到末尾。
+pub fn rewrite(path: &str, values: &[(&str, String, bool)]) -> Result<(), String> {
+    const MARKER: &str = "# Generated by CONFIG REWRITE";
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+    let mut lines: Vec<Option<String>> = content.lines().map(|l| Some(l.to_string())).collect();
+    let mut appended = Vec::new();
+    for (name, value, is_default) in values {
+        let new_line = format!("{} {}", name, value);
+        let mut found = false;
+        for line in lines.iter_mut() {
+            let Some(text) = line else { continue };
+            let directive = split_args(text.trim())
+                .ok()
+                .and_then(|args| args.into_iter().next());
+            if directive.is_some_and(|d| d.eq_ignore_ascii_case(name)) {
+                *line = (!found).then(|| new_line.clone());
+                found = true;
+            }
+        }
+        if !found && !is_default {
+            appended.push(new_line);
+        }
+    }
+    let mut output: Vec<String> = lines.into_iter().flatten().collect();
+    if !appended.is_empty() {
+        if !output.iter().any(|l| l == MARKER) {
+            output.push(MARKER.to_string());
+        }
+        output.extend(appended);
+    }
+    let mut text = output.join("\n");
+    text.push('\n');
+    write_atomic(path, &text)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_split_args() {
+        assert_eq!(split_args("save 900 1").unwrap(), vec!["save", "900", "1"]);
+        assert_eq!(
+            split_args(r#"requirepass "a \"b\" c" 'd e'"#).unwrap(),
+            vec!["requirepass", "a \"b\" c", "d e"]
+        );
+        assert_eq!(split_args(r#"x """#).unwrap(), vec!["x", ""]);
+        assert!(split_args(r#"x "abc"#).is_err());
+        assert_eq!(quote("a b"), r#""a b""#);
+        assert_eq!(quote(""), r#""""#);
+        assert_eq!(split_args(&quote("a \"b\"")).unwrap(), vec!["a \"b\""]);
+    }
+
+    #[test]
+    fn f2_rewrite() {
+        let path = std::env::temp_dir().join(format!("rust-ez-redis-{}.conf", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        std::fs::write(
+            &path,
+            "# my config\nport 6380\nsave 900 1\nsave 300 10\nunknown-option x\n",
+        )
+        .unwrap();
+        rewrite(
+            &path,
+            &[
+                ("port", "7000".to_string(), false),
+                ("save", "\"\"".to_string(), false),
+                ("maxmemory", "100mb".to_string(), false),
+                ("hz", "10".to_string(), true),
+            ],
+        )
+        .unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            content,
+            "# my config\nport 7000\nsave \"\"\nunknown-option x\n# Generated by CONFIG REWRITE\nmaxmemory 100mb\n"
+        );
+    }
+}
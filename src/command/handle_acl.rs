@@ -0,0 +1,183 @@
+use crate::command::Command;
+use crate::command::acl::{self, ACL, LogEntry, User};
+use crate::command::handle_macro::*;
+use crate::command::session::Session;
+use crate::command::table::{self, CATEGORIES, COMMANDS, SUBCOMMANDS};
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(Some(s.to_string()))
+}
+
+pub struct HandleAcl;
+impl HandleAcl {
+    pub fn handle_acl(session: &mut Session, command: Command) -> RespValue {
+        let args = &command.args[1..];
+        match (command.args[0].to_uppercase().as_str(), args.len()) {
+            ("SETUSER", 1..) => Self::setuser(&args[0], &args[1..]),
+            ("GETUSER", 1) => Self::getuser(&args[0]),
+            ("DELUSER", 1..) => Self::deluser(args),
+            ("LIST", 0) => RespValue::Array(
+                ACL.read()
+                    .unwrap()
+                    .users
+                    .values()
+                    .map(|user| bulk(&user.describe()))
+                    .collect(),
+            ),
+            ("USERS", 0) => RespValue::Array(
+                ACL.read()
+                    .unwrap()
+                    .users
+                    .keys()
+                    .map(|name| bulk(name))
+                    .collect(),
+            ),
+            ("WHOAMI", 0) => bulk(&session.user),
+            ("CAT", 0 | 1) => Self::cat(args.first()),
+            ("LOG", 0 | 1) => Self::log(args.first()),
+            ("LOAD", 0) => match acl::load_file() {
+                Ok(()) => RespOK!(),
+                Err(e) => RespValue::Error(format!("ERR {}", e)),
+            },
+            ("SAVE", 0) => match acl::save_file() {
+                Ok(()) => RespOK!(),
+                Err(e) => RespValue::Error(format!(
+                    "ERR There was an error trying to save the ACLs. Please check the server logs for more information: {}",
+                    e
+                )),
+            },
+            _ => RespValue::Error(format!(
+                "ERR unknown subcommand or wrong number of arguments for '{}'. Try ACL HELP.",
+                command.args[0]
+            )),
+        }
+    }
+
+    fn setuser(name: &str, rules: &[String]) -> RespValue {
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '\0') {
+            return RespValue::Error(
+                "ERR Usernames can't contain spaces or null characters".to_string(),
+            );
+        }
+        match ACL.write().unwrap().set_user(name, rules) {
+            Ok(()) => RespOK!(),
+            Err((rule, reason)) => RespValue::Error(format!(
+                "ERR Error in ACL SETUSER modifier '{}': {}",
+                rule, reason
+            )),
+        }
+    }
+
+    fn getuser(name: &str) -> RespValue {
+        let acl = ACL.read().unwrap();
+        let Some(user) = acl.user(name) else {
+            return RespValue::Null;
+        };
+        Self::describe_user(user)
+    }
+
+    fn describe_user(user: &User) -> RespValue {
+        let channels = if user.describe_channels() == "resetchannels" {
+            String::new()
+        } else {
+            user.describe_channels()
+        };
+        RespValue::Map(vec![
+            (
+                bulk("flags"),
+                RespValue::Array(user.flags().into_iter().map(bulk).collect()),
+            ),
+            (
+                bulk("passwords"),
+                RespValue::Array(user.passwords.iter().map(|p| bulk(p)).collect()),
+            ),
+            (bulk("commands"), bulk(&user.describe_commands())),
+            (bulk("keys"), bulk(&user.describe_keys())),
+            (bulk("channels"), bulk(&channels)),
+            (bulk("selectors"), RespValue::Array(Vec::new())),
+        ])
+    }
+
+    fn deluser(names: &[String]) -> RespValue {
+        if names.iter().any(|name| name == "default") {
+            return RespValue::Error("ERR The 'default' user cannot be removed".to_string());
+        }
+        let mut acl = ACL.write().unwrap();
+        let removed = names
+            .iter()
+            .filter(|name| acl.users.remove(name.as_str()).is_some())
+            .count();
+        RespValue::Integer(removed as i64)
+    }
+
+    /// ACL CAT [category]
+    fn cat(category: Option<&String>) -> RespValue {
+        let Some(category) = category else {
+            return RespValue::Array(CATEGORIES.iter().map(|c| bulk(c)).collect());
+        };
+        let category = category.to_lowercase();
+        if !CATEGORIES.contains(&category.as_str()) {
+            return RespValue::Error(format!("ERR Unknown category '{}'", category));
+        }
+        RespValue::Array(
+            COMMANDS
+                .iter()
+                .filter(|spec| spec.categories().contains(&category.as_str()))
+                .map(|spec| bulk(&spec.name.to_lowercase()))
+                .chain(
+                    SUBCOMMANDS
+                        .iter()
+                        .filter(|sub| {
+                            table::subcommand_categories(sub).contains(&category.as_str())
+                        })
+                        .map(|sub| bulk(sub)),
+                )
+                .collect(),
+        )
+    }
+
+    /// ACL LOG [count | RESET]
+    fn log(arg: Option<&String>) -> RespValue {
+        let mut acl = ACL.write().unwrap();
+        let count = match arg {
+            None => 10,
+            Some(arg) if arg.eq_ignore_ascii_case("RESET") => {
+                acl.log.clear();
+                return RespOK!();
+            }
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => {
+                    return RespValue::Error(
+                        "ERR value is out of range, must be positive".to_string(),
+                    );
+                }
+            },
+        };
+        RespValue::Array(acl.log.iter().take(count).map(Self::log_entry).collect())
+    }
+
+    fn log_entry(entry: &LogEntry) -> RespValue {
+        RespValue::Map(vec![
+            (bulk("count"), RespValue::Integer(entry.count as i64)),
+            (bulk("reason"), bulk(entry.reason)),
+            (bulk("context"), bulk(entry.context)),
+            (bulk("object"), bulk(&entry.object)),
+            (bulk("username"), bulk(&entry.username)),
+            (
+                bulk("age-seconds"),
+                bulk(&format!("{:.3}", entry.age_seconds())),
+            ),
+            (bulk("client-info"), bulk(&entry.client_info)),
+            (bulk("entry-id"), RespValue::Integer(entry.entry_id as i64)),
+            (
+                bulk("timestamp-created"),
+                RespValue::Integer(entry.created as i64),
+            ),
+            (
+                bulk("timestamp-last-updated"),
+                RespValue::Integer(entry.updated as i64),
+            ),
+        ])
+    }
+}
@@ -0,0 +1,585 @@
+use crate::command::acl::ACL;
+use crate::command::handle_macro::*;
+use std::time::Duration;
+
+use crate::command::session::{ReplyMode, Session};
+use crate::command::{Command, auth, pause};
+use crate::storage::{Client, TrackingOptions};
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(Some(s.to_string()))
+}
+
+/// 客户端名字与库信息不能包含空格、换行等字符
+fn valid_name(name: &str) -> bool {
+    name.bytes().all(|b| (b'!'..=b'~').contains(&b))
+}
+
+/// CLIENT LIST / KILL 的 TYPE，没有主从复制，master 与 replica 不会匹配任何连接
+fn parse_type(kind: &str) -> Result<&'static str, RespValue> {
+    match kind.to_lowercase().as_str() {
+        "normal" => Ok("normal"),
+        "pubsub" => Ok("pubsub"),
+        "master" => Ok("master"),
+        "replica" | "slave" => Ok("replica"),
+        _ => Err(RespValue::Error(format!(
+            "ERR Unknown client type '{}'",
+            kind
+        ))),
+    }
+}
+
+pub struct HandleClient;
+impl HandleClient {
+    /// HELLO [protover [AUTH username password] [SETNAME clientname]]：切换协议版本并返回服务器信息
+    pub fn handle_hello(session: &mut Session, command: Command) -> RespValue {
+        let mut protocol = session.outbox.protocol();
+        let mut args = command.args.iter();
+        if let Some(version) = args.next() {
+            match version.parse::<u8>() {
+                Ok(v @ (2 | 3)) => protocol = v,
+                Ok(_) => {
+                    return RespValue::Error("NOPROTO unsupported protocol version".to_string());
+                }
+                Err(_) => {
+                    return RespValue::Error(
+                        "ERR Protocol version is not an integer or out of range".to_string(),
+                    );
+                }
+            }
+        }
+        let mut credentials = None;
+        let mut name = None;
+        while let Some(option) = args.next() {
+            if option.eq_ignore_ascii_case("AUTH")
+                && let (Some(username), Some(password)) = (args.next(), args.next())
+            {
+                credentials = Some((username, password));
+            } else if option.eq_ignore_ascii_case("SETNAME")
+                && let Some(clientname) = args.next()
+            {
+                if !valid_name(clientname) {
+                    return RespValue::Error(
+                        "ERR Client names cannot contain spaces, newlines or special characters."
+                            .to_string(),
+                    );
+                }
+                name = Some(clientname);
+            } else {
+                return RespValue::Error(format!("ERR Syntax error in HELLO option '{}'", option));
+            }
+        }
+        match credentials {
+            Some((username, password)) => {
+                if let Err(e) = Self::authenticate(session, username, password) {
+                    return e;
+                }
+            }
+            None if !session.authenticated => {
+                return RespValue::Error(
+                    "NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time".to_string(),
+                );
+            }
+            None => {}
+        }
+        session.outbox.set_protocol(protocol);
+        if let Some(name) = name {
+            session.client.state().name = name.clone();
+        }
+        RespValue::Map(vec![
+            (bulk("server"), bulk("redis")),
+            (bulk("version"), bulk(env!("CARGO_PKG_VERSION"))),
+            (bulk("proto"), RespValue::Integer(protocol as i64)),
+            (bulk("id"), RespValue::Integer(session.id as i64)),
+            (bulk("mode"), bulk("standalone")),
+            (bulk("role"), bulk("master")),
+            (bulk("modules"), RespValue::Array(Vec::new())),
+        ])
+    }
+
+    /// AUTH [username] password
+    pub fn handle_auth(session: &mut Session, command: Command) -> RespValue {
+        let (username, password) = match command.args.as_slice() {
+            [password] => {
+                if auth::default_user_nopass() {
+                    return RespValue::Error(
+                        "ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?".to_string(),
+                    );
+                }
+                ("default", password)
+            }
+            [username, password] => (username.as_str(), password),
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        };
+        match Self::authenticate(session, username, password) {
+            Ok(()) => RespOK!(),
+            Err(e) => e,
+        }
+    }
+
+    fn authenticate(
+        session: &mut Session,
+        username: &str,
+        password: &str,
+    ) -> Result<(), RespValue> {
+        if auth::check_credentials(username, password) {
+            session.authenticated = true;
+            session.user = username.to_string();
+            Ok(())
+        } else {
+            let context = if session.in_multi() {
+                "multi"
+            } else {
+                "toplevel"
+            };
+            ACL.write()
+                .unwrap()
+                .log("auth", context, "AUTH", username, session.client_info());
+            Err(RespValue::Error(
+                "WRONGPASS invalid username-password pair or user is disabled.".to_string(),
+            ))
+        }
+    }
+
+    pub fn handle_client(db: &mut Database, session: &mut Session, command: Command) -> RespValue {
+        let subcommand = command.args[0].to_uppercase();
+        match (subcommand.as_str(), command.args.len()) {
+            ("ID", 1) => RespValue::Integer(session.id as i64),
+            ("TRACKING", n) if n >= 2 => Self::tracking(db, session, &command.args[1..]),
+            ("CACHING", 2) => Self::caching(db, session, &command.args[1]),
+            ("GETREDIR", 1) => match db.tracking.options(session.id) {
+                None => RespValue::Integer(-1),
+                Some(options) => RespValue::Integer(options.redirect.map_or(0, |id| id as i64)),
+            },
+            ("TRACKINGINFO", 1) => Self::tracking_info(db, session),
+            ("LIST", _) => Self::list(db, &command.args[1..]),
+            ("INFO", 1) => bulk(&format!("{}\n", Self::describe(db, &session.client))),
+            ("SETNAME", 2) => {
+                if !valid_name(&command.args[1]) {
+                    return RespValue::Error(
+                        "ERR Client names cannot contain spaces, newlines or special characters."
+                            .to_string(),
+                    );
+                }
+                session.client.state().name = command.args[1].clone();
+                RespOK!()
+            }
+            ("GETNAME", 1) => match session.client.state().name.as_str() {
+                "" => RespValue::BulkString(None),
+                name => bulk(name),
+            },
+            ("SETINFO", 3) => Self::setinfo(session, &command.args[1], &command.args[2]),
+            ("KILL", n) if n >= 2 => Self::kill(db, session, &command.args[1..]),
+            ("PAUSE", 2 | 3) => Self::pause(&command.args[1..]),
+            ("UNPAUSE", 1) => {
+                pause::unpause();
+                RespOK!()
+            }
+            ("REPLY", 2) => {
+                session.reply = match command.args[1].to_uppercase().as_str() {
+                    "ON" => ReplyMode::On,
+                    "OFF" => ReplyMode::Off,
+                    "SKIP" => ReplyMode::SkipNext,
+                    _ => return RespValue::Error("ERR syntax error".to_string()),
+                };
+                RespOK!()
+            }
+            ("NO-EVICT", 2) => {
+                session.client.state().no_evict = match command.args[1].to_uppercase().as_str() {
+                    "ON" => true,
+                    "OFF" => false,
+                    _ => return RespValue::Error("ERR syntax error".to_string()),
+                };
+                RespOK!()
+            }
+            ("HELP", 1) => RespValue::Array(
+                [
+                    "CLIENT <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                    "CACHING (YES|NO)",
+                    "    Enable/disable tracking of the keys for next command in OPTIN/OPTOUT modes.",
+                    "GETREDIR",
+                    "    Return the client ID we are redirecting to when tracking is enabled.",
+                    "GETNAME",
+                    "    Return the name of the current connection.",
+                    "ID",
+                    "    Return the ID of the current connection.",
+                    "INFO",
+                    "    Return information about the current client connection.",
+                    "KILL <ip:port>",
+                    "    Kill connection made from <ip:port>.",
+                    "KILL <option> <value> [<option> <value> [...]]",
+                    "    Kill connections. Options are:",
+                    "    * ADDR (<ip:port>|<unixsocket>:0)",
+                    "      Kill connections made from the specified address",
+                    "    * LADDR (<ip:port>|<unixsocket>:0)",
+                    "      Kill connections made to specified local address",
+                    "    * TYPE (NORMAL|MASTER|REPLICA|PUBSUB)",
+                    "      Kill connections by type.",
+                    "    * USER <username>",
+                    "      Kill connections authenticated by <username>.",
+                    "    * SKIPME (YES|NO)",
+                    "      Skip killing current connection (default: yes).",
+                    "    * ID <client-id>",
+                    "      Kill connections by client id.",
+                    "    * MAXAGE <maxage>",
+                    "      Kill connections older than the specified age.",
+                    "LIST [options ...]",
+                    "    Return information about client connections. Options:",
+                    "    * TYPE (NORMAL|MASTER|REPLICA|PUBSUB)",
+                    "      Return clients of specified type.",
+                    "    * ID <client-id> [<client-id> ...]",
+                    "      Return clients of specified IDs only.",
+                    "PAUSE <timeout> [WRITE|ALL]",
+                    "    Suspend all, or just write, clients for <timeout> milliseconds.",
+                    "UNPAUSE",
+                    "    Stop the current client pause, resuming traffic.",
+                    "SETNAME <name>",
+                    "    Assign the name <name> to the current connection.",
+                    "SETINFO <option> <value>",
+                    "    Set client meta attr. Options are:",
+                    "    * LIB-NAME: the client lib name.",
+                    "    * LIB-VER: the client lib version.",
+                    "REPLY (ON|OFF|SKIP)",
+                    "    Control the replies sent to the current connection.",
+                    "NO-EVICT (ON|OFF)",
+                    "    Protect current client connection from eviction.",
+                    "TRACKING (ON|OFF) [REDIRECT <id>] [BCAST] [PREFIX <prefix> [...]]",
+                    "         [OPTIN] [OPTOUT] [NOLOOP]",
+                    "    Control server assisted client side caching.",
+                    "TRACKINGINFO",
+                    "    Report tracking status for the current connection.",
+                    "HELP",
+                    "    Print this help.",
+                ]
+                .into_iter()
+                .map(bulk)
+                .collect(),
+            ),
+            _ => RespValue::Error(format!(
+                "ERR unknown subcommand or wrong number of arguments for '{}'. Try CLIENT HELP.",
+                command.args[0]
+            )),
+        }
+    }
+
+    /// CLIENT LIST 中的一行，按 Redis 的字段顺序输出
+    fn describe(db: &Database, client: &Client) -> String {
+        let kind = client.kind();
+        let state = client.state();
+        let tracking = db.tracking.options(client.id);
+        let mut flags: String = [
+            (state.monitor, 'O'),
+            (kind == "pubsub", 'P'),
+            (state.multi >= 0, 'x'),
+            (state.blocked, 'b'),
+            (tracking.is_some(), 't'),
+            (tracking.is_some_and(|o| o.bcast), 'B'),
+            (
+                tracking
+                    .and_then(|o| o.redirect)
+                    .is_some_and(|id| !db.clients.contains_key(&id)),
+                'R',
+            ),
+            (state.no_evict, 'e'),
+        ]
+        .into_iter()
+        .filter_map(|(set, flag)| set.then_some(flag))
+        .collect();
+        if flags.is_empty() {
+            flags.push('N');
+        }
+        format!(
+            "id={} addr={} laddr={} name={} age={} idle={} flags={} db=0 sub={} psub={} ssub={} multi={} watch={} qbuf={} omem={} cmd={} user={} redir={} resp={} lib-name={} lib-ver={}",
+            client.id,
+            client.addr,
+            client.laddr,
+            state.name,
+            client.age().as_secs(),
+            state.last_interaction.elapsed().as_secs(),
+            flags,
+            state.sub,
+            state.psub,
+            state.ssub,
+            state.multi,
+            state.watch,
+            client.query_buffer(),
+            client.outbox.pending(),
+            state.last_cmd,
+            state.user,
+            tracking.map_or(-1, |o| o.redirect.map_or(0, |id| id as i64)),
+            client.outbox.protocol(),
+            state.lib_name,
+            state.lib_ver,
+        )
+    }
+
+    /// CLIENT LIST [TYPE type] [ID id [id ...]]
+    fn list(db: &Database, args: &[String]) -> RespValue {
+        let mut kind = None;
+        let mut ids = None;
+        match args.first().map(|arg| arg.to_uppercase()).as_deref() {
+            None => {}
+            Some("TYPE") if args.len() == 2 => match parse_type(&args[1]) {
+                Ok(t) => kind = Some(t),
+                Err(e) => return e,
+            },
+            Some("ID") if args.len() >= 2 => {
+                let parsed: Result<Vec<u64>, _> = args[1..].iter().map(|id| id.parse()).collect();
+                match parsed {
+                    Ok(parsed) if !parsed.contains(&0) => ids = Some(parsed),
+                    _ => return RespValue::Error("ERR Invalid client ID".to_string()),
+                }
+            }
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        }
+        let mut clients: Vec<_> = db
+            .clients
+            .values()
+            .filter(|client| kind.is_none_or(|kind| client.kind() == kind))
+            .filter(|client| ids.as_ref().is_none_or(|ids| ids.contains(&client.id)))
+            .collect();
+        clients.sort_by_key(|client| client.id);
+        let mut output = String::new();
+        for client in clients {
+            output.push_str(&Self::describe(db, client));
+            output.push('\n');
+        }
+        bulk(&output)
+    }
+
+    /// CLIENT SETINFO LIB-NAME|LIB-VER value
+    fn setinfo(session: &Session, attr: &str, value: &str) -> RespValue {
+        let attr = attr.to_uppercase();
+        if attr != "LIB-NAME" && attr != "LIB-VER" {
+            return RespValue::Error(format!("ERR Unrecognized option '{}'", attr));
+        }
+        if !valid_name(value) {
+            return RespValue::Error(format!(
+                "ERR {} cannot contain spaces, newlines or special characters.",
+                attr.to_lowercase()
+            ));
+        }
+        let mut state = session.client.state();
+        if attr == "LIB-NAME" {
+            state.lib_name = value.to_string();
+        } else {
+            state.lib_ver = value.to_string();
+        }
+        RespOK!()
+    }
+
+    /// CLIENT KILL ip:port，或 CLIENT KILL [ID id] [ADDR addr] [LADDR addr] [USER name] [TYPE type] [SKIPME yes|no] [MAXAGE secs]
+    fn kill(db: &Database, session: &Session, args: &[String]) -> RespValue {
+        // 旧格式只按地址匹配，可以断开自己
+        if let [addr] = args {
+            return match db.clients.values().find(|client| &client.addr == addr) {
+                Some(client) => {
+                    client.outbox.close();
+                    RespOK!()
+                }
+                None => RespValue::Error("ERR No such client".to_string()),
+            };
+        }
+        if !args.len().is_multiple_of(2) {
+            return RespValue::Error("ERR syntax error".to_string());
+        }
+        let mut id = None;
+        let mut addr = None;
+        let mut laddr = None;
+        let mut user = None;
+        let mut kind = None;
+        let mut skipme = true;
+        let mut maxage = None;
+        for pair in args.chunks(2) {
+            let value = &pair[1];
+            match pair[0].to_uppercase().as_str() {
+                "ID" => match value.parse::<u64>() {
+                    Ok(n) if n > 0 => id = Some(n),
+                    _ => {
+                        return RespValue::Error(
+                            "ERR client-id should be greater than 0".to_string(),
+                        );
+                    }
+                },
+                "ADDR" => addr = Some(value),
+                "LADDR" => laddr = Some(value),
+                "USER" => user = Some(value),
+                "TYPE" => match parse_type(value) {
+                    Ok(t) => kind = Some(t),
+                    Err(e) => return e,
+                },
+                "SKIPME" => match value.to_uppercase().as_str() {
+                    "YES" => skipme = true,
+                    "NO" => skipme = false,
+                    _ => return RespValue::Error("ERR syntax error".to_string()),
+                },
+                "MAXAGE" => match value.parse::<u64>() {
+                    Ok(n) => maxage = Some(n),
+                    Err(_) => return RespErrNumWrong!(),
+                },
+                _ => return RespValue::Error("ERR syntax error".to_string()),
+            }
+        }
+        let mut killed = 0;
+        for client in db.clients.values() {
+            let same_user = user.is_none_or(|user| &client.state().user == user);
+            let matched = id.is_none_or(|id| client.id == id)
+                && addr.is_none_or(|addr| &client.addr == addr)
+                && laddr.is_none_or(|laddr| &client.laddr == laddr)
+                && same_user
+                && kind.is_none_or(|kind| client.kind() == kind)
+                && maxage.is_none_or(|maxage| client.age().as_secs() >= maxage)
+                && !(skipme && client.id == session.id);
+            if matched {
+                client.outbox.close();
+                killed += 1;
+            }
+        }
+        RespValue::Integer(killed)
+    }
+
+    /// CLIENT PAUSE timeout [WRITE|ALL]
+    fn pause(args: &[String]) -> RespValue {
+        let Ok(timeout) = args[0].parse::<u64>() else {
+            return RespValue::Error("ERR timeout is not an integer or out of range".to_string());
+        };
+        let all = match args.get(1).map(|mode| mode.to_uppercase()).as_deref() {
+            None | Some("ALL") => true,
+            Some("WRITE") => false,
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        };
+        pause::pause(Duration::from_millis(timeout), all);
+        RespOK!()
+    }
+
+    /// CLIENT TRACKING ON|OFF [REDIRECT id] [PREFIX prefix ...] [BCAST] [OPTIN] [OPTOUT] [NOLOOP]
+    fn tracking(db: &mut Database, session: &mut Session, args: &[String]) -> RespValue {
+        let on = match args[0].to_uppercase().as_str() {
+            "ON" => true,
+            "OFF" => false,
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        };
+        let mut options = TrackingOptions::default();
+        let mut i = 1;
+        while i < args.len() {
+            match args[i].to_uppercase().as_str() {
+                "REDIRECT" if i + 1 < args.len() => {
+                    let Ok(id) = args[i + 1].parse::<u64>() else {
+                        return RespErrNumWrong!();
+                    };
+                    options.redirect = Some(id);
+                    i += 1;
+                }
+                "PREFIX" if i + 1 < args.len() => {
+                    options.prefixes.push(args[i + 1].clone());
+                    i += 1;
+                }
+                "BCAST" => options.bcast = true,
+                "OPTIN" => options.optin = true,
+                "OPTOUT" => options.optout = true,
+                "NOLOOP" => options.noloop = true,
+                _ => return RespValue::Error("ERR syntax error".to_string()),
+            }
+            i += 1;
+        }
+
+        if !on {
+            db.tracking.disable(session.id);
+            return RespOK!();
+        }
+        if !options.bcast && !options.prefixes.is_empty() {
+            return RespValue::Error(
+                "ERR PREFIX option requires BCAST mode to be enabled".to_string(),
+            );
+        }
+        if options.optin && options.optout {
+            return RespValue::Error(
+                "ERR You can't use OPTIN and OPTOUT at the same time".to_string(),
+            );
+        }
+        if options.bcast && (options.optin || options.optout) {
+            return RespValue::Error(
+                "ERR OPTIN and OPTOUT are not compatible with BCAST".to_string(),
+            );
+        }
+        if let Some(id) = options.redirect
+            && id != session.id
+            && !db.clients.contains_key(&id)
+        {
+            return RespValue::Error(
+                "ERR The client ID you want redirect to does not exist".to_string(),
+            );
+        }
+        if let Some(current) = db.tracking.options(session.id) {
+            if current.bcast != options.bcast {
+                return RespValue::Error(
+                    "ERR You can't switch BCAST mode on/off before disabling tracking for this client, and then re-enabling it with a different mode.".to_string(),
+                );
+            }
+            // 再次开启时保留之前登记的前缀
+            for prefix in &current.prefixes {
+                if !options.prefixes.contains(prefix) {
+                    options.prefixes.push(prefix.clone());
+                }
+            }
+        }
+        db.tracking.enable(session.id, options);
+        RespOK!()
+    }
+
+    /// CLIENT CACHING YES|NO：只对下一条命令生效
+    fn caching(db: &Database, session: &mut Session, arg: &str) -> RespValue {
+        let options = db.tracking.options(session.id);
+        match arg.to_uppercase().as_str() {
+            "YES" if options.is_some_and(|o| o.optin) => session.caching = Some(true),
+            "YES" => {
+                return RespValue::Error(
+                    "ERR CLIENT CACHING YES is only valid when tracking is enabled in OPTIN mode."
+                        .to_string(),
+                );
+            }
+            "NO" if options.is_some_and(|o| o.optout) => session.caching = Some(false),
+            "NO" => {
+                return RespValue::Error(
+                    "ERR CLIENT CACHING NO is only valid when tracking is enabled in OPTOUT mode."
+                        .to_string(),
+                );
+            }
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        }
+        RespOK!()
+    }
+
+    fn tracking_info(db: &Database, session: &Session) -> RespValue {
+        let (flags, redirect, prefixes) = match db.tracking.options(session.id) {
+            None => (vec![bulk("off")], -1, Vec::new()),
+            Some(options) => {
+                let mut flags = vec![bulk("on")];
+                for (set, name) in [
+                    (options.bcast, "bcast"),
+                    (options.optin, "optin"),
+                    (options.optout, "optout"),
+                    (options.noloop, "noloop"),
+                ] {
+                    if set {
+                        flags.push(bulk(name));
+                    }
+                }
+                let redirect = options.redirect.map_or(0, |id| id as i64);
+                if options
+                    .redirect
+                    .is_some_and(|id| !db.clients.contains_key(&id))
+                {
+                    flags.push(bulk("broken_redirect"));
+                }
+                let prefixes = options.prefixes.iter().map(|p| bulk(p)).collect();
+                (flags, redirect, prefixes)
+            }
+        };
+        RespValue::Map(vec![
+            (bulk("flags"), RespValue::Array(flags)),
+            (bulk("redirect"), RespValue::Integer(redirect)),
+            (bulk("prefixes"), RespValue::Array(prefixes)),
+        ])
+    }
+}
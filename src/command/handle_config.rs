@@ -0,0 +1,685 @@
+use std::sync::atomic::{AtomicU16, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{LazyLock, RwLock};
+
+use crate::command::Command;
+use crate::command::acl::{ACLFILE, ACLLOG_MAX_LEN};
+use crate::command::auth::{self, PROTECTED_MODE};
+use crate::command::config_file;
+use crate::command::handle_macro::*;
+use crate::command::slowlog::{SLOWLOG_LOG_SLOWER_THAN, SLOWLOG_MAX_LEN};
+use crate::command::table;
+use crate::protocol::PROTO_MAX_BULK_LEN;
+use crate::server::{
+    LATENCY_MONITOR_THRESHOLD, LISTEN_CONFIG, SERVER_CONFIG, STATS, TLS_CONFIG, log, loglevel,
+    set_loglevel,
+};
+use crate::storage::{
+    ENCODING_CONFIG, EVICTION_CONFIG, MAXMEMORY, NOTIFY_KEYSPACE_EVENTS, PUBSUB_OUTPUT_LIMIT,
+    format_notify_flags, parse_notify_flags,
+};
+use crate::util::{glob_match, parse_memory};
+
+/// 可在运行时读取和修改的配置项
+struct ConfigParam {
+    name: &'static str,
+    get: fn() -> String,
+    set: fn(&str) -> Result<(), String>,
+}
+
+fn set_usize(target: &AtomicUsize, value: &str) -> Result<(), String> {
+    let n = value
+        .parse::<usize>()
+        .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+    target.store(n, Ordering::Relaxed);
+    Ok(())
+}
+
+fn set_u64(target: &AtomicU64, value: &str) -> Result<(), String> {
+    let n = value
+        .parse::<u64>()
+        .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+    target.store(n, Ordering::Relaxed);
+    Ok(())
+}
+
+fn set_port(target: &AtomicU16, value: &str) -> Result<(), String> {
+    let port = value
+        .parse::<u16>()
+        .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+    target.store(port, Ordering::Relaxed);
+    Ok(())
+}
+
+fn set_string(target: &RwLock<String>, value: &str) -> Result<(), String> {
+    *target.write().unwrap() = value.to_string();
+    Ok(())
+}
+
+fn format_bool(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err("argument must be 'yes' or 'no'".to_string()),
+    }
+}
+
+const PARAMS: &[ConfigParam] = &[
+    ConfigParam {
+        name: "hash-max-listpack-entries",
+        get: || {
+            ENCODING_CONFIG
+                .hash_max_listpack_entries
+                .load(Ordering::Relaxed)
+                .to_string()
+        },
+        set: |v| set_usize(&ENCODING_CONFIG.hash_max_listpack_entries, v),
+    },
+    ConfigParam {
+        name: "hash-max-listpack-value",
+        get: || {
+            ENCODING_CONFIG
+                .hash_max_listpack_value
+                .load(Ordering::Relaxed)
+                .to_string()
+        },
+        set: |v| set_usize(&ENCODING_CONFIG.hash_max_listpack_value, v),
+    },
+    ConfigParam {
+        name: "set-max-intset-entries",
+        get: || {
+            ENCODING_CONFIG
+                .set_max_intset_entries
+                .load(Ordering::Relaxed)
+                .to_string()
+        },
+        set: |v| set_usize(&ENCODING_CONFIG.set_max_intset_entries, v),
+    },
+    ConfigParam {
+        name: "set-max-listpack-entries",
+        get: || {
+            ENCODING_CONFIG
+                .set_max_listpack_entries
+                .load(Ordering::Relaxed)
+                .to_string()
+        },
+        set: |v| set_usize(&ENCODING_CONFIG.set_max_listpack_entries, v),
+    },
+    ConfigParam {
+        name: "set-max-listpack-value",
+        get: || {
+            ENCODING_CONFIG
+                .set_max_listpack_value
+                .load(Ordering::Relaxed)
+                .to_string()
+        },
+        set: |v| set_usize(&ENCODING_CONFIG.set_max_listpack_value, v),
+    },
+    ConfigParam {
+        name: "list-max-listpack-size",
+        get: || {
+            ENCODING_CONFIG
+                .list_max_listpack_size
+                .load(Ordering::Relaxed)
+                .to_string()
+        },
+        set: |v| {
+            let n = v
+                .parse::<i64>()
+                .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+            ENCODING_CONFIG
+                .list_max_listpack_size
+                .store(n, Ordering::Relaxed);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "client-output-buffer-limit",
+        get: || {
+            format!(
+                "pubsub {} {} {}",
+                PUBSUB_OUTPUT_LIMIT.hard.load(Ordering::Relaxed),
+                PUBSUB_OUTPUT_LIMIT.soft.load(Ordering::Relaxed),
+                PUBSUB_OUTPUT_LIMIT.soft_seconds.load(Ordering::Relaxed)
+            )
+        },
+        set: set_output_buffer_limit,
+    },
+    ConfigParam {
+        name: "notify-keyspace-events",
+        get: || format_notify_flags(NOTIFY_KEYSPACE_EVENTS.load(Ordering::Relaxed)),
+        set: |v| {
+            let flags = parse_notify_flags(v).ok_or_else(|| {
+                "Invalid event class character. Use 'Ag$lshzxeKEtmn'.".to_string()
+            })?;
+            NOTIFY_KEYSPACE_EVENTS.store(flags, Ordering::Relaxed);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "requirepass",
+        get: auth::requirepass,
+        set: |v| {
+            auth::set_requirepass(v);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "aclfile",
+        get: || ACLFILE.read().unwrap().clone(),
+        set: |v| set_string(&ACLFILE, v),
+    },
+    ConfigParam {
+        name: "acllog-max-len",
+        get: || ACLLOG_MAX_LEN.load(Ordering::Relaxed).to_string(),
+        set: |v| set_usize(&ACLLOG_MAX_LEN, v),
+    },
+    // 监听与 TLS 配置只在启动时生效
+    ConfigParam {
+        name: "bind",
+        get: || LISTEN_CONFIG.bind(),
+        set: |v| set_string(&LISTEN_CONFIG.bind, v),
+    },
+    ConfigParam {
+        name: "port",
+        get: || LISTEN_CONFIG.port().to_string(),
+        set: |v| set_port(&LISTEN_CONFIG.port, v),
+    },
+    ConfigParam {
+        name: "unixsocket",
+        get: || LISTEN_CONFIG.unixsocket(),
+        set: |v| set_string(&LISTEN_CONFIG.unixsocket, v),
+    },
+    ConfigParam {
+        name: "unixsocketperm",
+        get: || format!("{:o}", LISTEN_CONFIG.unixsocketperm()),
+        set: |v| {
+            let perm = u32::from_str_radix(v, 8)
+                .ok()
+                .filter(|perm| *perm <= 0o777)
+                .ok_or_else(|| "argument must be an octal permission like 700".to_string())?;
+            LISTEN_CONFIG.unixsocketperm.store(perm, Ordering::Relaxed);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "metrics-port",
+        get: || LISTEN_CONFIG.metrics_port().to_string(),
+        set: |v| set_port(&LISTEN_CONFIG.metrics_port, v),
+    },
+    ConfigParam {
+        name: "tls-port",
+        get: || TLS_CONFIG.port().to_string(),
+        set: |v| set_port(&TLS_CONFIG.port, v),
+    },
+    ConfigParam {
+        name: "tls-cert-file",
+        get: || TLS_CONFIG.cert_file.read().unwrap().clone(),
+        set: |v| set_string(&TLS_CONFIG.cert_file, v),
+    },
+    ConfigParam {
+        name: "tls-key-file",
+        get: || TLS_CONFIG.key_file.read().unwrap().clone(),
+        set: |v| set_string(&TLS_CONFIG.key_file, v),
+    },
+    ConfigParam {
+        name: "tls-ca-cert-file",
+        get: || TLS_CONFIG.ca_cert_file.read().unwrap().clone(),
+        set: |v| set_string(&TLS_CONFIG.ca_cert_file, v),
+    },
+    ConfigParam {
+        name: "tls-auth-clients",
+        get: || TLS_CONFIG.auth_clients().to_string(),
+        set: |v| TLS_CONFIG.set_auth_clients(v),
+    },
+    ConfigParam {
+        name: "protected-mode",
+        get: || format_bool(auth::protected_mode()),
+        set: |v| {
+            PROTECTED_MODE.store(parse_bool(v)?, Ordering::Relaxed);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "maxmemory",
+        get: || MAXMEMORY.load(Ordering::Relaxed).to_string(),
+        set: |v| {
+            let bytes =
+                parse_memory(v).ok_or_else(|| "argument must be a memory value".to_string())?;
+            MAXMEMORY.store(bytes, Ordering::Relaxed);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "maxmemory-policy",
+        get: || EVICTION_CONFIG.policy().name().to_string(),
+        set: |v| EVICTION_CONFIG.set_policy(v),
+    },
+    ConfigParam {
+        name: "maxmemory-samples",
+        get: || EVICTION_CONFIG.samples.load(Ordering::Relaxed).to_string(),
+        set: |v| match v.parse::<usize>() {
+            Ok(n @ 1..=64) => {
+                EVICTION_CONFIG.samples.store(n, Ordering::Relaxed);
+                Ok(())
+            }
+            _ => Err("argument must be between 1 and 64 inclusive".to_string()),
+        },
+    },
+    ConfigParam {
+        name: "slowlog-log-slower-than",
+        get: || SLOWLOG_LOG_SLOWER_THAN.load(Ordering::Relaxed).to_string(),
+        set: |v| {
+            let n = v
+                .parse::<i64>()
+                .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+            SLOWLOG_LOG_SLOWER_THAN.store(n, Ordering::Relaxed);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "slowlog-max-len",
+        get: || SLOWLOG_MAX_LEN.load(Ordering::Relaxed).to_string(),
+        set: |v| set_usize(&SLOWLOG_MAX_LEN, v),
+    },
+    ConfigParam {
+        name: "latency-monitor-threshold",
+        get: || {
+            LATENCY_MONITOR_THRESHOLD
+                .load(Ordering::Relaxed)
+                .to_string()
+        },
+        set: |v| set_u64(&LATENCY_MONITOR_THRESHOLD, v),
+    },
+    ConfigParam {
+        name: "lfu-log-factor",
+        get: || {
+            EVICTION_CONFIG
+                .lfu_log_factor
+                .load(Ordering::Relaxed)
+                .to_string()
+        },
+        set: |v| set_u64(&EVICTION_CONFIG.lfu_log_factor, v),
+    },
+    ConfigParam {
+        name: "lfu-decay-time",
+        get: || {
+            EVICTION_CONFIG
+                .lfu_decay_time
+                .load(Ordering::Relaxed)
+                .to_string()
+        },
+        set: |v| set_u64(&EVICTION_CONFIG.lfu_decay_time, v),
+    },
+    ConfigParam {
+        name: "timeout",
+        get: || SERVER_CONFIG.timeout.load(Ordering::Relaxed).to_string(),
+        set: |v| set_u64(&SERVER_CONFIG.timeout, v),
+    },
+    ConfigParam {
+        name: "maxclients",
+        get: || SERVER_CONFIG.maxclients.load(Ordering::Relaxed).to_string(),
+        set: |v| {
+            // 为 0 时会拒绝所有连接
+            match v.parse::<usize>() {
+                Ok(n) if n >= 1 => {
+                    SERVER_CONFIG.maxclients.store(n, Ordering::Relaxed);
+                    Ok(())
+                }
+                _ => Err("argument must be between 1 and 2147483647 inclusive".to_string()),
+            }
+        },
+    },
+    ConfigParam {
+        name: "proto-max-bulk-len",
+        get: || PROTO_MAX_BULK_LEN.load(Ordering::Relaxed).to_string(),
+        set: |v| {
+            let bytes =
+                parse_memory(v).ok_or_else(|| "argument must be a memory value".to_string())?;
+            if bytes < 1024 * 1024 {
+                return Err("argument must be 1mb or greater".to_string());
+            }
+            PROTO_MAX_BULK_LEN.store(bytes, Ordering::Relaxed);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tcp-keepalive",
+        get: || {
+            SERVER_CONFIG
+                .tcp_keepalive
+                .load(Ordering::Relaxed)
+                .to_string()
+        },
+        set: |v| set_u64(&SERVER_CONFIG.tcp_keepalive, v),
+    },
+    ConfigParam {
+        name: "pidfile",
+        get: || SERVER_CONFIG.pidfile(),
+        set: |v| set_string(&SERVER_CONFIG.pidfile, v),
+    },
+    ConfigParam {
+        name: "hz",
+        get: || SERVER_CONFIG.hz().to_string(),
+        set: |v| SERVER_CONFIG.set_hz(v),
+    },
+    ConfigParam {
+        name: "loglevel",
+        get: || loglevel().to_string(),
+        set: set_loglevel,
+    },
+    ConfigParam {
+        name: "databases",
+        get: || SERVER_CONFIG.databases.load(Ordering::Relaxed).to_string(),
+        set: |v| {
+            let n =
+                v.parse::<u32>().ok().filter(|n| *n >= 1).ok_or_else(|| {
+                    "argument must be between 1 and 2147483647 inclusive".to_string()
+                })?;
+            SERVER_CONFIG.databases.store(n, Ordering::Relaxed);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "save",
+        get: || SERVER_CONFIG.save.read().unwrap().clone(),
+        set: |v| {
+            // "<seconds> <changes>" 成对出现，空字符串表示关闭
+            let words: Vec<&str> = v.split_whitespace().collect();
+            if !words.len().is_multiple_of(2) || words.iter().any(|w| w.parse::<u64>().is_err()) {
+                return Err("Invalid save parameters".to_string());
+            }
+            set_string(&SERVER_CONFIG.save, &words.join(" "))
+        },
+    },
+    ConfigParam {
+        name: "appendonly",
+        get: || format_bool(SERVER_CONFIG.appendonly.load(Ordering::Relaxed)),
+        set: |v| {
+            SERVER_CONFIG
+                .appendonly
+                .store(parse_bool(v)?, Ordering::Relaxed);
+            Ok(())
+        },
+    },
+];
+
+/// 只能在配置文件或启动参数中设置的配置项
+const IMMUTABLE: &[&str] = &[
+    "bind",
+    "port",
+    "unixsocket",
+    "unixsocketperm",
+    "metrics-port",
+    "tls-port",
+    "tls-cert-file",
+    "tls-key-file",
+    "tls-ca-cert-file",
+    "tls-auth-clients",
+    "aclfile",
+    "databases",
+    "pidfile",
+];
+
+/// 值由多个参数组成，写回配置文件时不加引号
+const MULTI_ARG: &[&str] = &["bind", "save", "client-output-buffer-limit"];
+
+/// 启动时加载的配置文件，CONFIG REWRITE 写回这里
+static CONFIG_FILE: RwLock<String> = RwLock::new(String::new());
+
+/// 启动时加载的配置文件的绝对路径，没有时为空
+pub fn config_file() -> String {
+    CONFIG_FILE.read().unwrap().clone()
+}
+
+/// 加载配置文件之前的默认值，CONFIG REWRITE 不写出仍为默认值的配置
+static DEFAULTS: LazyLock<Vec<String>> =
+    LazyLock::new(|| PARAMS.iter().map(|p| (p.get)()).collect());
+
+fn find_param(name: &str) -> Option<&'static ConfigParam> {
+    let name = name.to_lowercase();
+    PARAMS.iter().find(|p| p.name == name)
+}
+
+/// 启动参数 --name value 与 CONFIG SET 使用同一组配置项
+pub fn set_config(name: &str, value: &str) -> Result<(), String> {
+    LazyLock::force(&DEFAULTS);
+    let param = find_param(name)
+        .ok_or_else(|| format!("Bad directive or wrong number of arguments: '{}'", name))?;
+    (param.set)(value)
+}
+
+/// 加载 redis.conf 格式的配置文件，不支持的指令只给出警告
+pub fn load_config(path: &str) -> Result<(), String> {
+    LazyLock::force(&DEFAULTS);
+    let mut save: Option<Vec<String>> = None;
+    for directive in config_file::read(path)? {
+        // 多行 save 合并为一个值
+        if directive.name == "save" {
+            save.get_or_insert_with(Vec::new).extend(directive.args);
+            continue;
+        }
+        let Some(param) = find_param(&directive.name) else {
+            log!(
+                Warning,
+                "{}: unsupported directive '{}' ignored",
+                directive.location,
+                directive.name
+            );
+            continue;
+        };
+        (param.set)(&directive.args.join(" "))
+            .map_err(|e| format!("{}: '{}': {}", directive.location, directive.name, e))?;
+    }
+    if let Some(save) = save {
+        set_config("save", &save.join(" "))?;
+    }
+    let path = std::path::absolute(path).map_err(|e| format!("{}: {}", path, e))?;
+    *CONFIG_FILE.write().unwrap() = path.to_string_lossy().into_owned();
+    Ok(())
+}
+
+/// 格式为 "<class> <hard> <soft> <soft seconds>"，目前只有 pubsub 类别生效
+fn set_output_buffer_limit(value: &str) -> Result<(), String> {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    if words.is_empty() || !words.len().is_multiple_of(4) {
+        return Err("Wrong number of arguments in buffer limit configuration.".to_string());
+    }
+    let mut limits = Vec::new();
+    for group in words.chunks(4) {
+        let class = group[0].to_lowercase();
+        if !matches!(class.as_str(), "pubsub" | "normal" | "replica" | "slave") {
+            return Err(
+                "Invalid client class specified in buffer limit configuration.".to_string(),
+            );
+        }
+        let (Some(hard), Some(soft), Ok(seconds)) = (
+            parse_memory(group[1]),
+            parse_memory(group[2]),
+            group[3].parse::<u64>(),
+        ) else {
+            return Err(
+                "Error in hard, soft or soft_seconds setting in buffer limit configuration."
+                    .to_string(),
+            );
+        };
+        // 普通客户端不限制，也没有副本，这两类只为兼容 redis.conf 而接受
+        if class == "pubsub" {
+            limits.push((hard as usize, soft as usize, seconds));
+        }
+    }
+    for (hard, soft, seconds) in limits {
+        PUBSUB_OUTPUT_LIMIT.hard.store(hard, Ordering::Relaxed);
+        PUBSUB_OUTPUT_LIMIT.soft.store(soft, Ordering::Relaxed);
+        PUBSUB_OUTPUT_LIMIT
+            .soft_seconds
+            .store(seconds, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+pub struct HandleConfig;
+impl HandleConfig {
+    pub fn handle_config(command: Command) -> RespValue {
+        if command.args.is_empty() {
+            return RespErrArgNum!();
+        }
+        match command.args[0].to_uppercase().as_str() {
+            "GET" if command.args.len() >= 2 => Self::config_get(&command.args[1..]),
+            "SET" if command.args.len() >= 3 && command.args.len() % 2 == 1 => {
+                Self::config_set(&command.args[1..])
+            }
+            "REWRITE" if command.args.len() == 1 => Self::config_rewrite(),
+            "RESETSTAT" if command.args.len() == 1 => {
+                STATS.reset();
+                table::reset_stats();
+                RespOK!()
+            }
+            _ => RespValue::Error(format!(
+                "ERR unknown subcommand or wrong number of arguments for '{}'. Try CONFIG HELP.",
+                command.args[0]
+            )),
+        }
+    }
+
+    fn config_get(patterns: &[String]) -> RespValue {
+        let mut result = Vec::new();
+        for param in PARAMS {
+            if patterns
+                .iter()
+                .any(|p| glob_match(&p.to_lowercase(), param.name))
+            {
+                result.push((
+                    RespValue::BulkString(Some(param.name.to_string())),
+                    RespValue::BulkString(Some((param.get)())),
+                ));
+            }
+        }
+        RespValue::Map(result)
+    }
+
+    /// 先检查所有配置项，再依次设置；某个值不合法时恢复已设置的配置项，保证整体生效或整体失败
+    fn config_set(pairs: &[String]) -> RespValue {
+        let failed = |name: &str, reason: &str| {
+            RespValue::Error(format!(
+                "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                name, reason
+            ))
+        };
+        let mut params: Vec<(&ConfigParam, &str, &str)> = Vec::new();
+        for pair in pairs.chunks(2) {
+            let Some(param) = find_param(&pair[0]) else {
+                return RespValue::Error(format!(
+                    "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                    pair[0]
+                ));
+            };
+            if IMMUTABLE.contains(&param.name) {
+                return failed(&pair[0], "can't set immutable config");
+            }
+            if params.iter().any(|(p, _, _)| p.name == param.name) {
+                return failed(&pair[0], "duplicate parameter");
+            }
+            params.push((param, &pair[0], &pair[1]));
+        }
+        let default_user = auth::save_default_user();
+        let mut applied: Vec<(&ConfigParam, String)> = Vec::new();
+        for (param, name, value) in params {
+            let old = (param.get)();
+            if let Err(reason) = (param.set)(value) {
+                for (param, old) in applied.iter().rev() {
+                    // 重新设置 requirepass 会覆盖 default 用户通过 ACL 设置的密码
+                    if param.name == "requirepass" {
+                        auth::restore_default_user(&default_user);
+                    } else {
+                        let _ = (param.set)(old);
+                    }
+                }
+                return failed(name, &reason);
+            }
+            applied.push((param, old));
+        }
+        RespOK!()
+    }
+
+    /// 把当前配置写回启动时加载的配置文件
+    fn config_rewrite() -> RespValue {
+        let path = CONFIG_FILE.read().unwrap().clone();
+        if path.is_empty() {
+            return RespValue::Error("ERR The server is running without a config file".to_string());
+        }
+        let values: Vec<(&str, String, bool)> = PARAMS
+            .iter()
+            .zip(DEFAULTS.iter())
+            .map(|(param, default)| {
+                let value = (param.get)();
+                let is_default = value == *default;
+                let value = if MULTI_ARG.contains(&param.name) && !value.is_empty() {
+                    value
+                } else {
+                    config_file::quote(&value)
+                };
+                (param.name, value, is_default)
+            })
+            .collect();
+        match config_file::rewrite(&path, &values) {
+            Ok(()) => RespOK!(),
+            Err(e) => RespValue::Error(format!("ERR Rewriting config file: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn f1_config_set_all_or_nothing() {
+        let hz = SERVER_CONFIG.hz();
+        let other = (hz % 100 + 1).to_string();
+        let result = HandleConfig::config_set(&args(&["hz", &other, "maxmemory-policy", "bogus"]));
+        assert!(matches!(result, RespValue::Error(e) if e.contains("'maxmemory-policy'")));
+        assert_eq!(SERVER_CONFIG.hz(), hz);
+
+        let result = HandleConfig::config_set(&args(&["hz", &other, "HZ", &other]));
+        assert!(matches!(result, RespValue::Error(e) if e.contains("duplicate parameter")));
+        assert_eq!(SERVER_CONFIG.hz(), hz);
+    }
+
+    #[test]
+    fn f2_maxclients() {
+        for value in ["0", "-1", "many"] {
+            let result = HandleConfig::config_set(&args(&["maxclients", value]));
+            assert!(matches!(result, RespValue::Error(e) if e.contains("between 1 and")));
+        }
+    }
+
+    #[test]
+    fn f3_requirepass_rollback() {
+        let _serial = crate::server::SERIAL.blocking_lock();
+        let rules = args(&["resetpass", ">a", ">b"]);
+        crate::command::acl::ACL
+            .write()
+            .unwrap()
+            .set_user("default", &rules)
+            .unwrap();
+        let result =
+            HandleConfig::config_set(&args(&["requirepass", "c", "maxmemory-policy", "bogus"]));
+        assert!(matches!(result, RespValue::Error(_)));
+        {
+            let acl = crate::command::acl::ACL.read().unwrap();
+            let user = acl.user("default").unwrap();
+            assert!(user.check_password("a") && user.check_password("b"));
+            assert!(!user.check_password("c"));
+        }
+        assert_eq!(auth::requirepass(), "");
+        auth::set_requirepass("");
+    }
+}
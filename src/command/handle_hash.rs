@@ -1,44 +1,109 @@
-use std::collections::HashMap;
-
 use crate::command::Command;
 use crate::command::handle_macro::*;
+use crate::storage::{HashValue, NotifyClass, now_millis};
+
+// HEXPIRE 系列命令的单字段返回值
+const FIELD_NOT_FOUND: i64 = -2;
+const FIELD_NO_EXPIRE: i64 = -1;
+const FIELD_CONDITION_NOT_MET: i64 = 0;
+const FIELD_UPDATED: i64 = 1;
+const FIELD_DELETED: i64 = 2;
+/// 字段过期时间（毫秒时间戳）的上限
+const FIELD_EXPIRE_MAX: u128 = 1 << 48;
+
+#[derive(Clone, Copy)]
+enum ExpireCondition {
+    Always,
+    Nx,
+    Xx,
+    Gt,
+    Lt,
+}
+
+#[derive(Clone, Copy)]
+enum GetExOption {
+    Keep,
+    Expire(u128),
+    Persist,
+}
+
+/// 解析 `FIELDS numfields field [field ...]`，必须位于参数末尾
+fn parse_fields(args: &[String]) -> Result<&[String], RespValue> {
+    if args.len() < 2 || !args[0].eq_ignore_ascii_case("FIELDS") {
+        return Err(RespValue::Error(
+            "ERR Mandatory argument FIELDS is missing or not at the right position".to_string(),
+        ));
+    }
+    match args[1].parse::<i64>() {
+        Ok(n) if n > 0 => {
+            if n as usize == args.len() - 2 {
+                Ok(&args[2..])
+            } else {
+                Err(RespValue::Error(
+                    "ERR The `numfields` parameter must match the number of arguments".to_string(),
+                ))
+            }
+        }
+        Ok(_) => Err(RespValue::Error(
+            "ERR Parameter `numFields` should be greater than 0".to_string(),
+        )),
+        Err(_) => Err(RespErrNumWrong!()),
+    }
+}
 
 pub struct HandleHash;
 impl HandleHash {
-    pub async fn handle_hset(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_hset(db: &mut Database, command: Command) -> RespValue {
         let len = command.args.len();
         if len % 2 == 1 && len != 1 {
             let key = command.args[0].clone();
-            let mut db_guard = db.lock().await;
-            match db_guard.get(&key) {
-                None | Some(Value::Hash(_)) => {
-                    // 新建或覆盖原有表
-                    let mut hashmap = HashMap::new();
+            db.expire_fields(&key);
+            let mut num = 0;
+            match db.get_mut(&key) {
+                Some(Value::Hash(hashmap)) => {
+                    for i in (1..len).step_by(2) {
+                        if hashmap
+                            .insert(command.args[i].clone(), command.args[i + 1].clone())
+                            .is_none()
+                        {
+                            num += 1;
+                        }
+                    }
+                    db.modified(&key);
+                }
+                None => {
+                    let mut hashmap = HashValue::new();
                     for i in (1..len).step_by(2) {
                         hashmap.insert(command.args[i].clone(), command.args[i + 1].clone());
                     }
-                    db_guard.set(key, Value::Hash(hashmap));
-                    RespValue::Integer((len as i64) / 2)
+                    num = hashmap.len() as i64;
+                    db.set(key.clone(), Value::Hash(hashmap));
                 }
                 _ => {
                     // 被其他类型占据，直接返回错误
-                    RespErrType!()
+                    return RespErrType!();
                 }
             }
+            // 覆盖写入的字段不再保留过期时间
+            for i in (1..len).step_by(2) {
+                db.persist_field(&key, &command.args[i]);
+            }
+            db.notify(NotifyClass::Hash, "hset", &key);
+            RespValue::Integer(num)
         } else {
             RespErrArgNum!()
         }
     }
 
-    pub async fn handle_hget(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_hget(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 2 {
-            let db_guard = db.lock().await;
-            let map = db_guard.get(&command.args[0]);
+            db.expire_fields(&command.args[0]);
+            let map = db.get(&command.args[0]);
             match map {
                 Some(Value::Hash(map)) => {
                     let v = map.get(&command.args[1]);
                     if let Some(v) = v {
-                        RespValue::BulkString(Some(v.clone()))
+                        RespValue::BulkString(Some(v.to_string()))
                     } else {
                         RespValue::Null
                     }
@@ -51,17 +116,17 @@ impl HandleHash {
         }
     }
 
-    pub async fn handle_hgetall(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_hgetall(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 1 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            db.expire_fields(&command.args[0]);
+            match db.get(&command.args[0]) {
                 None => RespValue::Array(Vec::new()),
                 Some(Value::Hash(hashmap)) => {
                     let mut vec: Vec<RespValue> = Vec::with_capacity(hashmap.len() * 2);
-                    for (k, v) in hashmap {
+                    for (k, v) in hashmap.iter() {
                         vec.extend([
-                            RespValue::BulkString(Some(k.clone())),
-                            RespValue::BulkString(Some(v.clone())),
+                            RespValue::BulkString(Some(k.to_string())),
+                            RespValue::BulkString(Some(v.to_string())),
                         ]);
                     }
                     RespValue::Array(vec)
@@ -73,10 +138,11 @@ impl HandleHash {
         }
     }
 
-    pub async fn handle_hdel(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_hdel(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() >= 2 {
-            let mut db_guard = db.lock().await;
-            match db_guard.get_mut(&command.args[0]) {
+            let key = &command.args[0];
+            db.expire_fields(key);
+            let (num, empty) = match db.get_mut(key) {
                 Some(Value::Hash(hashmap)) => {
                     let mut num = 0;
                     for k in command.args.iter().skip(1) {
@@ -84,20 +150,32 @@ impl HandleHash {
                             num += 1;
                         }
                     }
-                    RespValue::Integer(num)
+                    (num, hashmap.is_empty())
                 }
-                Some(_) => RespErrType!(),
-                None => RespValue::Integer(0),
+                Some(_) => return RespErrType!(),
+                None => return RespValue::Integer(0),
+            };
+            if num > 0 {
+                db.modified(key);
+                db.notify(NotifyClass::Hash, "hdel", key);
             }
+            if empty {
+                db.del(key);
+            } else {
+                for k in command.args.iter().skip(1) {
+                    db.persist_field(key, k);
+                }
+            }
+            RespValue::Integer(num)
         } else {
             RespErrArgNum!()
         }
     }
 
-    pub async fn handle_hexists(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_hexists(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 2 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            db.expire_fields(&command.args[0]);
+            match db.get(&command.args[0]) {
                 Some(Value::Hash(hashmap)) => {
                     let num = hashmap.contains_key(&command.args[1]) as i64;
                     RespValue::Integer(num)
@@ -110,10 +188,10 @@ impl HandleHash {
         }
     }
 
-    pub async fn handle_hlen(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_hlen(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 1 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            db.expire_fields(&command.args[0]);
+            match db.get(&command.args[0]) {
                 Some(Value::Hash(hashmap)) => RespValue::Integer(hashmap.len() as i64),
                 Some(_) => RespErrType!(),
                 None => RespValue::Integer(0),
@@ -123,14 +201,14 @@ impl HandleHash {
         }
     }
 
-    pub async fn handle_hkeys(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_hkeys(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 1 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            db.expire_fields(&command.args[0]);
+            match db.get(&command.args[0]) {
                 Some(Value::Hash(hashmap)) => {
                     let vec = hashmap
                         .keys()
-                        .map(|k| RespValue::BulkString(Some(k.clone())))
+                        .map(|k| RespValue::BulkString(Some(k.to_string())))
                         .collect::<Vec<_>>();
                     RespValue::Array(vec)
                 }
@@ -142,14 +220,14 @@ impl HandleHash {
         }
     }
 
-    pub async fn handle_hvals(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_hvals(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 1 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            db.expire_fields(&command.args[0]);
+            match db.get(&command.args[0]) {
                 Some(Value::Hash(hashmap)) => {
                     let vec = hashmap
                         .values()
-                        .map(|k| RespValue::BulkString(Some(k.clone())))
+                        .map(|k| RespValue::BulkString(Some(k.to_string())))
                         .collect::<Vec<_>>();
                     RespValue::Array(vec)
                 }
@@ -160,4 +238,380 @@ impl HandleHash {
             RespErrArgNum!()
         }
     }
+
+    pub fn handle_hexpire(db: &mut Database, command: Command) -> RespValue {
+        Self::expire_generic(db, command, 1000, false)
+    }
+
+    pub fn handle_hpexpire(db: &mut Database, command: Command) -> RespValue {
+        Self::expire_generic(db, command, 1, false)
+    }
+
+    pub fn handle_hexpireat(db: &mut Database, command: Command) -> RespValue {
+        Self::expire_generic(db, command, 1000, true)
+    }
+
+    pub fn handle_hpexpireat(db: &mut Database, command: Command) -> RespValue {
+        Self::expire_generic(db, command, 1, true)
+    }
+
+    pub fn handle_httl(db: &mut Database, command: Command) -> RespValue {
+        Self::ttl_generic(db, command, 1000, false)
+    }
+
+    pub fn handle_hpttl(db: &mut Database, command: Command) -> RespValue {
+        Self::ttl_generic(db, command, 1, false)
+    }
+
+    pub fn handle_hexpiretime(db: &mut Database, command: Command) -> RespValue {
+        Self::ttl_generic(db, command, 1000, true)
+    }
+
+    pub fn handle_hpexpiretime(db: &mut Database, command: Command) -> RespValue {
+        Self::ttl_generic(db, command, 1, true)
+    }
+
+    pub fn handle_hpersist(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() < 3 {
+            return RespErrArgNum!();
+        }
+        let fields = match parse_fields(&command.args[1..]) {
+            Ok(fields) => fields,
+            Err(e) => return e,
+        };
+        let key = &command.args[0];
+        db.expire_fields(key);
+        let hashmap = match db.get(key) {
+            Some(Value::Hash(hashmap)) => hashmap,
+            Some(_) => return RespErrType!(),
+            None => {
+                return RespValue::Array(vec![RespValue::Integer(FIELD_NOT_FOUND); fields.len()]);
+            }
+        };
+        let present: Vec<bool> = fields.iter().map(|f| hashmap.contains_key(f)).collect();
+        let result: Vec<i64> = fields
+            .iter()
+            .zip(present)
+            .map(|(f, present)| {
+                if !present {
+                    FIELD_NOT_FOUND
+                } else if db.persist_field(key, f) {
+                    FIELD_UPDATED
+                } else {
+                    FIELD_NO_EXPIRE
+                }
+            })
+            .collect();
+        if result.contains(&FIELD_UPDATED) {
+            db.notify(NotifyClass::Hash, "hpersist", key);
+        }
+        RespValue::Array(result.into_iter().map(RespValue::Integer).collect())
+    }
+
+    pub fn handle_hgetex(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() < 3 {
+            return RespErrArgNum!();
+        }
+        let key = &command.args[0];
+        let mut idx = 1;
+        let mut option = GetExOption::Keep;
+        let opt = command.args[idx].to_uppercase();
+        match opt.as_str() {
+            "EX" | "PX" | "EXAT" | "PXAT" => {
+                let Some(Ok(time)) = command.args.get(idx + 1).map(|t| t.parse::<u64>()) else {
+                    return RespErrNumWrong!();
+                };
+                // 与 Redis 一致，过期时间必须为正数
+                if time == 0 {
+                    return RespValue::Error(
+                        "ERR invalid expire time in 'hgetex' command".to_string(),
+                    );
+                }
+                let time = time as u128;
+                let expire_time = match opt.as_str() {
+                    "EX" => now_millis() + time * 1000,
+                    "PX" => now_millis() + time,
+                    "EXAT" => time * 1000,
+                    _ => time,
+                };
+                if expire_time > FIELD_EXPIRE_MAX {
+                    return RespValue::Error(
+                        "ERR invalid expire time in 'hgetex' command".to_string(),
+                    );
+                }
+                option = GetExOption::Expire(expire_time);
+                idx += 2;
+            }
+            "PERSIST" => {
+                option = GetExOption::Persist;
+                idx += 1;
+            }
+            _ => {}
+        }
+        let fields = match parse_fields(&command.args[idx..]) {
+            Ok(fields) => fields,
+            Err(e) => return e,
+        };
+
+        db.expire_fields(key);
+        let values: Vec<Option<String>> = match db.get(key) {
+            Some(Value::Hash(hashmap)) => fields
+                .iter()
+                .map(|f| hashmap.get(f).map(str::to_string))
+                .collect(),
+            Some(_) => return RespErrType!(),
+            None => return RespValue::Array(vec![RespValue::Null; fields.len()]),
+        };
+        let mut changed = false;
+        for (f, v) in fields.iter().zip(&values) {
+            if v.is_none() {
+                continue;
+            }
+            match option {
+                GetExOption::Keep => {}
+                GetExOption::Persist => {
+                    changed |= db.persist_field(key, f);
+                }
+                GetExOption::Expire(expire_time) => {
+                    db.set_field_expire(key, f, expire_time);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            let event = match option {
+                GetExOption::Persist => "hpersist",
+                _ => "hexpire",
+            };
+            db.notify(NotifyClass::Hash, event, key);
+        }
+        // 过期时间已经过去的字段立即删除
+        db.expire_fields(key);
+        RespValue::Array(
+            values
+                .into_iter()
+                .map(|v| match v {
+                    Some(v) => RespValue::BulkString(Some(v)),
+                    None => RespValue::Null,
+                })
+                .collect(),
+        )
+    }
+
+    fn expire_generic(
+        db: &mut Database,
+        command: Command,
+        unit: u128,
+        absolute: bool,
+    ) -> RespValue {
+        if command.args.len() < 4 {
+            return RespErrArgNum!();
+        }
+        let key = &command.args[0];
+        let Ok(time) = command.args[1].parse::<i64>() else {
+            return RespErrNumWrong!();
+        };
+        // 换算为毫秒时间戳之后不能超过 2^48
+        let now = now_millis();
+        let expire_time = time.max(0) as u128 * unit + if absolute { 0 } else { now };
+        if time < 0 || expire_time > FIELD_EXPIRE_MAX {
+            return RespValue::Error(
+                "ERR invalid expire time, must be >= 0 and <= 2^48".to_string(),
+            );
+        }
+        let mut idx = 2;
+        let condition = match command.args[idx].to_uppercase().as_str() {
+            "NX" => ExpireCondition::Nx,
+            "XX" => ExpireCondition::Xx,
+            "GT" => ExpireCondition::Gt,
+            "LT" => ExpireCondition::Lt,
+            _ => ExpireCondition::Always,
+        };
+        if !matches!(condition, ExpireCondition::Always) {
+            idx += 1;
+        }
+        let fields = match parse_fields(&command.args[idx..]) {
+            Ok(fields) => fields,
+            Err(e) => return e,
+        };
+
+        db.expire_fields(key);
+        let present: Vec<bool> = match db.get(key) {
+            Some(Value::Hash(hashmap)) => fields.iter().map(|f| hashmap.contains_key(f)).collect(),
+            Some(_) => return RespErrType!(),
+            None => {
+                return RespValue::Array(vec![RespValue::Integer(FIELD_NOT_FOUND); fields.len()]);
+            }
+        };
+
+        let mut result = Vec::with_capacity(fields.len());
+        for (f, present) in fields.iter().zip(present) {
+            if !present {
+                result.push(RespValue::Integer(FIELD_NOT_FOUND));
+                continue;
+            }
+            let current = db.get_field_expire(key, f);
+            // 没有过期时间视为无限长
+            let allowed = match condition {
+                ExpireCondition::Always => true,
+                ExpireCondition::Nx => current.is_none(),
+                ExpireCondition::Xx => current.is_some(),
+                ExpireCondition::Gt => current.is_some_and(|t| expire_time > t),
+                ExpireCondition::Lt => current.is_none_or(|t| expire_time < t),
+            };
+            if !allowed {
+                result.push(RespValue::Integer(FIELD_CONDITION_NOT_MET));
+            } else if expire_time <= now {
+                if let Some(Value::Hash(hashmap)) = db.get_mut(key) {
+                    hashmap.remove(f);
+                    db.modified(key);
+                }
+                db.persist_field(key, f);
+                result.push(RespValue::Integer(FIELD_DELETED));
+            } else {
+                db.set_field_expire(key, f, expire_time);
+                result.push(RespValue::Integer(FIELD_UPDATED));
+            }
+        }
+        if result.contains(&RespValue::Integer(FIELD_UPDATED)) {
+            db.notify(NotifyClass::Hash, "hexpire", key);
+        }
+        if result.contains(&RespValue::Integer(FIELD_DELETED)) {
+            db.notify(NotifyClass::Hash, "hdel", key);
+        }
+        if let Some(Value::Hash(hashmap)) = db.get(key)
+            && hashmap.is_empty()
+        {
+            db.del(key);
+        }
+        RespValue::Array(result)
+    }
+
+    fn ttl_generic(db: &mut Database, command: Command, unit: u128, absolute: bool) -> RespValue {
+        if command.args.len() < 3 {
+            return RespErrArgNum!();
+        }
+        let fields = match parse_fields(&command.args[1..]) {
+            Ok(fields) => fields,
+            Err(e) => return e,
+        };
+        let key = &command.args[0];
+        db.expire_fields(key);
+        let hashmap = match db.get(key) {
+            Some(Value::Hash(hashmap)) => hashmap,
+            Some(_) => return RespErrType!(),
+            None => {
+                return RespValue::Array(vec![RespValue::Integer(FIELD_NOT_FOUND); fields.len()]);
+            }
+        };
+        let now = now_millis();
+        let result = fields
+            .iter()
+            .map(|f| {
+                if !hashmap.contains_key(f) {
+                    return FIELD_NOT_FOUND;
+                }
+                match db.get_field_expire(key, f) {
+                    None => FIELD_NO_EXPIRE,
+                    Some(t) if absolute => (t / unit) as i64,
+                    // 与 Redis 一致，剩余时间向上取整
+                    Some(t) => t.saturating_sub(now).div_ceil(unit) as i64,
+                }
+            })
+            .map(RespValue::Integer)
+            .collect();
+        RespValue::Array(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command(name: &str, args: &[&str]) -> Command {
+        Command {
+            name: name.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn f1_hgetex_expire_time() {
+        let mut db = Database::new();
+        HandleHash::handle_hset(&mut db, command("HSET", &["h", "f", "v"]));
+        for unit in ["EX", "PX", "EXAT", "PXAT"] {
+            assert_eq!(
+                HandleHash::handle_hgetex(
+                    &mut db,
+                    command("HGETEX", &["h", unit, "0", "FIELDS", "1", "f"])
+                ),
+                RespValue::Error("ERR invalid expire time in 'hgetex' command".to_string())
+            );
+        }
+        assert!(db.get_field_expire("h", "f").is_none());
+        HandleHash::handle_hgetex(
+            &mut db,
+            command("HGETEX", &["h", "EX", "10", "FIELDS", "1", "f"]),
+        );
+        assert!(db.get_field_expire("h", "f").is_some());
+    }
+
+    #[test]
+    fn f2_noop_keeps_watch_version() {
+        let mut db = Database::new();
+        HandleHash::handle_hset(&mut db, command("HSET", &["h", "f", "v"]));
+        let version = db.watch("h");
+        HandleHash::handle_hdel(&mut db, command("HDEL", &["h", "missing"]));
+        assert_eq!(db.version("h"), version);
+        HandleHash::handle_hdel(&mut db, command("HDEL", &["h", "f"]));
+        assert_ne!(db.version("h"), version);
+    }
+
+    fn expire(db: &mut Database, name: &str, time: &str) -> RespValue {
+        let command = command(name, &["h", time, "FIELDS", "1", "f"]);
+        match name {
+            "HEXPIRE" => HandleHash::handle_hexpire(db, command),
+            "HPEXPIRE" => HandleHash::handle_hpexpire(db, command),
+            "HEXPIREAT" => HandleHash::handle_hexpireat(db, command),
+            _ => HandleHash::handle_hpexpireat(db, command),
+        }
+    }
+
+    #[test]
+    fn f3_expire_time_limit() {
+        let mut db = Database::new();
+        HandleHash::handle_hset(&mut db, command("HSET", &["h", "f", "v"]));
+        let error = RespValue::Error("ERR invalid expire time, must be >= 0 and <= 2^48".into());
+        let max = (1u64 << 48).to_string();
+        let over = ((1u64 << 48) + 1).to_string();
+        for (name, time) in [
+            ("HPEXPIRE", "9223372036854775807"),
+            ("HEXPIRE", &max),
+            ("HPEXPIREAT", &over),
+            ("HEXPIREAT", "281474976711"),
+            ("HPEXPIRE", "-1"),
+        ] {
+            let reply = expire(&mut db, name, time);
+            assert_eq!(reply, error, "{} {}", name, time);
+        }
+        assert!(db.get_field_expire("h", "f").is_none());
+        assert_eq!(
+            HandleHash::handle_hgetex(
+                &mut db,
+                command("HGETEX", &["h", "PXAT", &over, "FIELDS", "1", "f"])
+            ),
+            RespValue::Error("ERR invalid expire time in 'hgetex' command".to_string())
+        );
+
+        // 上限本身是合法的，HPTTL 不会溢出
+        let reply = expire(&mut db, "HPEXPIREAT", &max);
+        assert_eq!(reply, RespValue::Array(vec![RespValue::Integer(1)]));
+        assert_eq!(
+            HandleHash::handle_hpexpiretime(
+                &mut db,
+                command("HPEXPIRETIME", &["h", "FIELDS", "1", "f"])
+            ),
+            RespValue::Array(vec![RespValue::Integer(1 << 48)])
+        );
+    }
 }
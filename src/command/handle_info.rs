@@ -0,0 +1,335 @@
+use std::sync::atomic::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::command::Command;
+use crate::command::handle_config::config_file;
+use crate::command::handle_macro::*;
+use crate::command::table;
+use crate::server::{LISTEN_CONFIG, RUN_ID, SERVER_CONFIG, STATS, uptime};
+use crate::storage::{EVICTION_CONFIG, MAXMEMORY, SubKind};
+use crate::util::{
+    cpu_times, format_memory, resident_memory, used_memory, used_memory_peak, used_memory_startup,
+};
+
+/// 不带参数或 INFO default 时输出的部分，all 另外包含 commandstats
+const DEFAULT_SECTIONS: &[&str] = &[
+    "server",
+    "clients",
+    "memory",
+    "persistence",
+    "stats",
+    "replication",
+    "cpu",
+    "keyspace",
+];
+
+/// 所有部分与标题，按输出顺序排列
+const ALL_SECTIONS: &[(&str, &str)] = &[
+    ("server", "Server"),
+    ("clients", "Clients"),
+    ("memory", "Memory"),
+    ("persistence", "Persistence"),
+    ("stats", "Stats"),
+    ("replication", "Replication"),
+    ("cpu", "CPU"),
+    ("commandstats", "Commandstats"),
+    ("keyspace", "Keyspace"),
+];
+
+type Fields = Vec<(String, String)>;
+
+fn field(name: &str, value: impl ToString) -> (String, String) {
+    (name.to_string(), value.to_string())
+}
+
+fn ratio(part: usize, total: usize) -> f64 {
+    part as f64 / total.max(1) as f64
+}
+
+pub struct HandleInfo;
+impl HandleInfo {
+    pub fn handle_info(db: &mut Database, command: Command) -> RespValue {
+        let mut requested: Vec<String> = command.args.iter().map(|s| s.to_lowercase()).collect();
+        if requested.is_empty() {
+            requested.push("default".to_string());
+        }
+        let included = |section: &str| {
+            requested.iter().any(|name| {
+                name == section
+                    || name == "all"
+                    || name == "everything"
+                    || (name == "default" && DEFAULT_SECTIONS.contains(&section))
+            })
+        };
+
+        let mut output = String::new();
+        for (section, title) in ALL_SECTIONS.iter().filter(|(s, _)| included(s)) {
+            let fields = match *section {
+                "server" => Self::server(),
+                "clients" => Self::clients(db),
+                "memory" => Self::memory(db),
+                "persistence" => Self::persistence(),
+                "stats" => Self::stats(db),
+                "replication" => Self::replication(),
+                "cpu" => Self::cpu(),
+                "commandstats" => Self::commandstats(),
+                _ => Self::keyspace(db),
+            };
+            if !output.is_empty() {
+                output.push_str("\r\n");
+            }
+            output.push_str(&format!("# {}\r\n", title));
+            for (name, value) in fields {
+                output.push_str(&format!("{}:{}\r\n", name, value));
+            }
+        }
+        RespValue::BulkString(Some(output))
+    }
+
+    fn server() -> Fields {
+        let uptime = uptime().as_secs();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_micros();
+        let executable = std::env::current_exe()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        vec![
+            field("redis_version", env!("CARGO_PKG_VERSION")),
+            field("redis_mode", "standalone"),
+            field(
+                "os",
+                format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+            ),
+            field("arch_bits", usize::BITS),
+            field("multiplexing_api", "tokio"),
+            field("process_id", std::process::id()),
+            field("run_id", &*RUN_ID),
+            field("tcp_port", LISTEN_CONFIG.port()),
+            field("server_time_usec", now),
+            field("uptime_in_seconds", uptime),
+            field("uptime_in_days", uptime / 86400),
+            field("hz", SERVER_CONFIG.hz()),
+            field("configured_hz", SERVER_CONFIG.hz()),
+            field("executable", executable),
+            field("config_file", config_file()),
+        ]
+    }
+
+    fn clients(db: &Database) -> Fields {
+        let clients = db.clients.values();
+        let max_input = clients.clone().map(|c| c.query_buffer()).max();
+        let max_output = clients.clone().map(|c| c.outbox.pending()).max();
+        let pubsub = clients.clone().filter(|c| c.kind() == "pubsub").count();
+        let watching = clients.filter(|c| c.state().watch > 0).count();
+        vec![
+            field("connected_clients", db.clients.len()),
+            field("client_recent_max_input_buffer", max_input.unwrap_or(0)),
+            field("client_recent_max_output_buffer", max_output.unwrap_or(0)),
+            field("blocked_clients", db.blocking.blocked_count()),
+            field("tracking_clients", db.tracking.client_ids().len()),
+            field("pubsub_clients", pubsub),
+            field("watching_clients", watching),
+        ]
+    }
+
+    fn memory(db: &mut Database) -> Fields {
+        let used = used_memory();
+        let peak = used_memory_peak();
+        let startup = used_memory_startup();
+        let resident = resident_memory();
+        let dataset = db.dataset_bytes();
+        let maxmemory = MAXMEMORY.load(Ordering::Relaxed) as usize;
+        vec![
+            field("used_memory", used),
+            field("used_memory_human", format_memory(used)),
+            field("used_memory_rss", resident),
+            field("used_memory_rss_human", format_memory(resident)),
+            field("used_memory_peak", peak),
+            field("used_memory_peak_human", format_memory(peak)),
+            field(
+                "used_memory_peak_perc",
+                format!("{:.2}%", ratio(used, peak) * 100.0),
+            ),
+            field("used_memory_startup", startup),
+            field("used_memory_dataset", dataset),
+            field(
+                "used_memory_dataset_perc",
+                format!(
+                    "{:.2}%",
+                    ratio(dataset, used.saturating_sub(startup)) * 100.0
+                ),
+            ),
+            field("maxmemory", maxmemory),
+            field("maxmemory_human", format_memory(maxmemory)),
+            field("maxmemory_policy", EVICTION_CONFIG.policy().name()),
+            field(
+                "mem_fragmentation_ratio",
+                format!("{:.2}", ratio(resident, used)),
+            ),
+            field("mem_allocator", "libc"),
+        ]
+    }
+
+    /// 目前没有持久化，只输出监控工具常用的状态字段
+    fn persistence() -> Fields {
+        vec![
+            field("loading", 0),
+            field("async_loading", 0),
+            field("rdb_bgsave_in_progress", 0),
+            field("rdb_last_bgsave_status", "ok"),
+            field("aof_enabled", 0),
+            field("aof_rewrite_in_progress", 0),
+            field("aof_last_write_status", "ok"),
+        ]
+    }
+
+    fn stats(db: &Database) -> Fields {
+        let counter = |counter: &std::sync::atomic::AtomicU64| counter.load(Ordering::Relaxed);
+        let (ops, input, output) = STATS.instantaneous();
+        vec![
+            field(
+                "total_connections_received",
+                counter(&STATS.total_connections_received),
+            ),
+            field(
+                "total_commands_processed",
+                counter(&STATS.total_commands_processed),
+            ),
+            field("instantaneous_ops_per_sec", ops.round()),
+            field("total_net_input_bytes", counter(&STATS.net_input_bytes)),
+            field("total_net_output_bytes", counter(&STATS.net_output_bytes)),
+            field("instantaneous_input_kbps", format!("{:.2}", input / 1024.0)),
+            field(
+                "instantaneous_output_kbps",
+                format!("{:.2}", output / 1024.0),
+            ),
+            field("rejected_connections", counter(&STATS.rejected_connections)),
+            field("expired_keys", counter(&STATS.expired_keys)),
+            field("evicted_keys", counter(&STATS.evicted_keys)),
+            field("keyspace_hits", counter(&STATS.keyspace_hits)),
+            field("keyspace_misses", counter(&STATS.keyspace_misses)),
+            field(
+                "pubsub_channels",
+                db.pubsub.channels(SubKind::Channel, None).len(),
+            ),
+            field("pubsub_patterns", db.pubsub.numpat()),
+            field(
+                "pubsub_shardchannels",
+                db.pubsub.channels(SubKind::Shard, None).len(),
+            ),
+            field("total_error_replies", counter(&STATS.total_error_replies)),
+        ]
+    }
+
+    /// 不支持主从复制，固定为没有副本的 master
+    fn replication() -> Fields {
+        vec![
+            field("role", "master"),
+            field("connected_slaves", 0),
+            field("master_failover_state", "no-failover"),
+            field("master_replid", &*RUN_ID),
+            field("master_replid2", "0".repeat(40)),
+            field("master_repl_offset", 0),
+            field("second_repl_offset", -1),
+            field("repl_backlog_active", 0),
+        ]
+    }
+
+    fn cpu() -> Fields {
+        let [user, sys, user_children, sys_children] = cpu_times();
+        vec![
+            field("used_cpu_sys", format!("{:.6}", sys)),
+            field("used_cpu_user", format!("{:.6}", user)),
+            field("used_cpu_sys_children", format!("{:.6}", sys_children)),
+            field("used_cpu_user_children", format!("{:.6}", user_children)),
+        ]
+    }
+
+    fn commandstats() -> Fields {
+        table::command_stats()
+            .into_iter()
+            .map(|(name, stat)| {
+                let calls = stat.calls.load(Ordering::Relaxed);
+                let usec = stat.usec.load(Ordering::Relaxed);
+                field(
+                    &format!("cmdstat_{}", name.to_lowercase()),
+                    format!(
+                        "calls={},usec={},usec_per_call={:.2},rejected_calls={},failed_calls={}",
+                        calls,
+                        usec,
+                        usec as f64 / calls.max(1) as f64,
+                        stat.rejected_calls.load(Ordering::Relaxed),
+                        stat.failed_calls.load(Ordering::Relaxed),
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// 只有一个数据库，没有 key 时与 Redis 一样不输出
+    fn keyspace(db: &Database) -> Fields {
+        let (keys, expires, avg_ttl) = db.keyspace_stats();
+        if keys == 0 {
+            return Vec::new();
+        }
+        vec![field(
+            "db0",
+            format!("keys={},expires={},avg_ttl={}", keys, expires, avg_ttl),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// 返回输出中各部分的标题
+    fn titles(db: &mut Database, args: &[&str]) -> Vec<String> {
+        let command = Command {
+            name: "INFO".to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+        };
+        let RespValue::BulkString(Some(output)) = HandleInfo::handle_info(db, command) else {
+            panic!("INFO should return a bulk string");
+        };
+        output
+            .lines()
+            .filter_map(|line| line.strip_prefix("# "))
+            .map(|title| title.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn f1_info_sections() {
+        let mut db = Database::new();
+        db.set("k".into(), Value::String("v".into()));
+        let default = [
+            "Server",
+            "Clients",
+            "Memory",
+            "Persistence",
+            "Stats",
+            "Replication",
+            "CPU",
+            "Keyspace",
+        ];
+        assert_eq!(titles(&mut db, &[]), default);
+        assert_eq!(titles(&mut db, &["DEFAULT"]), default);
+        assert_eq!(titles(&mut db, &["memory"]), ["Memory"]);
+        // 按固定顺序输出，重复的部分只输出一次
+        assert_eq!(
+            titles(&mut db, &["keyspace", "Server", "keyspace"]),
+            ["Server", "Keyspace"]
+        );
+        assert_eq!(
+            titles(&mut db, &["default", "commandstats"]).len(),
+            ALL_SECTIONS.len()
+        );
+        for name in ["all", "everything"] {
+            assert_eq!(titles(&mut db, &[name]).len(), ALL_SECTIONS.len());
+        }
+        assert!(titles(&mut db, &["nosuchsection"]).is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::atomic::Ordering;
+
+use crate::command::Command;
+use crate::command::handle_macro::*;
+use crate::command::slowlog::SLOWLOG_LOG_SLOWER_THAN;
+use crate::server::{LATENCY, LATENCY_MONITOR_THRESHOLD, LatencyEvent};
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(Some(s.to_string()))
+}
+
+fn integer(n: u64) -> RespValue {
+    RespValue::Integer(n as i64)
+}
+
+pub struct HandleLatency;
+impl HandleLatency {
+    pub fn handle_latency(command: Command) -> RespValue {
+        let args = &command.args[1..];
+        match (command.args[0].to_uppercase().as_str(), args.len()) {
+            ("LATEST", 0) => Self::latest(),
+            ("HISTORY", 1) => Self::history(&args[0]),
+            ("RESET", _) => {
+                let mut events = LATENCY.lock().unwrap();
+                let count = if args.is_empty() {
+                    let count = events.len();
+                    events.clear();
+                    count
+                } else {
+                    let before = events.len();
+                    events.retain(|name, _| !args.iter().any(|arg| arg.eq_ignore_ascii_case(name)));
+                    before - events.len()
+                };
+                integer(count as u64)
+            }
+            ("DOCTOR", 0) => bulk(&Self::doctor()),
+            ("HELP", 0) => RespValue::Array(
+                [
+                    "LATENCY <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                    "DOCTOR",
+                    "    Return a human readable latency analysis report.",
+                    "HISTORY <event>",
+                    "    Return time-latency samples for the <event> class.",
+                    "LATEST",
+                    "    Return the latest latency samples for all events.",
+                    "RESET [<event> ...]",
+                    "    Reset latency data of one or more <event> classes.",
+                    "    (default: reset all data for all event classes)",
+                    "HELP",
+                    "    Print this help.",
+                ]
+                .into_iter()
+                .map(bulk)
+                .collect(),
+            ),
+            _ => RespValue::Error(format!(
+                "ERR unknown subcommand or wrong number of arguments for '{}'. Try LATENCY HELP.",
+                command.args[0]
+            )),
+        }
+    }
+
+    /// 每个事件一项：事件名、最近一次的时间与延迟、最大延迟
+    fn latest() -> RespValue {
+        let events = LATENCY.lock().unwrap();
+        RespValue::Array(
+            events
+                .iter()
+                .filter_map(|(name, event)| {
+                    let (time, latency) = event.history.back()?;
+                    Some(RespValue::Array(vec![
+                        bulk(name),
+                        integer(*time),
+                        integer(*latency),
+                        integer(event.max),
+                    ]))
+                })
+                .collect(),
+        )
+    }
+
+    fn history(name: &str) -> RespValue {
+        let events = LATENCY.lock().unwrap();
+        let Some(event) = events.get(name.to_lowercase().as_str()) else {
+            return RespValue::Array(Vec::new());
+        };
+        RespValue::Array(
+            event
+                .history
+                .iter()
+                .map(|(time, latency)| RespValue::Array(vec![integer(*time), integer(*latency)]))
+                .collect(),
+        )
+    }
+
+    /// 按 Redis 的格式汇总每个事件的延迟并给出建议
+    fn doctor() -> String {
+        let events = LATENCY.lock().unwrap();
+        let threshold = LATENCY_MONITOR_THRESHOLD.load(Ordering::Relaxed);
+        if events.is_empty() {
+            if threshold == 0 {
+                return "I'm sorry, Dave, I can't do that. Latency monitoring is disabled in this Redis instance. You may use \"CONFIG SET latency-monitor-threshold <milliseconds>.\" in order to enable it. If we weren't in a deep space mission I'd suggest to take a look at https://redis.io/topics/latency-monitor.\n".to_string();
+            }
+            return "Dave, no latency spike was observed during the lifetime of this Redis instance, not in the slightest bit. I honestly think you ought to sleep tonight.\n".to_string();
+        }
+        let mut report = "Dave, I have observed latency spikes in this Redis instance. You don't mind talking about it, do you Dave?\n\n".to_string();
+        for (i, (name, event)) in events.iter().enumerate() {
+            report.push_str(&format!("{}. {}: {}\n", i + 1, name, Self::summary(event)));
+        }
+        report.push_str("\nI have a few advices for you:\n\n");
+        if threshold > 0 && threshold < 100 {
+            report.push_str(&format!("- The system is configured to log latency spikes of {} milliseconds or more. You may want to raise latency-monitor-threshold to a bigger value if spikes of this size are expected in your workload.\n", threshold));
+        }
+        if events.contains_key("command") {
+            let slowlog = SLOWLOG_LOG_SLOWER_THAN.load(Ordering::Relaxed);
+            if slowlog < 0 || slowlog as u64 > threshold * 1000 {
+                report.push_str(&format!("- Your current Slow Log configuration only logs events that are slower than your configured latency monitor threshold. Please use 'CONFIG SET slowlog-log-slower-than {}'.\n", threshold * 1000));
+            }
+            report.push_str("- Check your Slow Log to understand what are the commands you are running which are too slow to execute. Please check https://redis.io/commands/slowlog for more information.\n");
+        }
+        if events.contains_key("expire-cycle") || events.contains_key("eviction-cycle") {
+            report.push_str("- Deleting, expiring or evicting (because of maxmemory policy) large objects is a blocking operation. If you have very large objects that are often deleted, expired, or evicted, try to fragment those objects into multiple smaller objects.\n");
+        }
+        report
+    }
+
+    /// 样本数、平均值、平均偏差、平均间隔与最大值
+    fn summary(event: &LatencyEvent) -> String {
+        let history = &event.history;
+        let count = history.len();
+        if count == 1 {
+            let (_, latency) = history[0];
+            return format!(
+                "1 latency spike ({}ms). Worst all time event {}ms.",
+                latency, event.max
+            );
+        }
+        let avg = history.iter().map(|(_, l)| *l as f64).sum::<f64>() / count as f64;
+        let deviation = history
+            .iter()
+            .map(|(_, l)| (*l as f64 - avg).abs())
+            .sum::<f64>()
+            / count as f64;
+        let period = (history[count - 1].0 - history[0].0) as f64 / (count - 1) as f64;
+        format!(
+            "{} latency spikes (average {:.0}ms, mean deviation {:.0}ms, period {:.2} sec). Worst all time event {}ms.",
+            count, avg, deviation, period, event.max
+        )
+    }
+}
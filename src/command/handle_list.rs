@@ -1,99 +1,268 @@
 use std::cmp;
-use std::collections::VecDeque;
+use std::time::Duration;
+
+use tokio::sync::oneshot;
 
 use crate::command::Command;
 use crate::command::handle_macro::*;
+use crate::storage::{BlockedOp, ListValue, NotifyClass};
 
-pub struct HandleList;
-impl HandleList {
-    pub async fn handle_lpush(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        if command.args.len() > 1 {
-            let mut db_guard = db.lock().await;
-            match db_guard.get_mut(&command.args[0]) {
-                Some(Value::List(list)) => {
-                    for element in command.args.iter().skip(1) {
-                        list.push_front(element.clone());
-                    }
-                    RespValue::Integer(list.len() as i64)
-                }
-                Some(_) => RespErrType!(),
-                None => {
-                    let mut list = VecDeque::with_capacity(command.args.len());
-                    for element in command.args.iter().skip(1) {
-                        list.push_front(element.clone());
-                    }
-                    let len = list.len();
-                    db_guard.set(command.args[0].clone(), Value::List(list));
-                    RespValue::Integer(len as i64)
-                }
+/// 已登记阻塞的命令，等待其他连接写入数据后唤醒
+pub struct Blocked {
+    rx: oneshot::Receiver<RespValue>,
+    timeout: Option<Duration>,
+    timeout_reply: RespValue,
+}
+
+impl Blocked {
+    /// 等待唤醒，超时返回 None
+    pub async fn wait(&mut self) -> Option<RespValue> {
+        match self.timeout {
+            None => (&mut self.rx).await.ok(),
+            Some(duration) => tokio::time::timeout(duration, &mut self.rx)
+                .await
+                .ok()
+                .and_then(Result::ok),
+        }
+    }
+
+    /// 超时后取消登记，超时与唤醒同时发生时，以已经送达的结果为准
+    pub fn timed_out(mut self, db: &mut Database, id: u64) -> RespValue {
+        db.blocking.unblock(id);
+        self.rx.try_recv().unwrap_or(self.timeout_reply)
+    }
+}
+
+/// 超时时 BLMOVE 返回 Null，其余返回空数组
+fn timeout_reply(op: &BlockedOp, protocol: u8) -> RespValue {
+    match op {
+        BlockedOp::Move { .. } => RespValue::Null,
+        _ if protocol >= 3 => RespValue::Null,
+        _ => RespValue::NullArray,
+    }
+}
+
+/// 解析阻塞命令的超时时间（秒，可为小数），0 表示一直阻塞
+fn parse_timeout(s: &str) -> Result<Option<Duration>, RespValue> {
+    match s.parse::<f64>() {
+        Ok(t) if t < 0.0 => Err(RespValue::Error("ERR timeout is negative".to_string())),
+        Ok(0.0) => Ok(None),
+        Ok(t) if t.is_finite() => Duration::try_from_secs_f64(t)
+            .map(Some)
+            .map_err(|_| RespValue::Error("ERR timeout is out of range".to_string())),
+        _ => Err(RespValue::Error(
+            "ERR timeout is not a float or out of range".to_string(),
+        )),
+    }
+}
+
+/// LEFT 返回 true，RIGHT 返回 false
+fn parse_direction(s: &str) -> Option<bool> {
+    match s.to_uppercase().as_str() {
+        "LEFT" => Some(true),
+        "RIGHT" => Some(false),
+        _ => None,
+    }
+}
+
+/// 从列表一端弹出至多 count 个元素，列表为空时删除 key
+fn pop_elements(db: &mut Database, key: &str, left: bool, count: usize) -> Vec<String> {
+    let mut elements = Vec::new();
+    if let Some(Value::List(list)) = db.get_mut(key) {
+        while elements.len() < count {
+            let element = if left {
+                list.pop_front()
+            } else {
+                list.pop_back()
+            };
+            match element {
+                Some(e) => elements.push(e),
+                None => break,
             }
-        } else {
-            RespErrArgNum!()
+        }
+        let empty = list.is_empty();
+        if !elements.is_empty() {
+            db.modified(key);
+            db.notify(NotifyClass::List, if left { "lpop" } else { "rpop" }, key);
+        }
+        if empty {
+            db.del(key);
         }
     }
+    elements
+}
+
+fn push_element(db: &mut Database, key: &str, element: String, left: bool) {
+    match db.get_mut(key) {
+        Some(Value::List(list)) => {
+            if left {
+                list.push_front(element);
+            } else {
+                list.push_back(element);
+            }
+            db.modified(key);
+        }
+        _ => {
+            db.set(
+                key.to_string(),
+                Value::List(ListValue::from_iter([element])),
+            );
+        }
+    }
+    db.notify(NotifyClass::List, if left { "lpush" } else { "rpush" }, key);
+    db.blocking.signal_key_ready(key);
+}
+
+/// 解析 `numkeys key [key ...] LEFT|RIGHT [COUNT count]`
+fn parse_mpop_args(args: &[String]) -> Result<(Vec<String>, bool, usize), RespValue> {
+    let numkeys = match args[0].parse::<i64>() {
+        Ok(n) if n > 0 => n as usize,
+        _ => {
+            return Err(RespValue::Error(
+                "ERR numkeys should be greater than 0".to_string(),
+            ));
+        }
+    };
+    let rest = &args[1..];
+    if rest.len() < numkeys + 1 {
+        return Err(RespValue::Error("ERR syntax error".to_string()));
+    }
+    let keys = rest[..numkeys].to_vec();
+    let Some(left) = parse_direction(&rest[numkeys]) else {
+        return Err(RespValue::Error("ERR syntax error".to_string()));
+    };
+    let count = match &rest[numkeys + 1..] {
+        [] => 1,
+        [opt, n] if opt.eq_ignore_ascii_case("COUNT") => match n.parse::<i64>() {
+            Ok(n) if n > 0 => n as usize,
+            _ => {
+                return Err(RespValue::Error(
+                    "ERR count should be greater than 0".to_string(),
+                ));
+            }
+        },
+        _ => return Err(RespValue::Error("ERR syntax error".to_string())),
+    };
+    Ok((keys, left, count))
+}
 
-    pub async fn handle_rpush(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+fn is_nonempty_list(db: &Database, key: &str) -> Result<bool, RespValue> {
+    match db.get(key) {
+        Some(Value::List(list)) => Ok(!list.is_empty()),
+        Some(_) => Err(RespErrType!()),
+        None => Ok(false),
+    }
+}
+
+pub struct HandleList;
+impl HandleList {
+    pub fn handle_lpush(db: &mut Database, command: Command) -> RespValue {
+        Self::push_generic(db, command, true, false)
+    }
+
+    pub fn handle_rpush(db: &mut Database, command: Command) -> RespValue {
+        Self::push_generic(db, command, false, false)
+    }
+
+    pub fn handle_lpushx(db: &mut Database, command: Command) -> RespValue {
+        Self::push_generic(db, command, true, true)
+    }
+
+    pub fn handle_rpushx(db: &mut Database, command: Command) -> RespValue {
+        Self::push_generic(db, command, false, true)
+    }
+
+    fn push_generic(
+        db: &mut Database,
+        command: Command,
+        left: bool,
+        only_existing: bool,
+    ) -> RespValue {
         if command.args.len() > 1 {
-            let mut db_guard = db.lock().await;
-            match db_guard.get_mut(&command.args[0]) {
+            let len = match db.get_mut(&command.args[0]) {
                 Some(Value::List(list)) => {
                     for element in command.args.iter().skip(1) {
-                        list.push_back(element.clone());
+                        if left {
+                            list.push_front(element.clone());
+                        } else {
+                            list.push_back(element.clone());
+                        }
                     }
-                    RespValue::Integer(list.len() as i64)
+                    let len = list.len();
+                    db.modified(&command.args[0]);
+                    len
                 }
-                Some(_) => RespErrType!(),
+                Some(_) => return RespErrType!(),
+                None if only_existing => return RespValue::Integer(0),
                 None => {
-                    let mut list = VecDeque::with_capacity(command.args.len());
+                    let mut list = ListValue::new();
                     for element in command.args.iter().skip(1) {
-                        list.push_back(element.clone());
+                        if left {
+                            list.push_front(element.clone());
+                        } else {
+                            list.push_back(element.clone());
+                        }
                     }
                     let len = list.len();
-                    db_guard.set(command.args[0].clone(), Value::List(list));
-                    RespValue::Integer(len as i64)
+                    db.set(command.args[0].clone(), Value::List(list));
+                    len
                 }
-            }
+            };
+            let event = if left { "lpush" } else { "rpush" };
+            db.notify(NotifyClass::List, event, &command.args[0]);
+            db.blocking.signal_key_ready(&command.args[0]);
+            RespValue::Integer(len as i64)
         } else {
             RespErrArgNum!()
         }
     }
 
-    pub async fn handle_lpop(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        if command.args.len() == 1 {
-            let mut db_guard = db.lock().await;
-            match db_guard.get_mut(&command.args[0]) {
-                Some(Value::List(list)) => match list.pop_front() {
-                    Some(s) => RespValue::BulkString(Some(s)),
-                    None => RespValue::Null,
-                },
-                Some(_) => RespErrType!(),
-                None => RespValue::Null,
-            }
-        } else {
-            RespErrArgNum!()
-        }
+    pub fn handle_lpop(db: &mut Database, protocol: u8, command: Command) -> RespValue {
+        Self::pop_generic(db, protocol, command, true)
     }
 
-    pub async fn handle_rpop(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        if command.args.len() == 1 {
-            let mut db_guard = db.lock().await;
-            match db_guard.get_mut(&command.args[0]) {
-                Some(Value::List(list)) => match list.pop_back() {
-                    Some(s) => RespValue::BulkString(Some(s)),
-                    None => RespValue::Null,
-                },
-                Some(_) => RespErrType!(),
+    pub fn handle_rpop(db: &mut Database, protocol: u8, command: Command) -> RespValue {
+        Self::pop_generic(db, protocol, command, false)
+    }
+
+    /// key 不存在时，带 count 的 RESP2 回复为空数组，否则为空字符串
+    fn pop_generic(db: &mut Database, protocol: u8, command: Command, left: bool) -> RespValue {
+        let count = match command.args.len() {
+            1 => None,
+            2 => match command.args[1].parse::<i64>() {
+                Ok(n) if n >= 0 => Some(n as usize),
+                _ => {
+                    return RespValue::Error(
+                        "ERR value is out of range, must be positive".to_string(),
+                    );
+                }
+            },
+            _ => return RespErrArgNum!(),
+        };
+        match is_nonempty_list(db, &command.args[0]) {
+            Ok(true) => {}
+            Ok(false) if count.is_some() && protocol < 3 => return RespValue::NullArray,
+            Ok(false) => return RespValue::Null,
+            Err(e) => return e,
+        }
+        let mut elements = pop_elements(db, &command.args[0], left, count.unwrap_or(1));
+        match count {
+            None => match elements.pop() {
+                Some(s) => RespValue::BulkString(Some(s)),
                 None => RespValue::Null,
-            }
-        } else {
-            RespErrArgNum!()
+            },
+            Some(_) => RespValue::Array(
+                elements
+                    .into_iter()
+                    .map(|e| RespValue::BulkString(Some(e)))
+                    .collect(),
+            ),
         }
     }
 
-    pub async fn handle_llen(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_llen(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 1 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            match db.get(&command.args[0]) {
                 Some(Value::List(list)) => RespValue::Integer(list.len() as i64),
                 Some(_) => RespErrType!(),
                 None => RespValue::Integer(0),
@@ -103,17 +272,18 @@ impl HandleList {
         }
     }
 
-    pub async fn handle_lindex(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_lindex(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 2 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            match db.get(&command.args[0]) {
                 Some(Value::List(list)) => {
                     if let Ok(mut index) = command.args[1].parse::<i64>() {
                         if index < 0 {
                             index += list.len() as i64;
                         };
                         if index >= 0 && index < list.len() as i64 {
-                            return RespValue::BulkString(Some(list[index as usize].clone()));
+                            return RespValue::BulkString(
+                                list.get(index as usize).map(str::to_string),
+                            );
                         }
                     }
                     RespErrNumWrong!()
@@ -126,17 +296,18 @@ impl HandleList {
         }
     }
 
-    pub async fn handle_lset(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_lset(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 3 {
-            let mut db_guard = db.lock().await;
-            match db_guard.get_mut(&command.args[0]) {
+            match db.get_mut(&command.args[0]) {
                 Some(Value::List(list)) => {
                     if let Ok(mut index) = command.args[1].parse::<i64>() {
                         if index < 0 {
                             index += list.len() as i64;
                         };
                         if index >= 0 && index < list.len() as i64 {
-                            list[index as usize] = command.args[2].clone();
+                            list.set(index as usize, command.args[2].clone());
+                            db.modified(&command.args[0]);
+                            db.notify(NotifyClass::List, "lset", &command.args[0]);
                             return RespOK!();
                         }
                     }
@@ -152,10 +323,9 @@ impl HandleList {
         }
     }
 
-    pub async fn handle_lrange(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_lrange(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 3 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            match db.get(&command.args[0]) {
                 Some(Value::List(list)) => {
                     if let (Ok(mut idx_from), Ok(mut idx_end)) = (
                         command.args[1].parse::<i64>(),
@@ -169,8 +339,10 @@ impl HandleList {
                         };
                         if idx_from >= 0 && idx_end < list.len() as i64 && idx_from <= idx_end {
                             let vec = Vec::from_iter(
-                                list.range(idx_from as usize..=idx_end as usize)
-                                    .map(|val| RespValue::BulkString(Some(val.clone()))),
+                                list.iter()
+                                    .skip(idx_from as usize)
+                                    .take((idx_end - idx_from) as usize + 1)
+                                    .map(|val| RespValue::BulkString(Some(val.to_string()))),
                             );
                             return RespValue::Array(vec);
                         }
@@ -189,21 +361,20 @@ impl HandleList {
         }
     }
 
-    pub async fn handle_lrem(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_lrem(db: &mut Database, command: Command) -> RespValue {
         match command.args.len() {
             3 => {
                 if let Ok(mut count) = command.args[1].parse::<i64>() {
-                    let mut db_guard = db.lock().await;
-                    match db_guard.get_mut(&command.args[0]) {
-                        None => RespValue::Integer(0),
+                    let (removed, empty) = match db.get_mut(&command.args[0]) {
+                        None => return RespValue::Integer(0),
                         Some(Value::List(list)) => {
                             let len_pre = list.len();
                             match count.cmp(&0) {
                                 cmp::Ordering::Equal => {
-                                    list.retain(|s| s != &command.args[2]);
+                                    list.retain(|s| s != command.args[2]);
                                 }
                                 cmp::Ordering::Greater => list.retain(|s| {
-                                    !(s == &command.args[2] && {
+                                    !(s == command.args[2] && {
                                         count -= 1;
                                         count >= 0
                                     })
@@ -211,9 +382,11 @@ impl HandleList {
                                 cmp::Ordering::Less => {
                                     let rm: Vec<bool> = list
                                         .iter()
+                                        .collect::<Vec<_>>()
+                                        .into_iter()
                                         .rev()
                                         .map(|s| {
-                                            s == &command.args[2] && {
+                                            s == command.args[2] && {
                                                 count += 1;
                                                 count <= 0
                                             }
@@ -224,10 +397,18 @@ impl HandleList {
                                     list.retain(|_| !rm.next().unwrap());
                                 }
                             }
-                            RespValue::Integer((len_pre - list.len()) as i64)
+                            (len_pre - list.len(), list.is_empty())
                         }
-                        _ => RespErrType!(),
+                        _ => return RespErrType!(),
+                    };
+                    if removed > 0 {
+                        db.modified(&command.args[0]);
+                        db.notify(NotifyClass::List, "lrem", &command.args[0]);
+                    }
+                    if empty {
+                        db.del(&command.args[0]);
                     }
+                    RespValue::Integer(removed as i64)
                 } else {
                     RespErrNumWrong!()
                 }
@@ -236,15 +417,14 @@ impl HandleList {
         }
     }
 
-    pub async fn handle_ltrim(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_ltrim(db: &mut Database, command: Command) -> RespValue {
         match command.args.len() {
             3 => {
                 if let (Ok(mut idx_from), Ok(mut idx_end)) = (
                     command.args[1].parse::<i64>(),
                     command.args[2].parse::<i64>(),
                 ) {
-                    let mut db_guard = db.lock().await;
-                    match db_guard.get_mut(&command.args[0]) {
+                    let empty = match db.get_mut(&command.args[0]) {
                         Some(Value::List(list)) => {
                             if idx_from < 0 {
                                 idx_from += list.len() as i64;
@@ -258,13 +438,18 @@ impl HandleList {
                             if idx_end < 0 {
                                 idx_end = 0;
                             }
-                            list.drain(idx_end as usize + 1..);
-                            list.drain(0..idx_from as usize);
+                            list.trim(idx_from as usize, idx_end as usize + 1);
+                            list.is_empty()
                         }
-                        None => {}
+                        None => return RespOK!(),
                         _ => {
                             return RespErrType!();
                         }
+                    };
+                    db.modified(&command.args[0]);
+                    db.notify(NotifyClass::List, "ltrim", &command.args[0]);
+                    if empty {
+                        db.del(&command.args[0]);
                     }
                     RespOK!()
                 } else {
@@ -274,4 +459,504 @@ impl HandleList {
             _ => RespErrArgNum!(),
         }
     }
+
+    pub fn handle_lmove(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() != 4 {
+            return RespErrArgNum!();
+        }
+        let (Some(from_left), Some(to_left)) = (
+            parse_direction(&command.args[2]),
+            parse_direction(&command.args[3]),
+        ) else {
+            return RespValue::Error("ERR syntax error".to_string());
+        };
+        Self::move_generic(db, &command.args[0], &command.args[1], from_left, to_left)
+    }
+
+    pub fn handle_rpoplpush(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() != 2 {
+            return RespErrArgNum!();
+        }
+        Self::move_generic(db, &command.args[0], &command.args[1], false, true)
+    }
+
+    fn move_generic(
+        db: &mut Database,
+        source: &str,
+        dest: &str,
+        from_left: bool,
+        to_left: bool,
+    ) -> RespValue {
+        match is_nonempty_list(db, source) {
+            Ok(true) => {}
+            Ok(false) => return RespValue::Null,
+            Err(e) => return e,
+        }
+        Self::serve_blocked_op(
+            db,
+            source,
+            &BlockedOp::Move {
+                dest: dest.to_string(),
+                from_left,
+                to_left,
+            },
+        )
+    }
+
+    /// 没有可以弹出的列表时，RESP2 回复空数组
+    pub fn handle_lmpop(db: &mut Database, protocol: u8, command: Command) -> RespValue {
+        if command.args.len() < 3 {
+            return RespErrArgNum!();
+        }
+        let (keys, left, count) = match parse_mpop_args(&command.args) {
+            Ok(parsed) => parsed,
+            Err(e) => return e,
+        };
+        for key in &keys {
+            match is_nonempty_list(db, key) {
+                Ok(true) => {
+                    return Self::serve_blocked_op(db, key, &BlockedOp::MPop { left, count });
+                }
+                Ok(false) => {}
+                Err(e) => return e,
+            }
+        }
+        if protocol < 3 {
+            RespValue::NullArray
+        } else {
+            RespValue::Null
+        }
+    }
+
+    pub fn handle_linsert(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() != 4 {
+            return RespErrArgNum!();
+        }
+        let after = match command.args[1].to_uppercase().as_str() {
+            "BEFORE" => false,
+            "AFTER" => true,
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        };
+        let len = match db.get_mut(&command.args[0]) {
+            Some(Value::List(list)) => match list.position(&command.args[2]) {
+                Some(pos) => {
+                    let idx = if after { pos + 1 } else { pos };
+                    list.insert(idx, command.args[3].clone());
+                    let len = list.len();
+                    db.modified(&command.args[0]);
+                    len
+                }
+                None => return RespValue::Integer(-1),
+            },
+            Some(_) => return RespErrType!(),
+            None => return RespValue::Integer(0),
+        };
+        db.notify(NotifyClass::List, "linsert", &command.args[0]);
+        RespValue::Integer(len as i64)
+    }
+
+    pub fn handle_lpos(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() < 2 {
+            return RespErrArgNum!();
+        }
+        let mut rank: i64 = 1;
+        let mut count: Option<usize> = None;
+        let mut maxlen: usize = 0;
+        for pair in command.args[2..].chunks(2) {
+            if pair.len() != 2 {
+                return RespValue::Error("ERR syntax error".to_string());
+            }
+            let Ok(n) = pair[1].parse::<i64>() else {
+                return RespErrNumWrong!();
+            };
+            match pair[0].to_uppercase().as_str() {
+                "RANK" => {
+                    if n == 0 {
+                        return RespValue::Error(
+                            "ERR RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list"
+                                .to_string(),
+                        );
+                    }
+                    rank = n;
+                }
+                "COUNT" => {
+                    if n < 0 {
+                        return RespValue::Error("ERR COUNT can't be negative".to_string());
+                    }
+                    count = Some(n as usize);
+                }
+                "MAXLEN" => {
+                    if n < 0 {
+                        return RespValue::Error("ERR MAXLEN can't be negative".to_string());
+                    }
+                    maxlen = n as usize;
+                }
+                _ => return RespValue::Error("ERR syntax error".to_string()),
+            }
+        }
+
+        let list = match db.get(&command.args[0]) {
+            Some(Value::List(list)) => list,
+            Some(_) => return RespErrType!(),
+            None => {
+                return match count {
+                    Some(_) => RespValue::Array(Vec::new()),
+                    None => RespValue::Null,
+                };
+            }
+        };
+        // count 为 0 表示返回全部匹配
+        let limit = match count {
+            Some(0) => usize::MAX,
+            Some(n) => n,
+            None => 1,
+        };
+        let scan = if maxlen == 0 { list.len() } else { maxlen };
+        let elements: Vec<&str> = list.iter().collect();
+        let indexes: Box<dyn Iterator<Item = usize>> = if rank > 0 {
+            Box::new(0..list.len())
+        } else {
+            Box::new((0..list.len()).rev())
+        };
+        let mut skip = rank.unsigned_abs() as usize - 1;
+        let mut matches = Vec::new();
+        for idx in indexes.take(scan) {
+            if elements[idx] != command.args[1] {
+                continue;
+            }
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            matches.push(RespValue::Integer(idx as i64));
+            if matches.len() >= limit {
+                break;
+            }
+        }
+        match count {
+            Some(_) => RespValue::Array(matches),
+            None => matches.pop().unwrap_or(RespValue::Null),
+        }
+    }
+
+    /// 命令或事务执行完毕后，按先来先服务的顺序唤醒阻塞在已就绪 key 上的客户端
+    pub fn serve_blocked_clients(db: &mut Database) {
+        loop {
+            let keys = db.blocking.take_ready_keys();
+            if keys.is_empty() {
+                break;
+            }
+            for key in keys {
+                while let Some((id, _)) = db.blocking.first_waiter(&key) {
+                    if !matches!(is_nonempty_list(db, &key), Ok(true)) {
+                        break;
+                    }
+                    if let Some(client) = db.blocking.unblock(id) {
+                        let reply = Self::serve_blocked_op(db, &key, &client.op);
+                        let _ = client.reply.send(reply);
+                    }
+                }
+            }
+        }
+    }
+
+    fn serve_blocked_op(db: &mut Database, key: &str, op: &BlockedOp) -> RespValue {
+        match op {
+            BlockedOp::Pop { left } => match pop_elements(db, key, *left, 1).pop() {
+                Some(e) => RespValue::Array(vec![
+                    RespValue::BulkString(Some(key.to_string())),
+                    RespValue::BulkString(Some(e)),
+                ]),
+                None => RespValue::Null,
+            },
+            BlockedOp::MPop { left, count } => {
+                let elements = pop_elements(db, key, *left, *count);
+                RespValue::Array(vec![
+                    RespValue::BulkString(Some(key.to_string())),
+                    RespValue::Array(
+                        elements
+                            .into_iter()
+                            .map(|e| RespValue::BulkString(Some(e)))
+                            .collect(),
+                    ),
+                ])
+            }
+            BlockedOp::Move {
+                dest,
+                from_left,
+                to_left,
+            } => {
+                if let Some(v) = db.get(dest)
+                    && !matches!(v, Value::List(_))
+                {
+                    return RespErrType!();
+                }
+                match pop_elements(db, key, *from_left, 1).pop() {
+                    Some(e) => {
+                        push_element(db, dest, e.clone(), *to_left);
+                        RespValue::BulkString(Some(e))
+                    }
+                    None => RespValue::Null,
+                }
+            }
+        }
+    }
+
+    /// 若某个 key 上已有数据则立即执行，没有数据时返回 None
+    fn serve_now(db: &mut Database, keys: &[String], op: &BlockedOp) -> Option<RespValue> {
+        for key in keys {
+            match is_nonempty_list(db, key) {
+                Ok(true) => return Some(Self::serve_blocked_op(db, key, op)),
+                Ok(false) => {}
+                Err(e) => return Some(e),
+            }
+        }
+        None
+    }
+
+    /// BLPOP / BRPOP / BLMOVE / BLMPOP：有数据时立即执行，否则以 client id 登记阻塞
+    pub fn handle_blocking(
+        db: &mut Database,
+        id: u64,
+        protocol: u8,
+        command: Command,
+    ) -> Result<RespValue, Blocked> {
+        let (keys, op, timeout) = match Self::parse_blocking(command) {
+            Ok(parsed) => parsed,
+            Err(e) => return Ok(e),
+        };
+        if let Some(reply) = Self::serve_now(db, &keys, &op) {
+            return Ok(reply);
+        }
+        let timeout_reply = timeout_reply(&op, protocol);
+        Err(Blocked {
+            rx: db.blocking.block(id, keys, op),
+            timeout,
+            timeout_reply,
+        })
+    }
+
+    /// 事务中的阻塞命令不会阻塞，没有数据时直接返回超时的回复
+    pub fn handle_blocking_nowait(db: &mut Database, protocol: u8, command: Command) -> RespValue {
+        match Self::parse_blocking(command) {
+            Ok((keys, op, _)) => {
+                Self::serve_now(db, &keys, &op).unwrap_or_else(|| timeout_reply(&op, protocol))
+            }
+            Err(e) => e,
+        }
+    }
+
+    fn parse_blocking(
+        mut command: Command,
+    ) -> Result<(Vec<String>, BlockedOp, Option<Duration>), RespValue> {
+        match command.name.as_str() {
+            "BLPOP" | "BRPOP" => {
+                if command.args.len() < 2 {
+                    return Err(RespErrArgNum!());
+                }
+                let timeout = parse_timeout(&command.args.pop().unwrap())?;
+                let left = command.name == "BLPOP";
+                Ok((command.args, BlockedOp::Pop { left }, timeout))
+            }
+            "BLMOVE" => {
+                if command.args.len() != 5 {
+                    return Err(RespErrArgNum!());
+                }
+                let (Some(from_left), Some(to_left)) = (
+                    parse_direction(&command.args[2]),
+                    parse_direction(&command.args[3]),
+                ) else {
+                    return Err(RespValue::Error("ERR syntax error".to_string()));
+                };
+                let timeout = parse_timeout(&command.args[4])?;
+                let op = BlockedOp::Move {
+                    dest: command.args[1].clone(),
+                    from_left,
+                    to_left,
+                };
+                Ok((vec![command.args[0].clone()], op, timeout))
+            }
+            _ => {
+                // BLMPOP
+                if command.args.len() < 4 {
+                    return Err(RespErrArgNum!());
+                }
+                let timeout = parse_timeout(&command.args[0])?;
+                let (keys, left, count) = parse_mpop_args(&command.args[1..])?;
+                Ok((keys, BlockedOp::MPop { left, count }, timeout))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command(name: &str, args: &[&str]) -> Command {
+        Command {
+            name: name.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn ints(list: &[i64]) -> RespValue {
+        RespValue::Array(list.iter().map(|&n| RespValue::Integer(n)).collect())
+    }
+
+    fn bulks(list: &[&str]) -> RespValue {
+        RespValue::Array(
+            list.iter()
+                .map(|s| RespValue::BulkString(Some(s.to_string())))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn f1_parse_timeout() {
+        assert_eq!(parse_timeout("0"), Ok(None));
+        assert_eq!(parse_timeout("1.5"), Ok(Some(Duration::from_millis(1500))));
+        assert_eq!(
+            parse_timeout("1e20"),
+            Err(RespValue::Error("ERR timeout is out of range".to_string()))
+        );
+        assert_eq!(
+            parse_timeout("-1"),
+            Err(RespValue::Error("ERR timeout is negative".to_string()))
+        );
+        assert!(parse_timeout("inf").is_err());
+        assert!(parse_timeout("abc").is_err());
+    }
+
+    #[test]
+    fn f2_linsert() {
+        let mut db = Database::new();
+        let mut linsert =
+            |args: &[&str]| HandleList::handle_linsert(&mut db, command("LINSERT", args));
+        assert_eq!(linsert(&["l", "BEFORE", "a", "x"]), RespValue::Integer(0));
+        let mut db = Database::new();
+        HandleList::handle_rpush(&mut db, command("RPUSH", &["l", "a", "b", "a"]));
+        let mut linsert =
+            |args: &[&str]| HandleList::handle_linsert(&mut db, command("LINSERT", args));
+        assert_eq!(linsert(&["l", "BEFORE", "z", "x"]), RespValue::Integer(-1));
+        assert_eq!(
+            linsert(&["l", "MIDDLE", "a", "x"]),
+            RespValue::Error("ERR syntax error".to_string())
+        );
+        // 只在第一个匹配的位置插入
+        assert_eq!(linsert(&["l", "after", "a", "x"]), RespValue::Integer(4));
+        assert_eq!(linsert(&["l", "BEFORE", "a", "y"]), RespValue::Integer(5));
+        assert_eq!(
+            HandleList::handle_lrange(&mut db, command("LRANGE", &["l", "0", "-1"])),
+            bulks(&["y", "a", "x", "b", "a"])
+        );
+        db.set("s".into(), Value::String("v".into()));
+        assert!(matches!(
+            HandleList::handle_linsert(&mut db, command("LINSERT", &["s", "BEFORE", "v", "x"])),
+            RespValue::Error(e) if e.starts_with("WRONGTYPE")
+        ));
+    }
+
+    #[test]
+    fn f3_lpos() {
+        let mut db = Database::new();
+        HandleList::handle_rpush(
+            &mut db,
+            command("RPUSH", &["l", "a", "b", "c", "1", "2", "3", "c", "c"]),
+        );
+        let mut lpos = |args: &[&str]| HandleList::handle_lpos(&mut db, command("LPOS", args));
+        assert_eq!(lpos(&["l", "c"]), RespValue::Integer(2));
+        assert_eq!(lpos(&["l", "c", "RANK", "2"]), RespValue::Integer(6));
+        assert_eq!(lpos(&["l", "c", "RANK", "-1"]), RespValue::Integer(7));
+        assert_eq!(lpos(&["l", "c", "RANK", "4"]), RespValue::Null);
+        assert_eq!(lpos(&["l", "c", "COUNT", "0"]), ints(&[2, 6, 7]));
+        assert_eq!(lpos(&["l", "c", "RANK", "-1", "COUNT", "2"]), ints(&[7, 6]));
+        // MAXLEN 限制比较的元素个数
+        assert_eq!(lpos(&["l", "c", "COUNT", "0", "MAXLEN", "3"]), ints(&[2]));
+        assert_eq!(
+            lpos(&["l", "c", "RANK", "-1", "MAXLEN", "1", "COUNT", "0"]),
+            ints(&[7])
+        );
+        assert_eq!(lpos(&["l", "x", "COUNT", "0"]), ints(&[]));
+        assert_eq!(lpos(&["missing", "c"]), RespValue::Null);
+        assert_eq!(lpos(&["missing", "c", "COUNT", "1"]), ints(&[]));
+        assert!(
+            matches!(lpos(&["l", "c", "RANK", "0"]), RespValue::Error(e) if e.contains("RANK can't be zero"))
+        );
+        assert_eq!(
+            lpos(&["l", "c", "COUNT", "-1"]),
+            RespValue::Error("ERR COUNT can't be negative".to_string())
+        );
+        assert_eq!(
+            lpos(&["l", "c", "COUNT"]),
+            RespValue::Error("ERR syntax error".to_string())
+        );
+        assert_eq!(
+            lpos(&["l", "c", "RANK", &i64::MIN.to_string()]),
+            RespValue::Null
+        );
+    }
+
+    #[test]
+    fn f4_lmpop() {
+        let mut db = Database::new();
+        HandleList::handle_rpush(&mut db, command("RPUSH", &["b", "1", "2", "3"]));
+        let mut lmpop =
+            |args: &[&str]| HandleList::handle_lmpop(&mut db, 2, command("LMPOP", args));
+        assert_eq!(lmpop(&["1", "a", "LEFT"]), RespValue::NullArray);
+        // 从第一个非空的列表弹出，COUNT 超过长度时弹出全部并删除 key
+        assert_eq!(
+            lmpop(&["2", "a", "b", "RIGHT", "COUNT", "2"]),
+            RespValue::Array(vec![
+                RespValue::BulkString(Some("b".into())),
+                bulks(&["3", "2"])
+            ])
+        );
+        assert_eq!(
+            lmpop(&["2", "a", "b", "LEFT", "COUNT", "10"]),
+            RespValue::Array(vec![RespValue::BulkString(Some("b".into())), bulks(&["1"])])
+        );
+        assert_eq!(lmpop(&["1", "b", "LEFT"]), RespValue::NullArray);
+        assert_eq!(
+            lmpop(&["0", "a", "LEFT"]),
+            RespValue::Error("ERR numkeys should be greater than 0".to_string())
+        );
+        assert_eq!(
+            lmpop(&["3", "a", "b", "LEFT"]),
+            RespValue::Error("ERR syntax error".to_string())
+        );
+        assert_eq!(
+            lmpop(&["1", "a", "LEFT", "COUNT", "0"]),
+            RespValue::Error("ERR count should be greater than 0".to_string())
+        );
+        assert_eq!(
+            lmpop(&["1", "a", "UP"]),
+            RespValue::Error("ERR syntax error".to_string())
+        );
+        assert!(!db.exists("b"));
+        assert_eq!(
+            HandleList::handle_lmpop(&mut db, 3, command("LMPOP", &["1", "b", "LEFT"])),
+            RespValue::Null
+        );
+    }
+
+    #[test]
+    fn f5_pop_missing_key() {
+        let mut db = Database::new();
+        let mut lpop = |protocol, args: &[&str]| {
+            HandleList::handle_lpop(&mut db, protocol, command("LPOP", args))
+        };
+        assert_eq!(lpop(2, &["l"]), RespValue::Null);
+        assert_eq!(lpop(2, &["l", "2"]), RespValue::NullArray);
+        assert_eq!(lpop(3, &["l", "2"]), RespValue::Null);
+        HandleList::handle_rpush(&mut db, command("RPUSH", &["l", "a", "b"]));
+        assert_eq!(
+            HandleList::handle_rpop(&mut db, 2, command("RPOP", &["l", "5"])),
+            bulks(&["b", "a"])
+        );
+        assert_eq!(
+            HandleList::handle_rpop(&mut db, 2, command("RPOP", &["l", "5"])),
+            RespValue::NullArray
+        );
+    }
 }
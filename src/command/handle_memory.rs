@@ -0,0 +1,159 @@
+use crate::command::Command;
+use crate::command::handle_macro::*;
+use crate::storage::{USAGE_SAMPLES, key_overhead};
+use crate::util::{
+    format_memory, resident_memory, used_memory, used_memory_peak, used_memory_startup,
+};
+
+/// MEMORY DOCTOR 认为是大 key 的估算大小
+const BIG_KEY_BYTES: usize = 1024 * 1024;
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(Some(s.to_string()))
+}
+
+fn integer(n: usize) -> RespValue {
+    RespValue::Integer(n as i64)
+}
+
+fn percentage(part: usize, total: usize) -> RespValue {
+    bulk(&format!("{}", part as f64 * 100.0 / total.max(1) as f64))
+}
+
+pub struct HandleMemory;
+impl HandleMemory {
+    pub fn handle_memory(db: &mut Database, command: Command) -> RespValue {
+        let args = &command.args[1..];
+        match (command.args[0].to_uppercase().as_str(), args.len()) {
+            ("USAGE", 1 | 3) => Self::usage(db, args),
+            ("STATS", 0) => Self::stats(db),
+            ("DOCTOR", 0) => bulk(&Self::doctor(db)),
+            ("HELP", 0) => RespValue::Array(
+                [
+                    "MEMORY <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                    "DOCTOR",
+                    "    Return memory problems reports.",
+                    "STATS",
+                    "    Return information about the memory usage of the server.",
+                    "USAGE <key> [SAMPLES <count>]",
+                    "    Return memory in bytes used by <key> and its value. Nested values are",
+                    "    sampled up to <count> times (default: 5, 0 means sample all).",
+                    "HELP",
+                    "    Print this help.",
+                ]
+                .into_iter()
+                .map(bulk)
+                .collect(),
+            ),
+            _ => RespValue::Error(format!(
+                "ERR unknown subcommand or wrong number of arguments for '{}'. Try MEMORY HELP.",
+                command.args[0]
+            )),
+        }
+    }
+
+    /// MEMORY USAGE key [SAMPLES count]
+    fn usage(db: &Database, args: &[String]) -> RespValue {
+        let samples = match args.get(1..) {
+            Some([option, count]) if option.eq_ignore_ascii_case("SAMPLES") => {
+                match count.parse::<usize>() {
+                    Ok(n) => n,
+                    Err(_) => return RespErrNumWrong!(),
+                }
+            }
+            Some([_, _]) => return RespValue::Error("ERR syntax error".to_string()),
+            _ => USAGE_SAMPLES,
+        };
+        match db.peek(&args[0]) {
+            Some((value, _)) => integer(value.memory_usage(samples) + key_overhead(&args[0])),
+            None => RespValue::Null,
+        }
+    }
+
+    fn client_buffers(db: &Database) -> usize {
+        db.clients
+            .values()
+            .map(|client| client.outbox.pending())
+            .sum()
+    }
+
+    fn stats(db: &mut Database) -> RespValue {
+        let total = used_memory();
+        let startup = used_memory_startup();
+        let peak = used_memory_peak();
+        let resident = resident_memory();
+        let dataset = db.dataset_bytes();
+        let keys = db.data.len();
+        let net = total.saturating_sub(startup);
+        let stats = vec![
+            ("peak.allocated", integer(peak)),
+            ("total.allocated", integer(total)),
+            ("startup.allocated", integer(startup)),
+            ("clients.normal", integer(Self::client_buffers(db))),
+            ("keys.count", integer(keys)),
+            ("keys.bytes-per-key", integer(net / keys.max(1))),
+            ("dataset.bytes", integer(dataset)),
+            ("dataset.percentage", percentage(dataset, net)),
+            ("overhead.total", integer(total.saturating_sub(dataset))),
+            ("peak.percentage", percentage(total, peak)),
+            ("allocator.allocated", integer(total)),
+            ("allocator.resident", integer(resident)),
+            (
+                "fragmentation",
+                bulk(&format!("{}", resident as f64 / total.max(1) as f64)),
+            ),
+            (
+                "fragmentation.bytes",
+                RespValue::Integer(resident as i64 - total as i64),
+            ),
+        ];
+        RespValue::Map(
+            stats
+                .into_iter()
+                .map(|(name, value)| (bulk(name), value))
+                .collect(),
+        )
+    }
+
+    /// 按与 Redis 相同的规则检查内存峰值、碎片率与客户端缓冲区，另外列出大 key
+    fn doctor(db: &mut Database) -> String {
+        let total = used_memory();
+        if total < 5 * 1024 * 1024 {
+            return "Hi Sam, this instance is empty or is using very little memory, my issues detector can't be used in these conditions. Please, leave for your mission on Earth and fill it with some data. The new Sam and I will be back to our programming as soon as I finished rebooting.".to_string();
+        }
+        db.update_sizes();
+        let mut issues = Vec::new();
+        if used_memory_peak() as f64 / total as f64 > 1.5 {
+            issues.push(" * Peak memory: In the past this instance used more than 150% the memory that is currently using. The allocator is normally not able to release memory after a peak, so you can expect to see a big fragmentation ratio, however this is actually harmless and is only due to the memory peak.".to_string());
+        }
+        let resident = resident_memory();
+        if resident as f64 / total as f64 > 1.4 {
+            issues.push(format!(" * High fragmentation: This instance has a memory fragmentation greater than 1.4 (this means that the Resident Set Size of the process ({}) is much larger than the sum of the logical allocations ({})). This is usually due either to a large peak memory or to a workload that causes the allocator to fragment memory a lot.", format_memory(resident), format_memory(total)));
+        }
+        if !db.clients.is_empty() && Self::client_buffers(db) / db.clients.len() > 200 * 1024 {
+            issues.push(" * Big client buffers: The clients output buffers are in average greater than 200k. This instance may have clients that are not able to read the replies or the Pub/Sub messages fast enough. Consider lowering client-output-buffer-limit.".to_string());
+        }
+        let mut big_keys: Vec<(&String, usize)> = db
+            .data
+            .iter()
+            .map(|(key, (_, _, meta))| (key, meta.size))
+            .filter(|&(_, size)| size >= BIG_KEY_BYTES)
+            .collect();
+        if !big_keys.is_empty() {
+            big_keys.sort_by_key(|&(_, size)| std::cmp::Reverse(size));
+            let listed: Vec<String> = big_keys
+                .iter()
+                .take(10)
+                .map(|(key, size)| format!("'{}' ({})", key, format_memory(*size)))
+                .collect();
+            issues.push(format!(" * Big keys: {} keys are estimated to use more than {} each, the biggest are: {}. Deleting, evicting or reading big keys blocks the server for a long time, consider splitting them into smaller keys.", big_keys.len(), format_memory(BIG_KEY_BYTES), listed.join(", ")));
+        }
+        if issues.is_empty() {
+            return "Hi Sam, I can't find any memory issue in your instance. I can only account for what occurs on this base.".to_string();
+        }
+        format!(
+            "Sam, I detected a few issues in this Redis instance memory implants:\n\n{}\n\nI'm here to keep you safe, Sam. I want to help you.\n",
+            issues.join("\n\n")
+        )
+    }
+}
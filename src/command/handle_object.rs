@@ -0,0 +1,77 @@
+use crate::command::Command;
+use crate::command::handle_macro::*;
+use crate::storage::EVICTION_CONFIG;
+
+/// Redis 中 0..10000 的整数是共享对象，引用计数固定为 INT_MAX
+const SHARED_INTEGERS: i64 = 10000;
+
+pub struct HandleObject;
+impl HandleObject {
+    pub fn handle_object(db: &mut Database, command: Command) -> RespValue {
+        if command.args.is_empty() {
+            return RespErrArgNum!();
+        }
+        let subcommand = command.args[0].to_uppercase();
+        match (subcommand.as_str(), command.args.len()) {
+            ("ENCODING", 2) => match db.get(&command.args[1]) {
+                Some(value) => RespValue::BulkString(Some(value.encoding().to_string())),
+                None => RespValue::Null,
+            },
+            ("REFCOUNT", 2) => match db.get(&command.args[1]) {
+                Some(value @ Value::String(s))
+                    if value.encoding() == "int"
+                        && s.parse::<i64>()
+                            .is_ok_and(|n| (0..SHARED_INTEGERS).contains(&n)) =>
+                {
+                    RespValue::Integer(i32::MAX as i64)
+                }
+                Some(_) => RespValue::Integer(1),
+                None => RespValue::Null,
+            },
+            ("IDLETIME", 2) => {
+                if EVICTION_CONFIG.policy().is_lfu() {
+                    return RespValue::Error("ERR An LFU maxmemory policy is selected, idle time not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.".to_string());
+                }
+                match db.peek(&command.args[1]) {
+                    Some((_, meta)) => RespValue::Integer((meta.idle_millis() / 1000) as i64),
+                    None => RespValue::Null,
+                }
+            }
+            ("FREQ", 2) => {
+                if !EVICTION_CONFIG.policy().is_lfu() {
+                    return RespValue::Error("ERR An LFU maxmemory policy is not selected, access frequency not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.".to_string());
+                }
+                match db.peek(&command.args[1]) {
+                    Some((_, meta)) => RespValue::Integer(meta.frequency() as i64),
+                    None => RespValue::Null,
+                }
+            }
+            ("HELP", 1) => RespValue::Array(
+                [
+                    "OBJECT <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                    "ENCODING <key>",
+                    "    Return the kind of internal representation used in order to store the value",
+                    "    associated with a <key>.",
+                    "FREQ <key>",
+                    "    Return the access frequency index of the <key>. The returned integer is",
+                    "    proportional to the logarithm of the recent access frequency of the key.",
+                    "IDLETIME <key>",
+                    "    Return the idle time of the <key>, that is the approximated number of",
+                    "    seconds elapsed since the last access to the key.",
+                    "REFCOUNT <key>",
+                    "    Return the number of references of the value associated with the specified",
+                    "    <key>.",
+                    "HELP",
+                    "    Print this help.",
+                ]
+                .into_iter()
+                .map(|line| RespValue::BulkString(Some(line.to_string())))
+                .collect(),
+            ),
+            _ => RespValue::Error(format!(
+                "ERR unknown subcommand or wrong number of arguments for '{}'. Try OBJECT HELP.",
+                command.args[0]
+            )),
+        }
+    }
+}
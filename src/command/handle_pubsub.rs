@@ -0,0 +1,127 @@
+use crate::command::Command;
+use crate::command::handle_macro::*;
+use crate::command::session::Session;
+use crate::storage::SubKind;
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(Some(s.to_string()))
+}
+
+pub struct HandlePubSub;
+impl HandlePubSub {
+    /// SUBSCRIBE / PSUBSCRIBE / SSUBSCRIBE：每个频道各回复一条确认，经推送队列写出
+    pub fn handle_subscribe(db: &mut Database, session: &mut Session, command: Command) {
+        let (kind, reply) = match command.name.as_str() {
+            "SUBSCRIBE" => (SubKind::Channel, "subscribe"),
+            "PSUBSCRIBE" => (SubKind::Pattern, "psubscribe"),
+            _ => (SubKind::Shard, "ssubscribe"),
+        };
+        for name in command.args {
+            if session.subscriptions_mut(kind).insert(name.clone()) {
+                db.pubsub
+                    .subscribe(kind, &name, session.id, session.outbox.clone());
+            }
+            let count = session.subscription_count(kind) as i64;
+            session.outbox.push(RespValue::Push(vec![
+                bulk(reply),
+                bulk(&name),
+                RespValue::Integer(count),
+            ]));
+        }
+    }
+
+    /// UNSUBSCRIBE / PUNSUBSCRIBE / SUNSUBSCRIBE：不带参数时退订全部
+    pub fn handle_unsubscribe(db: &mut Database, session: &mut Session, command: Command) {
+        let (kind, reply) = match command.name.as_str() {
+            "UNSUBSCRIBE" => (SubKind::Channel, "unsubscribe"),
+            "PUNSUBSCRIBE" => (SubKind::Pattern, "punsubscribe"),
+            _ => (SubKind::Shard, "sunsubscribe"),
+        };
+        let names: Vec<String> = if command.args.is_empty() {
+            session.subscriptions_mut(kind).iter().cloned().collect()
+        } else {
+            command.args
+        };
+        if names.is_empty() {
+            let count = session.subscription_count(kind) as i64;
+            session.outbox.push(RespValue::Push(vec![
+                bulk(reply),
+                RespValue::Null,
+                RespValue::Integer(count),
+            ]));
+        }
+        for name in names {
+            if session.subscriptions_mut(kind).remove(&name) {
+                db.pubsub.unsubscribe(kind, &name, session.id);
+            }
+            let count = session.subscription_count(kind) as i64;
+            session.outbox.push(RespValue::Push(vec![
+                bulk(reply),
+                bulk(&name),
+                RespValue::Integer(count),
+            ]));
+        }
+    }
+
+    /// 订阅状态下的 PING 以数组形式回复
+    pub fn handle_ping(command: Command) -> RespValue {
+        let message = command.args.first().map_or("", String::as_str);
+        RespValue::Array(vec![bulk("pong"), bulk(message)])
+    }
+
+    pub fn handle_publish(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() != 2 {
+            return RespErrArgNum!();
+        }
+        RespValue::Integer(db.pubsub.publish(&command.args[0], &command.args[1]) as i64)
+    }
+
+    pub fn handle_spublish(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() != 2 {
+            return RespErrArgNum!();
+        }
+        RespValue::Integer(db.pubsub.spublish(&command.args[0], &command.args[1]) as i64)
+    }
+
+    pub fn handle_pubsub(db: &mut Database, command: Command) -> RespValue {
+        if command.args.is_empty() {
+            return RespErrArgNum!();
+        }
+        match (command.args[0].to_uppercase().as_str(), command.args.len()) {
+            (sub @ ("CHANNELS" | "SHARDCHANNELS"), 1 | 2) => {
+                let kind = Self::channel_kind(sub);
+                let channels = db
+                    .pubsub
+                    .channels(kind, command.args.get(1).map(String::as_str));
+                RespValue::Array(channels.iter().map(|c| bulk(c)).collect())
+            }
+            (sub @ ("NUMSUB" | "SHARDNUMSUB"), _) => {
+                let kind = Self::channel_kind(sub);
+                RespValue::Array(
+                    command.args[1..]
+                        .iter()
+                        .flat_map(|c| {
+                            [
+                                bulk(c),
+                                RespValue::Integer(db.pubsub.numsub(kind, c) as i64),
+                            ]
+                        })
+                        .collect(),
+                )
+            }
+            ("NUMPAT", 1) => RespValue::Integer(db.pubsub.numpat() as i64),
+            _ => RespValue::Error(format!(
+                "ERR unknown subcommand or wrong number of arguments for '{}'. Try PUBSUB HELP.",
+                command.args[0]
+            )),
+        }
+    }
+
+    fn channel_kind(subcommand: &str) -> SubKind {
+        if subcommand.starts_with("SHARD") {
+            SubKind::Shard
+        } else {
+            SubKind::Channel
+        }
+    }
+}
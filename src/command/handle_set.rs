@@ -1,15 +1,106 @@
+use std::borrow::Cow;
 use std::collections::HashSet;
 
+use rand::Rng;
+use rand::seq::IteratorRandom;
+
 use crate::command::Command;
 use crate::command::handle_macro::*;
+use crate::storage::{NotifyClass, SetValue};
+use crate::util::glob_match;
+
+const SSCAN_DEFAULT_COUNT: usize = 10;
+/// SRANDMEMBER 负数 count 的绝对值上限，允许重复时回复的大小不受集合大小限制
+const MAX_RANDOM_COUNT: u64 = 1 << 24;
+
+#[derive(Clone, Copy)]
+enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+impl SetOp {
+    /// *STORE 命令对应的键空间事件名
+    fn store_event(self) -> &'static str {
+        match self {
+            Self::Inter => "sinterstore",
+            Self::Union => "sunionstore",
+            Self::Diff => "sdiffstore",
+        }
+    }
+}
+
+/// 读取集合，key 不存在时视为空集合
+fn get_set<'a>(db: &'a Database, key: &str) -> Result<Option<&'a SetValue>, RespValue> {
+    match db.get(key) {
+        Some(Value::Set(set)) => Ok(Some(set)),
+        Some(_) => Err(RespErrType!()),
+        None => Ok(None),
+    }
+}
+
+/// 与 Redis 一致：先检查所有 key 的类型，再计算结果
+fn compute_set_op(db: &Database, keys: &[String], op: SetOp) -> Result<HashSet<String>, RespValue> {
+    let sets = keys
+        .iter()
+        .map(|k| get_set(db, k))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut iter = sets.into_iter();
+    let mut result: HashSet<String> = iter
+        .next()
+        .flatten()
+        .map(|set| set.iter().map(Cow::into_owned).collect())
+        .unwrap_or_default();
+    for other in iter {
+        match (op, other) {
+            (SetOp::Inter, Some(other)) => result.retain(|m| other.contains(m)),
+            (SetOp::Inter, None) => result.clear(),
+            (SetOp::Union, Some(other)) => result.extend(other.iter().map(Cow::into_owned)),
+            (SetOp::Diff, Some(other)) => result.retain(|m| !other.contains(m)),
+            (SetOp::Union | SetOp::Diff, None) => {}
+        }
+    }
+    Ok(result)
+}
+
+fn set_to_array(set: impl IntoIterator<Item = String>) -> RespValue {
+    RespValue::Array(
+        set.into_iter()
+            .map(|s| RespValue::BulkString(Some(s)))
+            .collect(),
+    )
+}
+
+/// 不重复地随机选取 count 个成员，只复制选中的成员
+fn random_distinct(set: &SetValue, count: usize, rng: &mut impl Rng) -> Vec<String> {
+    if count >= set.len() {
+        return set.iter().map(Cow::into_owned).collect();
+    }
+    // count 接近集合大小时随机选取容易重复，改为遍历一次抽样
+    if count * 3 > set.len() {
+        return set
+            .iter()
+            .choose_multiple(rng, count)
+            .into_iter()
+            .map(Cow::into_owned)
+            .collect();
+    }
+    let mut chosen = HashSet::with_capacity(count);
+    while chosen.len() < count {
+        if let Some(m) = set.random(rng) {
+            chosen.insert(m.into_owned());
+        }
+    }
+    chosen.into_iter().collect()
+}
 
 pub struct HandleSet;
 
 impl HandleSet {
-    pub async fn handle_sadd(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_sadd(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() >= 2 {
-            let mut db_guard = db.lock().await;
-            match db_guard.get_mut(&command.args[0]) {
+            match db.get_mut(&command.args[0]) {
                 Some(Value::Set(set)) => {
                     let mut num = 0;
                     for v in command.args.iter().skip(1) {
@@ -17,13 +108,17 @@ impl HandleSet {
                             num += 1;
                         }
                     }
+                    if num > 0 {
+                        db.modified(&command.args[0]);
+                        db.notify(NotifyClass::Set, "sadd", &command.args[0]);
+                    }
                     RespValue::Integer(num)
                 }
                 None => {
-                    let set: HashSet<String> =
-                        HashSet::from_iter(command.args.iter().skip(1).cloned());
+                    let set = SetValue::from_iter(command.args.iter().skip(1).cloned());
                     let num = set.len();
-                    db_guard.set(command.args[0].clone(), Value::Set(set));
+                    db.set(command.args[0].clone(), Value::Set(set));
+                    db.notify(NotifyClass::Set, "sadd", &command.args[0]);
                     RespValue::Integer(num as i64)
                 }
                 _ => RespErrType!(),
@@ -33,10 +128,9 @@ impl HandleSet {
         }
     }
 
-    pub async fn handle_scard(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_scard(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 1 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            match db.get(&command.args[0]) {
                 Some(Value::Set(set)) => RespValue::Integer(set.len() as i64),
                 None => RespValue::Integer(0),
                 _ => RespErrType!(),
@@ -46,14 +140,13 @@ impl HandleSet {
         }
     }
 
-    pub async fn handle_smembers(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_smembers(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 1 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            match db.get(&command.args[0]) {
                 Some(Value::Set(set)) => {
                     let vec = set
                         .iter()
-                        .map(|e| RespValue::BulkString(Some(e.clone())))
+                        .map(|e| RespValue::BulkString(Some(e.into_owned())))
                         .collect::<Vec<_>>();
                     RespValue::Array(vec)
                 }
@@ -61,14 +154,13 @@ impl HandleSet {
                 _ => RespErrType!(),
             }
         } else {
-            RespErrType!()
+            RespErrArgNum!()
         }
     }
 
-    pub async fn handle_srem(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_srem(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() >= 2 {
-            let mut db_guard = db.lock().await;
-            match db_guard.get_mut(&command.args[0]) {
+            match db.get_mut(&command.args[0]) {
                 Some(Value::Set(set)) => {
                     let mut num = 0;
                     for v in command.args.iter().skip(1) {
@@ -76,6 +168,15 @@ impl HandleSet {
                             num += 1;
                         }
                     }
+                    let empty = set.is_empty();
+                    if num > 0 {
+                        db.modified(&command.args[0]);
+                        db.notify(NotifyClass::Set, "srem", &command.args[0]);
+                    }
+                    // 集合被清空后删除 key
+                    if empty {
+                        db.del(&command.args[0]);
+                    }
                     RespValue::Integer(num)
                 }
                 None => RespValue::Integer(0),
@@ -86,10 +187,9 @@ impl HandleSet {
         }
     }
 
-    pub async fn handle_sismember(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_sismember(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 2 {
-            let db_guard = db.lock().await;
-            match db_guard.get(&command.args[0]) {
+            match db.get(&command.args[0]) {
                 Some(Value::Set(set)) => RespValue::Integer(set.contains(&command.args[1]) as i64),
                 None => RespValue::Integer(0),
                 _ => RespErrType!(),
@@ -99,158 +199,433 @@ impl HandleSet {
         }
     }
 
-    pub async fn handle_sinter(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_smismember(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() >= 2 {
-            let db_guard = db.lock().await;
-            let mut set = match db_guard.get(&command.args[0]) {
-                Some(Value::Set(set)) => set.clone(),
-                None => HashSet::new(),
-                _ => return RespErrType!(),
-            };
-            for other_arg in command.args.iter().skip(1) {
-                match db_guard.get(other_arg) {
-                    Some(Value::Set(other_set)) => {
-                        set = set.intersection(other_set).cloned().collect();
-                    }
-                    None => return RespValue::Array(Vec::new()),
-                    _ => return RespErrType!(),
-                }
+            match get_set(db, &command.args[0]) {
+                Ok(set) => RespValue::Array(
+                    command.args[1..]
+                        .iter()
+                        .map(|m| RespValue::Integer(set.is_some_and(|s| s.contains(m)) as i64))
+                        .collect(),
+                ),
+                Err(e) => e,
             }
-            RespValue::Array(Vec::from_iter(
-                set.into_iter().map(|s| RespValue::BulkString(Some(s))),
-            ))
         } else {
             RespErrArgNum!()
         }
     }
 
-    pub async fn handle_sinterstore(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        if command.args.len() >= 2 {
-            let mut db_guard = db.lock().await;
-            let mut set = match db_guard.get(&command.args[1]) {
-                Some(Value::Set(set)) => set.clone(),
-                None => HashSet::new(),
-                _ => return RespErrType!(),
-            };
-            for other_arg in command.args.iter().skip(2) {
-                match db_guard.get(other_arg) {
-                    Some(Value::Set(other_set)) => {
-                        set = set.intersection(other_set).cloned().collect();
-                    }
-                    None => {
-                        set = HashSet::new();
-                        break;
-                    }
-                    _ => return RespErrType!(),
-                }
-            }
-            let len = set.len() as i64;
-            db_guard.set(command.args[0].to_string(), Value::Set(set));
-            RespValue::Integer(len)
+    pub fn handle_sinter(db: &mut Database, command: Command) -> RespValue {
+        Self::set_op_generic(db, command, SetOp::Inter)
+    }
+
+    pub fn handle_sinterstore(db: &mut Database, command: Command) -> RespValue {
+        Self::set_op_store_generic(db, command, SetOp::Inter)
+    }
+
+    pub fn handle_sunion(db: &mut Database, command: Command) -> RespValue {
+        Self::set_op_generic(db, command, SetOp::Union)
+    }
+
+    pub fn handle_sunionstore(db: &mut Database, command: Command) -> RespValue {
+        Self::set_op_store_generic(db, command, SetOp::Union)
+    }
+
+    pub fn handle_sdiff(db: &mut Database, command: Command) -> RespValue {
+        Self::set_op_generic(db, command, SetOp::Diff)
+    }
+
+    pub fn handle_sdiffstore(db: &mut Database, command: Command) -> RespValue {
+        Self::set_op_store_generic(db, command, SetOp::Diff)
+    }
+
+    fn set_op_generic(db: &mut Database, command: Command, op: SetOp) -> RespValue {
+        if command.args.is_empty() {
+            return RespErrArgNum!();
+        }
+        match compute_set_op(db, &command.args, op) {
+            Ok(set) => set_to_array(set),
+            Err(e) => e,
+        }
+    }
+
+    fn set_op_store_generic(db: &mut Database, command: Command, op: SetOp) -> RespValue {
+        if command.args.len() < 2 {
+            return RespErrArgNum!();
+        }
+        let set = match compute_set_op(db, &command.args[1..], op) {
+            Ok(set) => set,
+            Err(e) => return e,
+        };
+        let len = set.len() as i64;
+        // 结果为空集时删除目标 key
+        if set.is_empty() {
+            db.del(&command.args[0]);
         } else {
-            RespErrArgNum!()
+            db.set(
+                command.args[0].to_string(),
+                Value::Set(SetValue::from_iter(set)),
+            );
+            db.notify(NotifyClass::Set, op.store_event(), &command.args[0]);
         }
+        RespValue::Integer(len)
     }
 
-    pub async fn handle_sunion(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        if command.args.len() >= 2 {
-            let db_guard = db.lock().await;
-            let mut set = match db_guard.get(&command.args[0]) {
-                Some(Value::Set(set)) => set.clone(),
-                None => HashSet::new(),
-                _ => return RespErrType!(),
-            };
-            for other_arg in command.args.iter().skip(1) {
-                match db_guard.get(other_arg) {
-                    Some(Value::Set(other_set)) => {
-                        set = set.union(other_set).cloned().collect();
-                    }
-                    None => {}
-                    _ => return RespErrType!(),
+    pub fn handle_sintercard(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() < 2 {
+            return RespErrArgNum!();
+        }
+        let numkeys = match command.args[0].parse::<i64>() {
+            Ok(n) if n > 0 => n as usize,
+            _ => return RespValue::Error("ERR numkeys should be greater than 0".to_string()),
+        };
+        if numkeys > command.args.len() - 1 {
+            return RespValue::Error(
+                "ERR Number of keys can't be greater than number of args".to_string(),
+            );
+        }
+        let keys = &command.args[1..=numkeys];
+        // limit 为 0 表示不限制
+        let limit = match &command.args[numkeys + 1..] {
+            [] => 0,
+            [opt, n] if opt.eq_ignore_ascii_case("LIMIT") => match n.parse::<i64>() {
+                Ok(n) if n >= 0 => n as usize,
+                Ok(_) => return RespValue::Error("ERR LIMIT can't be negative".to_string()),
+                Err(_) => return RespErrNumWrong!(),
+            },
+            _ => return RespValue::Error("ERR syntax error".to_string()),
+        };
+
+        // 先检查所有 key 的类型，再处理不存在的 key
+        let sets = match keys
+            .iter()
+            .map(|k| get_set(db, k))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(sets) => sets,
+            Err(e) => return e,
+        };
+        let Some(sets) = sets.into_iter().collect::<Option<Vec<_>>>() else {
+            return RespValue::Integer(0);
+        };
+        // 从最小的集合开始遍历
+        let smallest = sets.iter().min_by_key(|s| s.len()).unwrap();
+        let mut count = 0;
+        for member in smallest.iter() {
+            if sets.iter().all(|s| s.contains(&member)) {
+                count += 1;
+                if limit != 0 && count >= limit {
+                    break;
                 }
             }
-            RespValue::Array(Vec::from_iter(
-                set.into_iter().map(|s| RespValue::BulkString(Some(s))),
-            ))
-        } else {
-            RespErrArgNum!()
         }
+        RespValue::Integer(count as i64)
     }
 
-    pub async fn handle_sunionstore(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        if command.args.len() >= 2 {
-            let mut db_guard = db.lock().await;
-            let mut set = match db_guard.get(&command.args[1]) {
-                Some(Value::Set(set)) => set.clone(),
-                None => HashSet::new(),
-                _ => return RespErrType!(),
-            };
-            for other_arg in command.args.iter().skip(2) {
-                match db_guard.get(other_arg) {
-                    Some(Value::Set(other_set)) => {
-                        set = set.union(other_set).cloned().collect();
-                    }
-                    None => {}
-                    _ => return RespErrType!(),
+    pub fn handle_spop(db: &mut Database, command: Command) -> RespValue {
+        let count = match command.args.len() {
+            1 => None,
+            2 => match command.args[1].parse::<i64>() {
+                Ok(n) if n >= 0 => Some(n as usize),
+                _ => {
+                    return RespValue::Error(
+                        "ERR value is out of range, must be positive".to_string(),
+                    );
                 }
+            },
+            _ => return RespErrArgNum!(),
+        };
+        let (popped, empty) = match db.get_mut(&command.args[0]) {
+            Some(Value::Set(set)) => {
+                let count = count.unwrap_or(1);
+                let popped: Vec<String> = if count >= set.len() {
+                    std::mem::take(set).iter().map(Cow::into_owned).collect()
+                } else {
+                    let mut rng = rand::rng();
+                    (0..count)
+                        .filter_map(|_| {
+                            let m = set.random(&mut rng)?.into_owned();
+                            set.remove(&m);
+                            Some(m)
+                        })
+                        .collect()
+                };
+                (popped, set.is_empty())
             }
-            let len = set.len() as i64;
-            db_guard.set(command.args[0].to_string(), Value::Set(set));
-            RespValue::Integer(len)
-        } else {
-            RespErrArgNum!()
+            Some(_) => return RespErrType!(),
+            None => (Vec::new(), false),
+        };
+        if !popped.is_empty() {
+            db.modified(&command.args[0]);
+            db.notify(NotifyClass::Set, "spop", &command.args[0]);
+        }
+        if empty {
+            db.del(&command.args[0]);
+        }
+        match count {
+            Some(_) => set_to_array(popped),
+            None => match popped.into_iter().next() {
+                Some(m) => RespValue::BulkString(Some(m)),
+                None => RespValue::Null,
+            },
         }
     }
 
-    pub async fn handle_sdiff(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        match command.args.len() {
-            2 => {
-                let db_guard = db.lock().await;
-                let set0 = match db_guard.get(&command.args[0]) {
-                    Some(Value::Set(set)) => set.clone(),
-                    None => HashSet::new(),
-                    _ => return RespErrType!(),
-                };
-                let set1 = match db_guard.get(&command.args[1]) {
-                    Some(Value::Set(set)) => set.clone(),
-                    None => HashSet::new(),
-                    _ => return RespErrType!(),
-                };
-                RespValue::Array(Vec::from_iter(
-                    set0.difference(&set1)
-                        .map(|s| RespValue::BulkString(Some(s.clone()))),
-                ))
+    pub fn handle_srandmember(db: &mut Database, command: Command) -> RespValue {
+        let count = match command.args.len() {
+            1 => None,
+            2 => match command.args[1].parse::<i64>() {
+                Ok(n) if n >= 0 || n.unsigned_abs() <= MAX_RANDOM_COUNT => Some(n),
+                Ok(_) => return RespValue::Error("ERR value is out of range".to_string()),
+                Err(_) => return RespErrNumWrong!(),
+            },
+            _ => return RespErrArgNum!(),
+        };
+        let set = match get_set(db, &command.args[0]) {
+            Ok(set) => set,
+            Err(e) => return e,
+        };
+        let mut rng = rand::rng();
+        match (count, set) {
+            (None, None) => RespValue::Null,
+            (None, Some(set)) => match set.random(&mut rng) {
+                Some(m) => RespValue::BulkString(Some(m.into_owned())),
+                None => RespValue::Null,
+            },
+            (Some(_), None) => RespValue::Array(Vec::new()),
+            // 正数返回不重复的成员
+            (Some(n), Some(set)) if n >= 0 => {
+                set_to_array(random_distinct(set, n as usize, &mut rng))
+            }
+            // 负数允许重复，返回 |n| 个成员
+            (Some(n), Some(set)) => set_to_array(
+                (0..n.unsigned_abs()).filter_map(|_| set.random(&mut rng).map(Cow::into_owned)),
+            ),
+        }
+    }
+
+    pub fn handle_smove(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() != 3 {
+            return RespErrArgNum!();
+        }
+        let (source, dest, member) = (&command.args[0], &command.args[1], &command.args[2]);
+        let contains = match (get_set(db, source), get_set(db, dest)) {
+            (Err(e), _) | (_, Err(e)) => return e,
+            (Ok(None), _) => return RespValue::Integer(0),
+            (Ok(Some(set)), _) => set.contains(member),
+        };
+        if !contains {
+            return RespValue::Integer(0);
+        }
+        if source == dest {
+            return RespValue::Integer(1);
+        }
+        if let Some(Value::Set(set)) = db.get_mut(source) {
+            set.remove(member);
+            let empty = set.is_empty();
+            db.modified(source);
+            db.notify(NotifyClass::Set, "srem", source);
+            if empty {
+                db.del(source);
+            }
+        }
+        match db.get_mut(dest) {
+            Some(Value::Set(set)) => {
+                set.insert(member.clone());
+                db.modified(dest);
             }
             _ => {
-                RespErrArgNum!()
+                db.set(
+                    dest.clone(),
+                    Value::Set(SetValue::from_iter([member.clone()])),
+                );
             }
         }
+        db.notify(NotifyClass::Set, "sadd", dest);
+        RespValue::Integer(1)
     }
 
-    pub async fn handle_sdiffstore(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        match command.args.len() {
-            3 => {
-                let mut db_guard = db.lock().await;
-                let set0 = match db_guard.get(&command.args[1]) {
-                    Some(Value::Set(set)) => set.clone(),
-                    None => HashSet::new(),
-                    _ => return RespErrType!(),
-                };
-                let set1 = match db_guard.get(&command.args[2]) {
-                    Some(Value::Set(set)) => set.clone(),
-                    None => HashSet::new(),
-                    _ => return RespErrType!(),
-                };
+    pub fn handle_sscan(db: &mut Database, command: Command) -> RespValue {
+        if command.args.len() < 2 {
+            return RespErrArgNum!();
+        }
+        let Ok(cursor) = command.args[1].parse::<u64>() else {
+            return RespValue::Error("ERR invalid cursor".to_string());
+        };
+        let mut pattern: Option<&str> = None;
+        let mut count = SSCAN_DEFAULT_COUNT;
+        for pair in command.args[2..].chunks(2) {
+            match pair {
+                [opt, p] if opt.eq_ignore_ascii_case("MATCH") => pattern = Some(p),
+                [opt, n] if opt.eq_ignore_ascii_case("COUNT") => match n.parse::<i64>() {
+                    Ok(n) if n > 0 => count = n as usize,
+                    Ok(_) => return RespValue::Error("ERR syntax error".to_string()),
+                    Err(_) => return RespErrNumWrong!(),
+                },
+                _ => return RespValue::Error("ERR syntax error".to_string()),
+            }
+        }
 
-                let set: HashSet<String> = set0.difference(&set1).cloned().collect();
-                let len = set.len() as i64;
-                db_guard.set(command.args[0].to_string(), Value::Set(set));
+        let set = match get_set(db, &command.args[0]) {
+            Ok(Some(set)) => set,
+            Ok(None) => {
+                return RespValue::Array(vec![
+                    RespValue::BulkString(Some("0".to_string())),
+                    RespValue::Array(Vec::new()),
+                ]);
+            }
+            Err(e) => return e,
+        };
+        let mut batch = Vec::new();
+        let next_cursor = set.scan(cursor, count, |m| {
+            if pattern.is_none_or(|p| glob_match(p, &m)) {
+                batch.push(m.into_owned());
+            }
+        });
+        RespValue::Array(vec![
+            RespValue::BulkString(Some(next_cursor.to_string())),
+            set_to_array(batch),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command(name: &str, args: &[&str]) -> Command {
+        Command {
+            name: name.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+        }
+    }
 
-                RespValue::Integer(len)
+    #[test]
+    fn f1_random_count_range() {
+        let mut db = Database::new();
+        HandleSet::handle_sadd(&mut db, command("SADD", &["s", "a", "b", "c"]));
+        let out_of_range = RespValue::Error("ERR value is out of range".to_string());
+        for count in [i64::MIN.to_string(), "-16777217".to_string()] {
+            assert_eq!(
+                HandleSet::handle_srandmember(&mut db, command("SRANDMEMBER", &["s", &count])),
+                out_of_range
+            );
+        }
+        // 正数不超过集合大小，负数可以重复
+        for count in ["16777217", &i64::MAX.to_string()] {
+            match HandleSet::handle_srandmember(&mut db, command("SRANDMEMBER", &["s", count])) {
+                RespValue::Array(members) => assert_eq!(members.len(), 3),
+                other => panic!("unexpected {:?}", other),
             }
-            _ => {
-                RespErrArgNum!()
+        }
+        match HandleSet::handle_srandmember(&mut db, command("SRANDMEMBER", &["s", "-5"])) {
+            RespValue::Array(members) => assert_eq!(members.len(), 5),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            HandleSet::handle_spop(&mut db, command("SPOP", &["s", "-1"])),
+            RespValue::Error(_)
+        ));
+        match HandleSet::handle_spop(&mut db, command("SPOP", &["s", &i64::MAX.to_string()])) {
+            RespValue::Array(members) => assert_eq!(members.len(), 3),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(!db.exists("s"));
+    }
+
+    #[test]
+    fn f2_noop_keeps_watch_version() {
+        let mut db = Database::new();
+        HandleSet::handle_sadd(&mut db, command("SADD", &["s", "a"]));
+        let version = db.watch("s");
+        HandleSet::handle_sadd(&mut db, command("SADD", &["s", "a"]));
+        HandleSet::handle_srem(&mut db, command("SREM", &["s", "x"]));
+        assert_eq!(db.version("s"), version);
+        HandleSet::handle_sadd(&mut db, command("SADD", &["s", "b"]));
+        assert_ne!(db.version("s"), version);
+    }
+
+    #[test]
+    fn f3_sintercard_checks_all_types() {
+        let mut db = Database::new();
+        HandleSet::handle_sadd(&mut db, command("SADD", &["a", "x", "y"]));
+        HandleSet::handle_sadd(&mut db, command("SADD", &["b", "y"]));
+        db.set("s".into(), Value::String("v".into()));
+        let mut sintercard =
+            |args: &[&str]| HandleSet::handle_sintercard(&mut db, command("SINTERCARD", args));
+        assert_eq!(sintercard(&["2", "a", "b"]), RespValue::Integer(1));
+        assert_eq!(sintercard(&["2", "a", "missing"]), RespValue::Integer(0));
+        // 不存在的 key 之后的错误类型 key 同样报错
+        assert!(matches!(
+            sintercard(&["3", "a", "missing", "s"]),
+            RespValue::Error(e) if e.starts_with("WRONGTYPE")
+        ));
+    }
+
+    #[test]
+    fn f4_sscan_and_spop_on_hashtable() {
+        let mut db = Database::new();
+        let members: Vec<String> = (0..1000).map(|n| format!("m{}", n)).collect();
+        let mut args = vec!["s"];
+        args.extend(members.iter().map(String::as_str));
+        HandleSet::handle_sadd(&mut db, command("SADD", &args));
+
+        // 每次调用只返回大约 COUNT 个成员，遍历结束时覆盖全部成员
+        let mut seen = HashSet::new();
+        let mut cursor = "0".to_string();
+        loop {
+            let reply =
+                HandleSet::handle_sscan(&mut db, command("SSCAN", &["s", &cursor, "COUNT", "20"]));
+            let RespValue::Array(mut items) = reply else {
+                panic!("unexpected {:?}", reply);
+            };
+            let (Some(RespValue::Array(batch)), Some(RespValue::BulkString(Some(next)))) =
+                (items.pop(), items.pop())
+            else {
+                panic!("unexpected SSCAN reply");
+            };
+            assert!(batch.len() < 100);
+            for member in batch {
+                if let RespValue::BulkString(Some(m)) = member {
+                    seen.insert(m);
+                }
+            }
+            cursor = next;
+            if cursor == "0" {
+                break;
+            }
+        }
+        assert_eq!(seen.len(), 1000);
+
+        let reply = HandleSet::handle_spop(&mut db, command("SPOP", &["s"]));
+        let RespValue::BulkString(Some(popped)) = reply else {
+            panic!("unexpected {:?}", reply);
+        };
+        assert!(members.contains(&popped));
+        assert_eq!(
+            HandleSet::handle_sismember(&mut db, command("SISMEMBER", &["s", &popped])),
+            RespValue::Integer(0)
+        );
+        match HandleSet::handle_spop(&mut db, command("SPOP", &["s", "10"])) {
+            RespValue::Array(popped) => assert_eq!(popped.len(), 10),
+            other => panic!("unexpected {:?}", other),
+        }
+        match HandleSet::handle_srandmember(&mut db, command("SRANDMEMBER", &["s", "100"])) {
+            RespValue::Array(members) => {
+                let distinct: HashSet<String> = members
+                    .into_iter()
+                    .map(|m| match m {
+                        RespValue::BulkString(Some(m)) => m,
+                        other => panic!("unexpected {:?}", other),
+                    })
+                    .collect();
+                assert_eq!(distinct.len(), 100);
             }
+            other => panic!("unexpected {:?}", other),
         }
+        assert_eq!(
+            HandleSet::handle_scard(&mut db, command("SCARD", &["s"])),
+            RespValue::Integer(989)
+        );
     }
 }
@@ -0,0 +1,79 @@
+use crate::command::Command;
+use crate::command::handle_macro::*;
+use crate::command::slowlog::{SLOWLOG, SlowlogEntry};
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(Some(s.to_string()))
+}
+
+pub struct HandleSlowlog;
+impl HandleSlowlog {
+    pub fn handle_slowlog(command: Command) -> RespValue {
+        let args = &command.args[1..];
+        match (command.args[0].to_uppercase().as_str(), args.len()) {
+            ("GET", 0 | 1) => Self::get(args.first()),
+            ("LEN", 0) => RespValue::Integer(SLOWLOG.lock().unwrap().entries.len() as i64),
+            ("RESET", 0) => {
+                SLOWLOG.lock().unwrap().entries.clear();
+                RespOK!()
+            }
+            ("HELP", 0) => RespValue::Array(
+                [
+                    "SLOWLOG <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                    "GET [<count>]",
+                    "    Return top <count> entries from the slowlog (default: 10, -1 mean all).",
+                    "    Entries are made of:",
+                    "    id, timestamp, time in microseconds, arguments array, client IP and port,",
+                    "    client name",
+                    "LEN",
+                    "    Return the length of the slowlog.",
+                    "RESET",
+                    "    Reset the slowlog.",
+                    "HELP",
+                    "    Print this help.",
+                ]
+                .into_iter()
+                .map(bulk)
+                .collect(),
+            ),
+            _ => RespValue::Error(format!(
+                "ERR unknown subcommand or wrong number of arguments for '{}'. Try SLOWLOG HELP.",
+                command.args[0]
+            )),
+        }
+    }
+
+    /// SLOWLOG GET [count]，count 为 -1 时返回全部
+    fn get(arg: Option<&String>) -> RespValue {
+        let count = match arg.map(|arg| arg.parse::<i64>()) {
+            None => 10,
+            Some(Ok(-1)) => usize::MAX,
+            Some(Ok(n)) if n >= 0 => n as usize,
+            Some(_) => {
+                return RespValue::Error(
+                    "ERR count should be greater than or equal to -1".to_string(),
+                );
+            }
+        };
+        let slowlog = SLOWLOG.lock().unwrap();
+        RespValue::Array(
+            slowlog
+                .entries
+                .iter()
+                .take(count)
+                .map(Self::entry)
+                .collect(),
+        )
+    }
+
+    fn entry(entry: &SlowlogEntry) -> RespValue {
+        RespValue::Array(vec![
+            RespValue::Integer(entry.id as i64),
+            RespValue::Integer(entry.timestamp as i64),
+            RespValue::Integer(entry.duration_usec as i64),
+            RespValue::Array(entry.argv.iter().map(|arg| bulk(arg)).collect()),
+            bulk(&entry.addr),
+            bulk(&entry.name),
+        ])
+    }
+}
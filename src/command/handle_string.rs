@@ -2,17 +2,18 @@ use std::time::Duration;
 
 use crate::command::Command;
 use crate::command::handle_macro::*;
+use crate::storage::NotifyClass;
 
 pub struct HandleString;
 impl HandleString {
-    pub async fn handle_set(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        let mut db_guard = db.lock().await;
+    pub fn handle_set(db: &mut Database, command: Command) -> RespValue {
         match command.args.len() {
             2 => {
-                db_guard.set(
+                db.set(
                     command.args[0].clone(),
                     Value::String(command.args[1].clone()),
                 );
+                db.notify(NotifyClass::String, "set", &command.args[0]);
                 RespOK!()
             }
             4 => {
@@ -40,21 +41,22 @@ impl HandleString {
                         return RespValue::Error("ERR syntax error".to_string());
                     }
                 }
-                db_guard.set_with_duration(
+                db.set_with_duration(
                     command.args[0].clone(),
                     Value::String(command.args[1].clone()),
                     Some(duration),
                 );
+                db.notify(NotifyClass::String, "set", &command.args[0]);
+                db.notify(NotifyClass::Generic, "expire", &command.args[0]);
                 RespOK!()
             }
             _ => RespErrArgNum!(),
         }
     }
 
-    pub async fn handle_get(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        let db_guard = db.lock().await;
+    pub fn handle_get(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 1 {
-            match db_guard.get(&command.args[0]) {
+            match db.get(&command.args[0]) {
                 Some(Value::String(s)) => RespValue::BulkString(Some(s.to_string())),
                 Some(_) => RespErrType!(),
                 None => RespValue::Null,
@@ -64,18 +66,17 @@ impl HandleString {
         }
     }
 
-    pub async fn handle_del(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        let mut db_guard = db.lock().await;
+    pub fn handle_del(db: &mut Database, command: Command) -> RespValue {
         match command.args.len() {
             0 => RespErrArgNum!(),
             1 => {
-                db_guard.del(&command.args[0]);
+                db.del(&command.args[0]);
                 RespOK!()
             }
             _ => {
                 let mut num = 0;
                 for k in command.args {
-                    if db_guard.del(&k).is_some() {
+                    if db.del(&k).is_some() {
                         num += 1;
                     }
                 }
@@ -84,12 +85,11 @@ impl HandleString {
         }
     }
 
-    pub async fn handle_exists(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        let db_guard = db.lock().await;
+    pub fn handle_exists(db: &mut Database, command: Command) -> RespValue {
         if !command.args.is_empty() {
             let mut num = 0;
             for k in command.args {
-                if db_guard.exists(&k) {
+                if db.exists(&k) {
                     num += 1;
                 }
             }
@@ -99,23 +99,25 @@ impl HandleString {
         }
     }
 
-    pub async fn handle_incr(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        let mut db_guard = db.lock().await;
+    #[allow(clippy::collapsible_if)]
+    pub fn handle_incr(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 1 {
             let key = command.args[0].clone();
-            match db_guard.get(&key) {
+            match db.get(&key) {
                 Some(val) => {
                     if let Value::String(s) = val {
                         if let Ok(mut n) = s.parse::<i64>() {
                             n += 1;
-                            db_guard.set(key, Value::String(n.to_string()));
+                            db.set(key.clone(), Value::String(n.to_string()));
+                            db.notify(NotifyClass::String, "incrby", &key);
                             return RespValue::Integer(n);
                         }
                     }
                     RespValue::Error("ERR value is not an integer or out of range".to_string())
                 }
                 None => {
-                    db_guard.set(key, Value::String("1".to_string()));
+                    db.set(key.clone(), Value::String("1".to_string()));
+                    db.notify(NotifyClass::String, "incrby", &key);
                     RespValue::Integer(1)
                 }
             }
@@ -124,23 +126,25 @@ impl HandleString {
         }
     }
 
-    pub async fn handle_decr(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
-        let mut db_guard = db.lock().await;
+    #[allow(clippy::collapsible_if)]
+    pub fn handle_decr(db: &mut Database, command: Command) -> RespValue {
         if command.args.len() == 1 {
             let key = command.args[0].clone();
-            match db_guard.get(&key) {
+            match db.get(&key) {
                 Some(val) => {
                     if let Value::String(s) = val {
                         if let Ok(mut n) = s.parse::<i64>() {
                             n -= 1;
-                            db_guard.set(key, Value::String(n.to_string()));
+                            db.set(key.clone(), Value::String(n.to_string()));
+                            db.notify(NotifyClass::String, "incrby", &key);
                             return RespValue::Integer(n);
                         }
                     }
                     RespValue::Error("ERR value is not an integer or out of range".to_string())
                 }
                 None => {
-                    db_guard.set(key, Value::String("-1".to_string()));
+                    db.set(key.clone(), Value::String("-1".to_string()));
+                    db.notify(NotifyClass::String, "incrby", &key);
                     RespValue::Integer(-1)
                 }
             }
@@ -1,5 +1,8 @@
+use std::sync::atomic::Ordering;
+
 use crate::command::Command;
 use crate::command::handle_macro::*;
+use crate::server::{STATS, log, request_shutdown};
 
 pub struct HandleSys;
 impl HandleSys {
@@ -19,20 +22,56 @@ impl HandleSys {
         }
     }
 
-    pub async fn handle_clean(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    /// SHUTDOWN [NOSAVE|SAVE] [NOW] [FORCE] [ABORT]：没有持久化，不带参数或 NOSAVE 时直接关闭，
+    /// SAVE 因无法保存而返回错误，服务器继续运行
+    ///
+    /// 关闭是立即进行的，没有可以 ABORT 的等待中的关闭
+    pub fn handle_shutdown(command: Command) -> Option<RespValue> {
+        let (mut save, mut nosave, mut abort) = (false, false, false);
+        for arg in &command.args {
+            match arg.to_uppercase().as_str() {
+                "SAVE" => save = true,
+                "NOSAVE" => nosave = true,
+                "NOW" | "FORCE" => {}
+                "ABORT" => abort = true,
+                _ => return Some(RespValue::Error("ERR syntax error".to_string())),
+            }
+        }
+        if (save && nosave) || (abort && command.args.len() > 1) {
+            return Some(RespValue::Error("ERR syntax error".to_string()));
+        }
+        if abort {
+            return Some(RespValue::Error("ERR No shutdown in progress.".to_string()));
+        }
+        // 没有实现持久化，无法按要求保存，与 Redis 保存失败时一样拒绝关闭
+        if save {
+            log!(
+                Notice,
+                "Error trying to save the DB: persistence is not implemented"
+            );
+            return Some(RespValue::Error(
+                "ERR Errors trying to SHUTDOWN. Check logs.".to_string(),
+            ));
+        }
+        request_shutdown();
+        None
+    }
+
+    pub fn handle_clean(db: &mut Database, command: Command) -> RespValue {
         if command.args.is_empty() {
-            let mut db_guard = db.lock().await;
-            db_guard.clean_expired();
+            let expired = db.clean_expired();
+            STATS
+                .expired_keys
+                .fetch_add(expired as u64, Ordering::Relaxed);
             RespOK!()
         } else {
             RespErrArgNum!()
         }
     }
 
-    pub async fn handle_flushdb(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    pub fn handle_flushdb(db: &mut Database, command: Command) -> RespValue {
         if command.args.is_empty() {
-            let mut db_guard = db.lock().await;
-            db_guard.data.clear();
+            db.clear();
             RespOK!()
         } else {
             RespErrArgNum!()
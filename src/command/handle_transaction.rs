@@ -0,0 +1,82 @@
+use crate::command::Command;
+use crate::command::handle_list::HandleList;
+use crate::command::handle_macro::*;
+use crate::command::session::Session;
+
+pub struct HandleTransaction;
+impl HandleTransaction {
+    pub fn handle_multi(session: &mut Session) -> RespValue {
+        if session.in_multi() {
+            return RespValue::Error("ERR MULTI calls can not be nested".to_string());
+        }
+        session.multi = Some(Vec::new());
+        RespOK!()
+    }
+
+    pub async fn handle_discard(db: Arc<Mutex<Database>>, session: &mut Session) -> RespValue {
+        if !session.in_multi() {
+            return RespValue::Error("ERR DISCARD without MULTI".to_string());
+        }
+        session.discard_multi();
+        session.unwatch_all(&mut *db.lock().await);
+        RespOK!()
+    }
+
+    pub async fn handle_watch(
+        db: Arc<Mutex<Database>>,
+        session: &mut Session,
+        command: Command,
+    ) -> RespValue {
+        if session.in_multi() {
+            return RespValue::Error("ERR WATCH inside MULTI is not allowed".to_string());
+        }
+        let mut db = db.lock().await;
+        for key in command.args {
+            if session.watched.iter().any(|(k, _, _)| *k == key) {
+                continue;
+            }
+            let version = db.watch(&key);
+            let alive = db.exists(&key);
+            session.watched.push((key, version, alive));
+        }
+        RespOK!()
+    }
+
+    pub async fn handle_unwatch(db: Arc<Mutex<Database>>, session: &mut Session) -> RespValue {
+        session.unwatch_all(&mut *db.lock().await);
+        RespOK!()
+    }
+
+    /// 在一次加锁内依次执行排队的命令，WATCH 的 key 被修改时返回 Null，
+    /// 已经没有权限执行的命令在对应位置返回错误
+    pub async fn handle_exec(db: Arc<Mutex<Database>>, session: &mut Session) -> RespValue {
+        let Some(queue) = session.multi.take() else {
+            return RespValue::Error("ERR EXEC without MULTI".to_string());
+        };
+        let aborted = session.multi_error;
+        session.discard_multi();
+
+        let mut db = db.lock().await;
+        if aborted {
+            session.unwatch_all(&mut db);
+            return RespValue::Error(
+                "EXECABORT Transaction discarded because of previous errors.".to_string(),
+            );
+        }
+        let dirty = session.watched_dirty(&db);
+        session.unwatch_all(&mut db);
+        if dirty {
+            return RespValue::Null;
+        }
+        let replies = queue
+            .into_iter()
+            .map(|command| match Command::check_queued(session, &command) {
+                Ok(()) => Command::call(&mut db, session, command),
+                Err(e) => e,
+            })
+            .collect();
+        // 整个事务执行完毕后才唤醒阻塞的客户端
+        HandleList::serve_blocked_clients(&mut db);
+        RespValue::Array(replies)
+    }
+}
@@ -1,4 +1,12 @@
 // src/command/mod.rs
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::server::{STATS, add_sample};
+
+pub mod acl;
+pub mod auth;
+mod config_file;
 mod handle_macro;
 use handle_macro::*;
 mod handle_sys;
@@ -11,6 +19,35 @@ mod handle_list;
 use handle_list::HandleList;
 mod handle_set;
 use handle_set::HandleSet;
+mod handle_object;
+use handle_object::HandleObject;
+mod handle_info;
+use handle_info::HandleInfo;
+mod handle_memory;
+use handle_memory::HandleMemory;
+mod handle_client;
+use handle_client::HandleClient;
+mod handle_acl;
+use handle_acl::HandleAcl;
+mod handle_config;
+use handle_config::HandleConfig;
+pub use handle_config::{load_config, set_config};
+mod handle_pubsub;
+use handle_pubsub::HandlePubSub;
+mod handle_transaction;
+use handle_transaction::HandleTransaction;
+mod session;
+use session::ReplyMode;
+pub use session::Session;
+mod handle_slowlog;
+mod monitor;
+mod pause;
+mod slowlog;
+use handle_slowlog::HandleSlowlog;
+mod handle_latency;
+use handle_latency::HandleLatency;
+mod table;
+pub use table::{CommandStat, LATENCY_BUCKETS, command_stats};
 
 #[derive(Debug)]
 pub struct Command {
@@ -18,6 +55,14 @@ pub struct Command {
     pub args: Vec<String>,
 }
 
+/// call 在命令执行前收集、执行后用于记录的状态
+struct CallState {
+    spec: Option<&'static table::CommandSpec>,
+    argv: Option<Vec<String>>,
+    tracked: Vec<String>,
+    elapsed: Duration,
+}
+
 impl Command {
     pub fn parse(resp_value: RespValue) -> Result<Self, Box<GeneralError>> {
         match resp_value {
@@ -45,52 +90,569 @@ impl Command {
         }
     }
 
-    pub async fn handle(db: Arc<Mutex<Database>>, command: Command) -> RespValue {
+    /// 会阻塞等待数据的命令，事务中只排队不阻塞
+    pub fn blocks(&self, session: &Session) -> bool {
+        !session.in_multi()
+            && table::lookup(&self.name).is_some_and(|spec| spec.has_flag("blocking"))
+    }
+
+    /// 返回 None 表示回复已经通过推送队列写出
+    pub async fn handle(
+        db: Arc<Mutex<Database>>,
+        session: &mut Session,
+        command: Command,
+    ) -> Option<RespValue> {
+        // CLIENT CACHING 只对紧随其后的一条命令或一个事务生效
+        let keep_caching = (session.in_multi()
+            && !matches!(command.name.as_str(), "EXEC" | "DISCARD"))
+            || command.name == "MULTI"
+            || (command.name == "CLIENT"
+                && command
+                    .args
+                    .first()
+                    .is_some_and(|sub| sub.eq_ignore_ascii_case("CACHING")));
+        session.touch(table::lookup(&command.name).map(|spec| spec.full_name(&command.args)));
+        let response = Self::dispatch(db, session, command).await;
+        if !keep_caching {
+            session.caching = None;
+        }
+        session.sync();
+        match session.reply {
+            ReplyMode::On => response,
+            ReplyMode::Off => None,
+            ReplyMode::SkipNext => {
+                session.reply = ReplyMode::Skip;
+                None
+            }
+            ReplyMode::Skip => {
+                session.reply = ReplyMode::On;
+                None
+            }
+        }
+    }
+
+    async fn dispatch(
+        db: Arc<Mutex<Database>>,
+        session: &mut Session,
+        command: Command,
+    ) -> Option<RespValue> {
+        let Some(spec) = table::lookup(&command.name) else {
+            session.flag_multi_error();
+            return Some(RespValue::Error(format!(
+                "ERR unknown command '{}'",
+                command.name
+            )));
+        };
+        if !spec.check_arity(command.args.len() + 1) {
+            session.flag_multi_error();
+            return Self::reject(
+                spec,
+                RespValue::Error(format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    command.name.to_lowercase()
+                )),
+            );
+        }
+        if !session.authenticated && !spec.has_flag("no-auth") {
+            session.flag_multi_error();
+            return Self::reject(
+                spec,
+                RespValue::Error("NOAUTH Authentication required.".to_string()),
+            );
+        }
+        let context = if session.in_multi() {
+            "multi"
+        } else {
+            "toplevel"
+        };
+        if !spec.has_flag("no-auth")
+            && let Err(e) = Self::check_acl(session, spec, &command, context)
+        {
+            session.flag_multi_error();
+            return Self::reject(spec, e);
+        }
+        // RESP2 连接在订阅状态下只能执行订阅相关的命令
+        let resp2_subscribed = session.is_subscribed() && session.outbox.protocol() == 2;
+        if resp2_subscribed
+            && !matches!(
+                command.name.as_str(),
+                "SUBSCRIBE"
+                    | "PSUBSCRIBE"
+                    | "SSUBSCRIBE"
+                    | "UNSUBSCRIBE"
+                    | "PUNSUBSCRIBE"
+                    | "SUNSUBSCRIBE"
+                    | "PING"
+                    | "QUIT"
+                    | "RESET"
+            )
+        {
+            return Self::reject(
+                spec,
+                RespValue::Error(format!(
+                    "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                    command.name.to_lowercase()
+                )),
+            );
+        }
+
+        Self::wait_unpaused(session, spec, &command).await;
+        if let Err(e) = Self::check_memory(&db, session, spec, &command).await {
+            return Self::reject(spec, e);
+        }
+
+        // 事务控制命令与 QUIT / RESET 在事务中也立即执行
+        let transaction = match command.name.as_str() {
+            "MULTI" | "EXEC" | "DISCARD" | "WATCH" | "QUIT" | "RESET" => true,
+            "UNWATCH" => !session.in_multi(),
+            _ => false,
+        };
+        if !transaction {
+            return Self::dispatch_command(db, session, spec, command).await;
+        }
+        let argv = Self::before_execute(session, spec, &command);
+        let start = Instant::now();
+        let response = match command.name.as_str() {
+            "MULTI" => HandleTransaction::handle_multi(session),
+            "EXEC" => HandleTransaction::handle_exec(db, session).await,
+            "DISCARD" => HandleTransaction::handle_discard(db, session).await,
+            "WATCH" => HandleTransaction::handle_watch(db, session, command).await,
+            "QUIT" => {
+                // 回复写出之后断开连接
+                session.outbox.close();
+                RespOK!()
+            }
+            "RESET" => {
+                session.reset(&mut *db.lock().await);
+                RespValue::SimpleString("RESET".to_string())
+            }
+            _ => HandleTransaction::handle_unwatch(db, session).await,
+        };
+        Self::record(session, spec, argv, start.elapsed(), Some(&response));
+        Some(response)
+    }
+
+    /// 事务控制之外的命令：事务中排队，否则立即执行
+    async fn dispatch_command(
+        db: Arc<Mutex<Database>>,
+        session: &mut Session,
+        spec: &table::CommandSpec,
+        command: Command,
+    ) -> Option<RespValue> {
+        if let Some(queue) = session.multi.as_mut() {
+            if spec.has_flag("no-multi") {
+                session.multi_error = true;
+                return Self::reject(
+                    spec,
+                    RespValue::Error("ERR Command not allowed inside a transaction".to_string()),
+                );
+            }
+            queue.push(command);
+            return Some(RespValue::SimpleString("QUEUED".to_string()));
+        }
+
+        if spec.has_flag("blocking") {
+            return Some(Self::call_blocking(db, session, command).await);
+        }
+        let resp2_subscribed = session.is_subscribed() && session.outbox.protocol() == 2;
+        let direct = match command.name.as_str() {
+            "SUBSCRIBE" | "PSUBSCRIBE" | "SSUBSCRIBE" => true,
+            "UNSUBSCRIBE" | "PUNSUBSCRIBE" | "SUNSUBSCRIBE" => true,
+            "PING" => resp2_subscribed,
+            // 成功时不回复，连接随服务器关闭
+            "SHUTDOWN" => true,
+            _ => false,
+        };
+        // 其余命令在 call 中持有数据库锁执行并记录，释放锁之前唤醒因此就绪的阻塞客户端
+        if !direct {
+            let mut db = db.lock().await;
+            let response = Self::call(&mut db, session, command);
+            HandleList::serve_blocked_clients(&mut db);
+            return Some(response);
+        }
+        let argv = Self::before_execute(session, spec, &command);
+        let start = Instant::now();
+        let response = match command.name.as_str() {
+            "SUBSCRIBE" | "PSUBSCRIBE" | "SSUBSCRIBE" => {
+                HandlePubSub::handle_subscribe(&mut *db.lock().await, session, command);
+                None
+            }
+            "UNSUBSCRIBE" | "PUNSUBSCRIBE" | "SUNSUBSCRIBE" => {
+                HandlePubSub::handle_unsubscribe(&mut *db.lock().await, session, command);
+                None
+            }
+            "PING" => Some(HandlePubSub::handle_ping(command)),
+            _ => HandleSys::handle_shutdown(command),
+        };
+        Self::record(session, spec, argv, start.elapsed(), response.as_ref());
+        response
+    }
+
+    /// 命令执行前发给 MONITOR 连接，并保存慢日志需要的参数
+    fn before_execute(
+        session: &Session,
+        spec: &table::CommandSpec,
+        command: &Command,
+    ) -> Option<Vec<String>> {
+        monitor::feed(session, spec, command);
+        slowlog::capture(command)
+    }
+
+    /// 记录命令的调用次数与耗时，回复为错误时计为失败，耗时较长时写入慢日志与延迟监控
+    fn record(
+        session: &Session,
+        spec: &table::CommandSpec,
+        argv: Option<Vec<String>>,
+        elapsed: Duration,
+        response: Option<&RespValue>,
+    ) {
+        spec.stat()
+            .record(elapsed, matches!(response, Some(RespValue::Error(_))));
+        if let Some(argv) = argv {
+            slowlog::SLOWLOG.lock().unwrap().log(argv, elapsed, session);
+        }
+        add_sample("command", elapsed);
+    }
+
+    /// 命令在执行前被拒绝
+    fn reject(spec: &table::CommandSpec, error: RespValue) -> Option<RespValue> {
+        spec.stat().rejected_calls.fetch_add(1, Ordering::Relaxed);
+        Some(error)
+    }
+
+    /// CLIENT PAUSE 期间等待暂停结束，事务中排队的命令与 CLIENT 命令不受影响
+    async fn wait_unpaused(session: &Session, spec: &table::CommandSpec, command: &Command) {
+        if command.name == "CLIENT" || (session.in_multi() && command.name != "EXEC") {
+            return;
+        }
+        // EXEC 按事务中排队的命令判断
+        let write = match (&session.multi, command.name.as_str()) {
+            (Some(queue), "EXEC") => queue
+                .iter()
+                .filter_map(|c| table::lookup(&c.name))
+                .any(|s| s.has_flag("write")),
+            _ => spec.has_flag("write") || command.name == "PUBLISH",
+        };
+        pause::wait(write).await;
+    }
+
+    /// 设置了 maxmemory 时，在写命令之前按淘汰策略释放内存，内存仍然不足则拒绝 denyoom 命令
+    async fn check_memory(
+        db: &Arc<Mutex<Database>>,
+        session: &mut Session,
+        spec: &table::CommandSpec,
+        command: &Command,
+    ) -> Result<(), RespValue> {
+        if crate::storage::MAXMEMORY.load(Ordering::Relaxed) == 0 {
+            return Ok(());
+        }
+        // EXEC 按事务中排队的命令判断
+        let mut specs = vec![spec];
+        if command.name == "EXEC"
+            && let Some(queue) = &session.multi
+        {
+            specs.extend(queue.iter().filter_map(|c| table::lookup(&c.name)));
+        }
+        if !specs.iter().any(|s| s.has_flag("write")) {
+            return Ok(());
+        }
+        let mut db = db.lock().await;
+        let start = Instant::now();
+        let result = db.free_memory();
+        add_sample("eviction-cycle", start.elapsed());
+        let (Ok(evicted) | Err(evicted)) = result;
+        STATS
+            .evicted_keys
+            .fetch_add(evicted as u64, Ordering::Relaxed);
+        if result.is_ok() || !specs.iter().any(|s| s.has_flag("denyoom")) {
+            return Ok(());
+        }
+        const OOM: &str = "OOM command not allowed when used memory > 'maxmemory'.";
+        if command.name == "EXEC" {
+            session.discard_multi();
+            session.unwatch_all(&mut db);
+            return Err(RespValue::Error(format!(
+                "EXECABORT Transaction discarded because of: {}",
+                OOM
+            )));
+        }
+        session.flag_multi_error();
+        Err(RespValue::Error(OOM.to_string()))
+    }
+
+    /// 检查当前用户的命令、key 与频道权限，拒绝时记录到 ACL LOG
+    fn check_acl(
+        session: &Session,
+        spec: &table::CommandSpec,
+        command: &Command,
+        context: &'static str,
+    ) -> Result<(), RespValue> {
+        let denial = {
+            let acl = acl::ACL.read().unwrap();
+            match acl.user(&session.user) {
+                Some(user) => acl::check_command(user, spec, &command.args).err(),
+                // 登录后用户被删除
+                None => Some(acl::Denial::Command),
+            }
+        };
+        let Some(denial) = denial else {
+            return Ok(());
+        };
+        // 单独授权的子命令以 "client|kill" 的形式报告
+        let name = spec
+            .subcommand(&command.args)
+            .map_or_else(|| command.name.to_lowercase(), String::from);
+        let object = match &denial {
+            acl::Denial::Command => name.clone(),
+            acl::Denial::Key(name) | acl::Denial::Channel(name) => name.clone(),
+        };
+        acl::ACL.write().unwrap().log(
+            denial.reason(),
+            context,
+            &object,
+            &session.user,
+            session.client_info(),
+        );
+        Err(RespValue::Error(denial.message(&session.user, &name)))
+    }
+
+    /// EXEC 时按当前的权限重新检查排队的命令，排队之后权限可能已被修改
+    fn check_queued(session: &Session, command: &Command) -> Result<(), RespValue> {
+        let Some(spec) = table::lookup(&command.name) else {
+            return Ok(());
+        };
+        if spec.has_flag("no-auth") {
+            return Ok(());
+        }
+        Self::check_acl(session, spec, command, "multi").inspect_err(|_| {
+            spec.stat().rejected_calls.fetch_add(1, Ordering::Relaxed);
+        })
+    }
+
+    /// 执行单条命令，并为开启了 CLIENT TRACKING 的连接记录读取的 key
+    pub fn call(db: &mut Database, session: &mut Session, command: Command) -> RespValue {
+        let mut state = Self::before_call(db, session, &command);
+        let response = Self::run(db, session, &mut state, |db, session| {
+            Self::execute(db, session, command)
+        });
+        Self::after_call(db, session, state, &response);
+        response
+    }
+
+    /// 阻塞命令同样经由 call 的各个步骤执行与记录，耗时不包括等待数据的时间
+    async fn call_blocking(
+        db: Arc<Mutex<Database>>,
+        session: &mut Session,
+        command: Command,
+    ) -> RespValue {
+        let mut db_guard = db.lock().await;
+        let mut state = Self::before_call(&db_guard, session, &command);
+        let protocol = session.outbox.protocol();
+        let attempt = Self::run(&mut db_guard, session, &mut state, |db, session| {
+            HandleList::handle_blocking(db, session.id, protocol, command)
+        });
+        let response = match attempt {
+            Ok(response) => response,
+            Err(mut blocked) => {
+                drop(db_guard);
+                session.client.state().blocked = true;
+                let reply = blocked.wait().await;
+                session.client.state().blocked = false;
+                db_guard = db.lock().await;
+                match reply {
+                    Some(reply) => reply,
+                    None => Self::run(&mut db_guard, session, &mut state, |db, session| {
+                        blocked.timed_out(db, session.id)
+                    }),
+                }
+            }
+        };
+        Self::after_call(&mut db_guard, session, state, &response);
+        // BLMOVE 写入的目标列表可能唤醒其他阻塞的客户端
+        HandleList::serve_blocked_clients(&mut db_guard);
+        response
+    }
+
+    /// 执行前统计 key 的命中、确定要跟踪的 key，并发给 MONITOR 连接
+    fn before_call(db: &Database, session: &Session, command: &Command) -> CallState {
+        let spec = table::lookup(&command.name);
+        if let Some(spec) = spec {
+            Self::count_lookups(db, spec, command);
+        }
+        let tracked = Self::tracked_keys(db, session, command);
+        CallState {
+            spec,
+            argv: spec.and_then(|spec| Self::before_execute(session, spec, command)),
+            tracked,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// 在数据库知道当前客户端的情况下执行，并累计耗时
+    fn run<T>(
+        db: &mut Database,
+        session: &mut Session,
+        state: &mut CallState,
+        f: impl FnOnce(&mut Database, &mut Session) -> T,
+    ) -> T {
+        db.current_client = Some(session.id);
+        db.no_touch = state.spec.is_some_and(|spec| spec.has_flag("no-touch"));
+        let start = Instant::now();
+        let result = f(db, session);
+        state.elapsed += start.elapsed();
+        db.current_client = None;
+        db.no_touch = false;
+        result
+    }
+
+    /// 执行后更新内存统计、记录命令，并记录客户端缓存跟踪的 key
+    fn after_call(db: &mut Database, session: &Session, state: CallState, response: &RespValue) {
+        db.update_sizes();
+        if let Some(spec) = state.spec {
+            Self::record(session, spec, state.argv, state.elapsed, Some(response));
+        }
+        if !matches!(response, RespValue::Error(_)) {
+            for key in state.tracked {
+                db.tracking.remember(session.id, key);
+            }
+        }
+    }
+
+    /// 读命令查找的 key 计入 keyspace_hits / keyspace_misses
+    fn count_lookups(db: &Database, spec: &table::CommandSpec, command: &Command) {
+        if !spec.has_flag("readonly") || spec.has_flag("no-touch") {
+            return;
+        }
+        for key in spec.keys(&command.args) {
+            let counter = if db.exists(key) {
+                &STATS.keyspace_hits
+            } else {
+                &STATS.keyspace_misses
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn tracked_keys(db: &Database, session: &Session, command: &Command) -> Vec<String> {
+        let Some(spec) = table::lookup(&command.name) else {
+            return Vec::new();
+        };
+        let Some(options) = db.tracking.options(session.id) else {
+            return Vec::new();
+        };
+        let track = if options.bcast || !spec.has_flag("readonly") {
+            false
+        } else if options.optin {
+            session.caching == Some(true)
+        } else if options.optout {
+            session.caching != Some(false)
+        } else {
+            true
+        };
+        if !track {
+            return Vec::new();
+        }
+        spec.keys(&command.args)
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    /// 在已持有数据库锁的情况下执行单条命令
+    pub fn execute(db: &mut Database, session: &mut Session, command: Command) -> RespValue {
         match command.name.as_str() {
+            // Connection
+            "HELLO" => HandleClient::handle_hello(session, command),
+            "AUTH" => HandleClient::handle_auth(session, command),
+            "ACL" => HandleAcl::handle_acl(session, command),
+            "CLIENT" => HandleClient::handle_client(db, session, command),
             // System
             "PING" => HandleSys::handle_ping(command),
             "ECHO" => HandleSys::handle_echo(command),
-            "CLEAN" => HandleSys::handle_clean(db, command).await,
-            "FLUSHDB" => HandleSys::handle_flushdb(db, command).await,
+            "CLEAN" => HandleSys::handle_clean(db, command),
+            "FLUSHDB" => HandleSys::handle_flushdb(db, command),
+            "OBJECT" => HandleObject::handle_object(db, command),
+            "MEMORY" => HandleMemory::handle_memory(db, command),
+            "INFO" => HandleInfo::handle_info(db, command),
+            "CONFIG" => HandleConfig::handle_config(command),
+            "SLOWLOG" => HandleSlowlog::handle_slowlog(command),
+            "LATENCY" => HandleLatency::handle_latency(command),
+            "MONITOR" => {
+                monitor::add(session);
+                RespOK!()
+            }
             // String
-            "SET" => HandleString::handle_set(db, command).await,
-            "GET" => HandleString::handle_get(db, command).await,
-            "DEL" => HandleString::handle_del(db, command).await,
-            "EXISTS" => HandleString::handle_exists(db, command).await,
-            "INCR" => HandleString::handle_incr(db, command).await,
-            "DECR" => HandleString::handle_decr(db, command).await,
+            "SET" => HandleString::handle_set(db, command),
+            "GET" => HandleString::handle_get(db, command),
+            "DEL" => HandleString::handle_del(db, command),
+            "EXISTS" => HandleString::handle_exists(db, command),
+            "INCR" => HandleString::handle_incr(db, command),
+            "DECR" => HandleString::handle_decr(db, command),
             // Hash
-            "HSET" => HandleHash::handle_hset(db, command).await,
-            "HGET" => HandleHash::handle_hget(db, command).await,
-            "HGETALL" => HandleHash::handle_hgetall(db, command).await,
-            "HDEL" => HandleHash::handle_hdel(db, command).await,
-            "HEXISTS" => HandleHash::handle_hexists(db, command).await,
-            "HLEN" => HandleHash::handle_hlen(db, command).await,
-            "HKEYS" => HandleHash::handle_hkeys(db, command).await,
-            "HVALS" => HandleHash::handle_hvals(db, command).await,
+            "HSET" => HandleHash::handle_hset(db, command),
+            "HGET" => HandleHash::handle_hget(db, command),
+            "HGETALL" => HandleHash::handle_hgetall(db, command),
+            "HDEL" => HandleHash::handle_hdel(db, command),
+            "HEXISTS" => HandleHash::handle_hexists(db, command),
+            "HLEN" => HandleHash::handle_hlen(db, command),
+            "HKEYS" => HandleHash::handle_hkeys(db, command),
+            "HVALS" => HandleHash::handle_hvals(db, command),
+            "HEXPIRE" => HandleHash::handle_hexpire(db, command),
+            "HPEXPIRE" => HandleHash::handle_hpexpire(db, command),
+            "HEXPIREAT" => HandleHash::handle_hexpireat(db, command),
+            "HPEXPIREAT" => HandleHash::handle_hpexpireat(db, command),
+            "HTTL" => HandleHash::handle_httl(db, command),
+            "HPTTL" => HandleHash::handle_hpttl(db, command),
+            "HEXPIRETIME" => HandleHash::handle_hexpiretime(db, command),
+            "HPEXPIRETIME" => HandleHash::handle_hpexpiretime(db, command),
+            "HPERSIST" => HandleHash::handle_hpersist(db, command),
+            "HGETEX" => HandleHash::handle_hgetex(db, command),
             // List
-            "LPUSH" => HandleList::handle_lpush(db, command).await,
-            "RPUSH" => HandleList::handle_rpush(db, command).await,
-            "LPOP" => HandleList::handle_lpop(db, command).await,
-            "RPOP" => HandleList::handle_rpop(db, command).await,
-            "LLEN" => HandleList::handle_llen(db, command).await,
-            "LINDEX" => HandleList::handle_lindex(db, command).await,
-            "LSET" => HandleList::handle_lset(db, command).await,
-            "LRANGE" => HandleList::handle_lrange(db, command).await,
-            "LREM" => HandleList::handle_lrem(db, command).await,
-            "LTRIM" => HandleList::handle_ltrim(db, command).await,
+            "LPUSH" => HandleList::handle_lpush(db, command),
+            "RPUSH" => HandleList::handle_rpush(db, command),
+            "LPUSHX" => HandleList::handle_lpushx(db, command),
+            "RPUSHX" => HandleList::handle_rpushx(db, command),
+            "LPOP" => HandleList::handle_lpop(db, session.outbox.protocol(), command),
+            "RPOP" => HandleList::handle_rpop(db, session.outbox.protocol(), command),
+            "LLEN" => HandleList::handle_llen(db, command),
+            "LINDEX" => HandleList::handle_lindex(db, command),
+            "LSET" => HandleList::handle_lset(db, command),
+            "LRANGE" => HandleList::handle_lrange(db, command),
+            "LREM" => HandleList::handle_lrem(db, command),
+            "LTRIM" => HandleList::handle_ltrim(db, command),
+            "LINSERT" => HandleList::handle_linsert(db, command),
+            "LPOS" => HandleList::handle_lpos(db, command),
+            "LMOVE" => HandleList::handle_lmove(db, command),
+            "RPOPLPUSH" => HandleList::handle_rpoplpush(db, command),
+            "LMPOP" => HandleList::handle_lmpop(db, session.outbox.protocol(), command),
             // Set
-            "SADD" => HandleSet::handle_sadd(db, command).await,
-            "SCARD" => HandleSet::handle_scard(db, command).await,
-            "SMEMBERS" => HandleSet::handle_smembers(db, command).await,
-            "SREM" => HandleSet::handle_srem(db, command).await,
-            "SISMEMBER" => HandleSet::handle_sismember(db, command).await,
-            "SINTER" => HandleSet::handle_sinter(db, command).await,
-            "SINTERSTORE" => HandleSet::handle_sinterstore(db, command).await,
-            "SUNION" => HandleSet::handle_sunion(db, command).await,
-            "SUNIONSTORE" => HandleSet::handle_sunionstore(db, command).await,
-            "SDIFF" => HandleSet::handle_sdiff(db, command).await,
-            "SDIFFSTORE" => HandleSet::handle_sdiffstore(db, command).await,
+            "SADD" => HandleSet::handle_sadd(db, command),
+            "SCARD" => HandleSet::handle_scard(db, command),
+            "SMEMBERS" => HandleSet::handle_smembers(db, command),
+            "SREM" => HandleSet::handle_srem(db, command),
+            "SISMEMBER" => HandleSet::handle_sismember(db, command),
+            "SMISMEMBER" => HandleSet::handle_smismember(db, command),
+            "SPOP" => HandleSet::handle_spop(db, command),
+            "SRANDMEMBER" => HandleSet::handle_srandmember(db, command),
+            "SMOVE" => HandleSet::handle_smove(db, command),
+            "SINTERCARD" => HandleSet::handle_sintercard(db, command),
+            "SSCAN" => HandleSet::handle_sscan(db, command),
+            "SINTER" => HandleSet::handle_sinter(db, command),
+            "SINTERSTORE" => HandleSet::handle_sinterstore(db, command),
+            "SUNION" => HandleSet::handle_sunion(db, command),
+            "SUNIONSTORE" => HandleSet::handle_sunionstore(db, command),
+            "SDIFF" => HandleSet::handle_sdiff(db, command),
+            "SDIFFSTORE" => HandleSet::handle_sdiffstore(db, command),
+            // Pub/Sub
+            "PUBLISH" => HandlePubSub::handle_publish(db, command),
+            "SPUBLISH" => HandlePubSub::handle_spublish(db, command),
+            "PUBSUB" => HandlePubSub::handle_pubsub(db, command),
+            // 事务中的阻塞命令不会阻塞
+            "BLPOP" | "BRPOP" | "BLMOVE" | "BLMPOP" => {
+                HandleList::handle_blocking_nowait(db, session.outbox.protocol(), command)
+            }
+            // EXEC 结束时总会取消 WATCH，事务中的 UNWATCH 无需额外处理
+            "UNWATCH" => RespOK!(),
             _ => RespValue::Error(format!("ERR unknown command '{}'", command.name)),
         }
     }
@@ -113,4 +675,85 @@ mod test {
         println!("{:?}", cmd);
         println!("{:?}", String::from_utf8(hex));
     }
+
+    async fn run(
+        db: &Arc<Mutex<Database>>,
+        session: &mut Session,
+        args: &[&str],
+    ) -> Option<RespValue> {
+        let command = Command {
+            name: args[0].to_string(),
+            args: args[1..].iter().map(|s| s.to_string()).collect(),
+        };
+        Command::handle(db.clone(), session, command).await
+    }
+
+    #[tokio::test]
+    async fn f2_noauth_aborts_multi() {
+        let db = Arc::new(Mutex::new(Database::new()));
+        let (outbox, _pushes) = crate::storage::Outbox::new();
+        let mut session = Session::new(outbox, "a:1".to_string(), "b:2".to_string());
+        session.authenticated = true;
+        assert_eq!(run(&db, &mut session, &["MULTI"]).await, Some(RespOK!()));
+        assert_eq!(
+            run(&db, &mut session, &["SET", "k", "v"]).await,
+            Some(RespValue::SimpleString("QUEUED".to_string()))
+        );
+        session.authenticated = false;
+        assert_eq!(
+            run(&db, &mut session, &["SET", "k", "v"]).await,
+            Some(RespValue::Error(
+                "NOAUTH Authentication required.".to_string()
+            ))
+        );
+        // 被拒绝的命令使整个事务被放弃
+        session.authenticated = true;
+        assert_eq!(
+            run(&db, &mut session, &["EXEC"]).await,
+            Some(RespValue::Error(
+                "EXECABORT Transaction discarded because of previous errors.".to_string()
+            ))
+        );
+        assert!(!db.lock().await.exists("k"));
+    }
+
+    #[tokio::test]
+    async fn f3_exec_rechecks_acl() {
+        let _serial = crate::server::SERIAL.lock().await;
+        let user = "exec-recheck";
+        let rules = |rules: &[&str]| rules.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        acl::ACL
+            .write()
+            .unwrap()
+            .set_user(user, &rules(&["on", "nopass", "+@all", "~*"]))
+            .unwrap();
+        let db = Arc::new(Mutex::new(Database::new()));
+        let (outbox, _pushes) = crate::storage::Outbox::new();
+        let mut session = Session::new(outbox, "a:1".to_string(), "b:2".to_string());
+        session.authenticated = true;
+        session.user = user.to_string();
+        run(&db, &mut session, &["MULTI"]).await;
+        run(&db, &mut session, &["SET", "k", "v"]).await;
+        run(&db, &mut session, &["GET", "k"]).await;
+
+        // 排队之后被收回的权限在 EXEC 时生效，其余命令照常执行
+        acl::ACL
+            .write()
+            .unwrap()
+            .set_user(user, &rules(&["-set"]))
+            .unwrap();
+        let exec = run(&db, &mut session, &["EXEC"]).await;
+        acl::ACL.write().unwrap().users.remove(user);
+        assert_eq!(
+            exec,
+            Some(RespValue::Array(vec![
+                RespValue::Error(
+                    "NOPERM User exec-recheck has no permissions to run the 'set' command"
+                        .to_string()
+                ),
+                RespValue::Null,
+            ]))
+        );
+        assert!(!db.lock().await.exists("k"));
+    }
 }
@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::command::Command;
+use crate::command::Session;
+use crate::command::table::CommandSpec;
+use crate::protocol::RespValue;
+use crate::storage::Outbox;
+
+/// 执行了 MONITOR 的连接: client id -> outbox
+static MONITORS: RwLock<BTreeMap<u64, Arc<Outbox>>> = RwLock::new(BTreeMap::new());
+
+pub fn add(session: &Session) {
+    session.client.state().monitor = true;
+    MONITORS
+        .write()
+        .unwrap()
+        .insert(session.id, session.outbox.clone());
+}
+
+pub fn remove(id: u64) {
+    MONITORS.write().unwrap().remove(&id);
+}
+
+/// 按 Redis 的 sdscatrepr 加引号并转义不可打印的字节
+fn quote(arg: &str) -> String {
+    let mut quoted = String::from("\"");
+    for &byte in arg.as_bytes() {
+        match byte {
+            b'\\' => quoted.push_str("\\\\"),
+            b'"' => quoted.push_str("\\\""),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            b' '..=b'~' => quoted.push(byte as char),
+            _ => quoted.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// 不能明文记录的参数，i 为参数位置（不含命令名）：AUTH 的全部参数、HELLO 中 AUTH 之后的
+/// 用户名和密码、CONFIG SET 中 requirepass 的值，以及 ACL SETUSER 中设置密码的规则
+pub fn is_redacted(command: &Command, i: usize) -> bool {
+    let args = &command.args;
+    let sub = |name: &str| {
+        args.first()
+            .is_some_and(|arg| arg.eq_ignore_ascii_case(name))
+    };
+    match command.name.as_str() {
+        "AUTH" => true,
+        "HELLO" => args
+            .iter()
+            .position(|arg| arg.eq_ignore_ascii_case("AUTH"))
+            .is_some_and(|at| i > at && i <= at + 2),
+        // CONFIG SET name value [name value ...]
+        "CONFIG" if sub("SET") => {
+            i >= 2 && i.is_multiple_of(2) && args[i - 1].eq_ignore_ascii_case("requirepass")
+        }
+        // ACL SETUSER username rule [rule ...]
+        "ACL" if sub("SETUSER") => i >= 2 && args[i].starts_with(['>', '<', '#', '!']),
+        _ => false,
+    }
+}
+
+fn redacted_args(command: &Command) -> Vec<String> {
+    command
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            if is_redacted(command, i) {
+                "(redacted)".to_string()
+            } else {
+                quote(arg)
+            }
+        })
+        .collect()
+}
+
+fn format_line(session: &Session, command: &Command) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let mut line = format!(
+        "{}.{:06} [0 {}] {}",
+        now.as_secs(),
+        now.subsec_micros(),
+        session.addr,
+        quote(&command.name.to_lowercase())
+    );
+    for arg in redacted_args(command) {
+        line.push(' ');
+        line.push_str(&arg);
+    }
+    line
+}
+
+/// 把即将执行的命令发给所有 MONITOR 连接，管理类命令不发送
+///
+/// 推送只是入队，不会等待慢的 MONITOR 连接
+pub fn feed(session: &Session, spec: &CommandSpec, command: &Command) {
+    if spec.has_flag("admin") {
+        return;
+    }
+    let monitors = MONITORS.read().unwrap();
+    if monitors.is_empty() {
+        return;
+    }
+    let line = format_line(session, command);
+    for outbox in monitors.values() {
+        outbox.push(RespValue::SimpleString(line.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command(name: &str, args: &[&str]) -> Command {
+        Command {
+            name: name.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn f1_quote_and_redact() {
+        assert_eq!(
+            quote("a \"b\"\\\n\u{1}é"),
+            "\"a \\\"b\\\"\\\\\\n\\x01\\xc3\\xa9\""
+        );
+        assert_eq!(
+            redacted_args(&command("AUTH", &["user", "pass"])),
+            ["(redacted)", "(redacted)"]
+        );
+        assert_eq!(
+            redacted_args(&command(
+                "HELLO",
+                &["3", "auth", "user", "pass", "SETNAME", "x"]
+            )),
+            [
+                "\"3\"",
+                "\"auth\"",
+                "(redacted)",
+                "(redacted)",
+                "\"SETNAME\"",
+                "\"x\""
+            ]
+        );
+        assert_eq!(redacted_args(&command("GET", &["k"])), ["\"k\""]);
+    }
+}
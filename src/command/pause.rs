@@ -0,0 +1,78 @@
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+/// CLIENT PAUSE 的状态：暂停到何时，是否暂停所有命令（否则只暂停写命令）
+struct Pause {
+    until: Instant,
+    all: bool,
+}
+
+static PAUSE: Mutex<Option<Pause>> = Mutex::new(None);
+
+/// CLIENT UNPAUSE 时唤醒等待中的连接
+static UNPAUSED: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// 多次暂停时取较晚的结束时间与较强的模式
+pub fn pause(timeout: Duration, all: bool) {
+    let mut pause = PAUSE.lock().unwrap();
+    let until = Instant::now() + timeout;
+    *pause = match pause.take().filter(|p| p.until > Instant::now()) {
+        Some(current) => Some(Pause {
+            until: current.until.max(until),
+            all: current.all || all,
+        }),
+        None => Some(Pause { until, all }),
+    };
+}
+
+pub fn unpause() {
+    *PAUSE.lock().unwrap() = None;
+    UNPAUSED.notify_waiters();
+}
+
+/// 命令仍被暂停时返回剩余时间
+fn remaining(write: bool) -> Option<Duration> {
+    let pause = PAUSE.lock().unwrap();
+    let pause = pause.as_ref().filter(|p| p.all || write)?;
+    Some(pause.until.checked_duration_since(Instant::now())?).filter(|d| !d.is_zero())
+}
+
+/// 等待暂停结束，write 表示命令会修改数据
+pub async fn wait(write: bool) {
+    loop {
+        // 先登记再检查，避免错过检查之后的 UNPAUSE
+        let unpaused = UNPAUSED.notified();
+        let Some(remaining) = remaining(write) else {
+            return;
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(remaining) => {}
+            _ = unpaused => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn f1_pause() {
+        pause(Duration::from_millis(200), false);
+        assert!(remaining(false).is_none());
+        assert!(remaining(true).is_some());
+        // 较短的 ALL 暂停不会缩短已有的暂停
+        pause(Duration::from_millis(10), true);
+        assert!(remaining(false).unwrap() > Duration::from_millis(100));
+        let start = Instant::now();
+        tokio::spawn(async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            unpause();
+        });
+        wait(true).await;
+        assert!(start.elapsed() < Duration::from_millis(150));
+        assert!(remaining(true).is_none());
+    }
+}
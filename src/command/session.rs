@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use crate::command::{Command, auth, monitor};
+use crate::storage::{Client, Database, Outbox, SubKind};
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 单个连接的状态
+#[derive(Debug)]
+pub struct Session {
+    pub id: u64,
+    /// 客户端与本端地址，Unix socket 为 "路径:0"
+    pub addr: String,
+    pub laddr: String,
+    /// 服务端主动推送的消息经由此队列写出
+    pub outbox: Arc<Outbox>,
+    /// MULTI 之后排队等待 EXEC 的命令
+    pub multi: Option<Vec<Command>>,
+    /// 排队期间出现过语法错误，EXEC 时整个事务被放弃
+    pub multi_error: bool,
+    /// WATCH 的 key: (key, WATCH 时的版本号, WATCH 时 key 是否存在)
+    pub watched: Vec<(String, u64, bool)>,
+    pub channels: HashSet<String>,
+    pub patterns: HashSet<String>,
+    pub shard_channels: HashSet<String>,
+    /// CLIENT CACHING 的设置，只对下一条命令生效
+    pub caching: Option<bool>,
+    /// default 用户需要密码时，AUTH 之后才能执行其他命令
+    pub authenticated: bool,
+    /// 当前登录的 ACL 用户
+    pub user: String,
+    /// 登记在 Database 中、其他连接可见的状态
+    pub client: Arc<Client>,
+    pub reply: ReplyMode,
+}
+
+/// CLIENT REPLY 的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyMode {
+    On,
+    Off,
+    /// 执行 CLIENT REPLY SKIP 的这条命令，之后的一条命令也不回复
+    SkipNext,
+    Skip,
+}
+
+impl Session {
+    pub fn new(outbox: Arc<Outbox>, addr: String, laddr: String) -> Self {
+        let id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+        let client = Arc::new(Client::new(id, addr.clone(), laddr.clone(), outbox.clone()));
+        Self {
+            id,
+            addr,
+            laddr,
+            outbox,
+            multi: None,
+            multi_error: false,
+            watched: Vec::new(),
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+            shard_channels: HashSet::new(),
+            caching: None,
+            authenticated: auth::default_user_nopass(),
+            user: "default".to_string(),
+            client,
+            reply: ReplyMode::On,
+        }
+    }
+
+    /// ACL LOG 中记录的客户端信息
+    pub fn client_info(&self) -> String {
+        format!(
+            "id={} addr={} laddr={} user={}",
+            self.id, self.addr, self.laddr, self.user
+        )
+    }
+
+    /// 连接建立时登记到 Database，使其他客户端可以找到它
+    pub fn open(&self, db: &mut Database) {
+        db.clients.insert(self.id, self.client.clone());
+    }
+
+    /// 开始执行命令时记录命令全名与交互时间
+    pub fn touch(&self, command: Option<String>) {
+        let mut state = self.client.state();
+        if let Some(command) = command {
+            state.last_cmd = command;
+        }
+        state.last_interaction = Instant::now();
+    }
+
+    /// 命令执行后把连接状态同步到登记的 Client
+    pub fn sync(&self) {
+        let mut state = self.client.state();
+        state.user.clone_from(&self.user);
+        state.multi = self.multi.as_ref().map_or(-1, |queue| queue.len() as i64);
+        state.watch = self.watched.len();
+        state.sub = self.channels.len();
+        state.psub = self.patterns.len();
+        state.ssub = self.shard_channels.len();
+    }
+
+    /// 连接断开时释放在数据库中登记的状态
+    pub fn close(&mut self, db: &mut Database) {
+        self.release(db);
+        db.clients.remove(&self.id);
+    }
+
+    /// RESET：恢复为新连接的状态，连接本身与 client id 不变
+    pub fn reset(&mut self, db: &mut Database) {
+        self.discard_multi();
+        self.release(db);
+        self.caching = None;
+        self.authenticated = auth::default_user_nopass();
+        self.user = "default".to_string();
+        self.reply = ReplyMode::On;
+        self.outbox.set_protocol(2);
+        let mut state = self.client.state();
+        state.name.clear();
+        state.monitor = false;
+        state.no_evict = false;
+    }
+
+    /// 取消阻塞、WATCH、客户端缓存、MONITOR 与所有订阅，不发送退订确认
+    fn release(&mut self, db: &mut Database) {
+        db.blocking.unblock(self.id);
+        self.unwatch_all(db);
+        db.tracking.disable(self.id);
+        monitor::remove(self.id);
+        for kind in [SubKind::Channel, SubKind::Pattern, SubKind::Shard] {
+            for name in std::mem::take(self.subscriptions_mut(kind)) {
+                db.pubsub.unsubscribe(kind, &name, self.id);
+            }
+        }
+    }
+
+    pub fn subscriptions_mut(&mut self, kind: SubKind) -> &mut HashSet<String> {
+        match kind {
+            SubKind::Channel => &mut self.channels,
+            SubKind::Pattern => &mut self.patterns,
+            SubKind::Shard => &mut self.shard_channels,
+        }
+    }
+
+    /// 订阅确认中返回的订阅数，分片频道单独计数
+    pub fn subscription_count(&self, kind: SubKind) -> usize {
+        match kind {
+            SubKind::Shard => self.shard_channels.len(),
+            _ => self.channels.len() + self.patterns.len(),
+        }
+    }
+
+    /// 处于订阅状态的连接只接受订阅相关的命令
+    pub fn is_subscribed(&self) -> bool {
+        !self.channels.is_empty() || !self.patterns.is_empty() || !self.shard_channels.is_empty()
+    }
+
+    pub fn in_multi(&self) -> bool {
+        self.multi.is_some()
+    }
+
+    /// 在事务排队期间记录一次命令错误
+    pub fn flag_multi_error(&mut self) {
+        if self.in_multi() {
+            self.multi_error = true;
+        }
+    }
+
+    pub fn discard_multi(&mut self) {
+        self.multi = None;
+        self.multi_error = false;
+    }
+
+    pub fn unwatch_all(&mut self, db: &mut Database) {
+        for (key, _, _) in self.watched.drain(..) {
+            db.unwatch(&key);
+        }
+    }
+
+    /// 被 WATCH 的 key 自 WATCH 之后是否被修改或过期
+    pub fn watched_dirty(&self, db: &Database) -> bool {
+        self.watched
+            .iter()
+            .any(|(key, version, alive)| db.version(key) != *version || (*alive && !db.exists(key)))
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::command::monitor::is_redacted;
+use crate::command::{Command, Session};
+
+/// 执行时间超过该值（微秒）的命令写入慢日志，负数表示关闭，0 表示记录所有命令
+pub static SLOWLOG_LOG_SLOWER_THAN: AtomicI64 = AtomicI64::new(10_000);
+pub static SLOWLOG_MAX_LEN: AtomicUsize = AtomicUsize::new(128);
+
+/// 每条日志最多保存的参数个数与单个参数的长度
+const MAX_ARGC: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct SlowlogEntry {
+    pub id: u64,
+    /// 命令开始执行的 Unix 时间（秒）
+    pub timestamp: u64,
+    pub duration_usec: u64,
+    pub argv: Vec<String>,
+    pub addr: String,
+    pub name: String,
+}
+
+#[derive(Debug)]
+pub struct Slowlog {
+    /// 最新的条目在前
+    pub entries: VecDeque<SlowlogEntry>,
+    next_id: u64,
+}
+
+pub static SLOWLOG: Mutex<Slowlog> = Mutex::new(Slowlog {
+    entries: VecDeque::new(),
+    next_id: 0,
+});
+
+/// 执行命令之前保存截断后的参数，密码替换为 (redacted)，慢日志关闭时返回 None
+pub fn capture(command: &Command) -> Option<Vec<String>> {
+    if SLOWLOG_LOG_SLOWER_THAN.load(Ordering::Relaxed) < 0 {
+        return None;
+    }
+    let argc = command.args.len() + 1;
+    let mut argv: Vec<String> = std::iter::once(&command.name)
+        .chain(&command.args)
+        .take(if argc > MAX_ARGC { MAX_ARGC - 1 } else { argc })
+        .enumerate()
+        .map(|(i, arg)| {
+            if i > 0 && is_redacted(command, i - 1) {
+                return "(redacted)".to_string();
+            }
+            if arg.len() <= MAX_ARG_LEN {
+                return arg.clone();
+            }
+            let end = arg.floor_char_boundary(MAX_ARG_LEN);
+            format!("{}... ({} more bytes)", &arg[..end], arg.len() - end)
+        })
+        .collect();
+    if argc > MAX_ARGC {
+        argv.push(format!("... ({} more arguments)", argc - MAX_ARGC + 1));
+    }
+    Some(argv)
+}
+
+impl Slowlog {
+    /// 执行时间达到阈值时记录一条日志
+    pub fn log(&mut self, argv: Vec<String>, elapsed: Duration, session: &Session) {
+        let threshold = SLOWLOG_LOG_SLOWER_THAN.load(Ordering::Relaxed);
+        let duration_usec = elapsed.as_micros() as u64;
+        if threshold < 0 || duration_usec < threshold as u64 {
+            return;
+        }
+        let timestamp = (SystemTime::now() - elapsed)
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.entries.push_front(SlowlogEntry {
+            id: self.next_id,
+            timestamp,
+            duration_usec,
+            argv,
+            addr: session.addr.clone(),
+            name: session.client.state().name.clone(),
+        });
+        self.next_id += 1;
+        self.entries
+            .truncate(SLOWLOG_MAX_LEN.load(Ordering::Relaxed));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_capture() {
+        let command = Command {
+            name: "RPUSH".to_string(),
+            args: (0..40).map(|i| i.to_string()).collect(),
+        };
+        let argv = capture(&command).unwrap();
+        assert_eq!(argv.len(), MAX_ARGC);
+        assert_eq!(argv[0], "RPUSH");
+        assert_eq!(argv[30], "29");
+        assert_eq!(argv[31], "... (10 more arguments)");
+
+        let command = Command {
+            name: "SET".to_string(),
+            args: vec!["k".to_string(), "é".repeat(100)],
+        };
+        let argv = capture(&command).unwrap();
+        assert_eq!(argv[2], format!("{}... (72 more bytes)", "é".repeat(64)));
+
+        let command = Command {
+            name: "HELLO".to_string(),
+            args: ["3", "AUTH", "user", "secret"].map(String::from).to_vec(),
+        };
+        assert_eq!(
+            capture(&command).unwrap(),
+            ["HELLO", "3", "AUTH", "(redacted)", "(redacted)"]
+        );
+
+        let command = Command {
+            name: "CONFIG".to_string(),
+            args: ["set", "maxclients", "10", "REQUIREPASS", "secret"]
+                .map(String::from)
+                .to_vec(),
+        };
+        assert_eq!(
+            capture(&command).unwrap(),
+            [
+                "CONFIG",
+                "set",
+                "maxclients",
+                "10",
+                "REQUIREPASS",
+                "(redacted)"
+            ]
+        );
+
+        let command = Command {
+            name: "ACL".to_string(),
+            args: ["SETUSER", "alice", "on", ">p1", "<p2", "#abc", "!def", "~*"]
+                .map(String::from)
+                .to_vec(),
+        };
+        assert_eq!(
+            capture(&command).unwrap(),
+            [
+                "ACL",
+                "SETUSER",
+                "alice",
+                "on",
+                "(redacted)",
+                "(redacted)",
+                "(redacted)",
+                "(redacted)",
+                "~*"
+            ]
+        );
+    }
+}
@@ -0,0 +1,482 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 命令表：记录每个命令的参数个数、标志与 key 的位置，供分发前的统一检查使用
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// 参数个数（包含命令名），负数表示至少 -arity 个
+    pub arity: i32,
+    pub flags: &'static [&'static str],
+    /// 第一个与最后一个 key 的位置（命令名为 0），负数表示从末尾倒数，0 表示没有 key
+    pub first_key: i32,
+    pub last_key: i32,
+    pub key_step: usize,
+    /// 所属的 ACL 分类（数据类型或功能），flags 会再补充 read/write 等分类
+    pub group: &'static str,
+    /// 按位置列出每个 key 的访问方式，最后一项适用于其余 key；为空时由 write 标志决定
+    pub access: &'static [KeyAccess],
+}
+
+/// ACL 检查 key 时所需的权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAccess {
+    /// 只读取，如 SINTERSTORE 的源 key
+    Read,
+    /// 只写入或覆盖，如 SINTERSTORE 的目标 key
+    Write,
+    /// 读取并修改，如 LMOVE 弹出并返回元素的源 key
+    ReadWrite,
+}
+
+impl KeyAccess {
+    pub fn read(self) -> bool {
+        self != Self::Write
+    }
+
+    pub fn write(self) -> bool {
+        self != Self::Read
+    }
+}
+
+impl CommandSpec {
+    pub fn check_arity(&self, argc: usize) -> bool {
+        let argc = argc as i32;
+        if self.arity >= 0 {
+            argc == self.arity
+        } else {
+            argc >= -self.arity
+        }
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// CLIENT LIST 中的 cmd：带子命令的命令为 "client|list" 的形式
+    pub fn full_name(&self, args: &[String]) -> String {
+        const CONTAINERS: [&str; 7] = [
+            "ACL", "CLIENT", "CONFIG", "LATENCY", "MEMORY", "OBJECT", "SLOWLOG",
+        ];
+        let name = self.name.to_lowercase();
+        match args.first() {
+            Some(sub) if CONTAINERS.contains(&self.name) => {
+                format!("{}|{}", name, sub.to_lowercase())
+            }
+            _ => name,
+        }
+    }
+
+    /// 属于 SUBCOMMANDS、需要单独授权的子命令名
+    pub fn subcommand(&self, args: &[String]) -> Option<&'static str> {
+        let full_name = self.full_name(args);
+        SUBCOMMANDS.iter().copied().find(|&name| name == full_name)
+    }
+
+    /// ACL 分类：所属分组加上由标志推导的分类
+    pub fn categories(&self) -> Vec<&'static str> {
+        let mut categories = vec![self.group];
+        for (flag, category) in [
+            ("readonly", "read"),
+            ("write", "write"),
+            ("admin", "admin"),
+            ("admin", "dangerous"),
+            ("dangerous", "dangerous"),
+            ("blocking", "blocking"),
+            ("pubsub", "pubsub"),
+        ] {
+            if self.has_flag(flag) && !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+        categories
+    }
+
+    /// 取出 key 以及各自的访问方式
+    pub fn keys_with_access<'a>(&self, args: &'a [String]) -> Vec<(&'a str, KeyAccess)> {
+        let default = if self.has_flag("write") {
+            KeyAccess::Write
+        } else {
+            KeyAccess::Read
+        };
+        self.keys(args)
+            .into_iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let access = self.access.get(i).or(self.access.last());
+                (key, access.copied().unwrap_or(default))
+            })
+            .collect()
+    }
+
+    /// 从参数（不含命令名）中取出 key
+    pub fn keys<'a>(&self, args: &'a [String]) -> Vec<&'a str> {
+        // key 个数由 numkeys 参数决定的命令
+        let numkeys_at = match self.name {
+            "SINTERCARD" | "LMPOP" => Some(0),
+            "BLMPOP" => Some(1),
+            _ => None,
+        };
+        if let Some(at) = numkeys_at {
+            let n = args
+                .get(at)
+                .and_then(|n| n.parse::<usize>().ok())
+                .unwrap_or(0);
+            return args
+                .iter()
+                .skip(at + 1)
+                .take(n)
+                .map(String::as_str)
+                .collect();
+        }
+        if self.first_key == 0 {
+            return Vec::new();
+        }
+        let argc = args.len() as i32 + 1;
+        let last = if self.last_key < 0 {
+            argc + self.last_key
+        } else {
+            self.last_key.min(argc - 1)
+        };
+        (self.first_key..=last)
+            .step_by(self.key_step)
+            .filter_map(|i| args.get(i as usize - 1).map(String::as_str))
+            .collect()
+    }
+}
+
+const fn spec(
+    name: &'static str,
+    arity: i32,
+    flags: &'static [&'static str],
+    first_key: i32,
+    last_key: i32,
+    key_step: usize,
+    group: &'static str,
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        arity,
+        flags,
+        first_key,
+        last_key,
+        key_step,
+        group,
+        access: &[],
+    }
+}
+
+/// 为 key 位置指定与命令标志不同的访问方式
+const fn access(spec: CommandSpec, access: &'static [KeyAccess]) -> CommandSpec {
+    CommandSpec { access, ..spec }
+}
+
+// 修改并返回内容的 key 需要读写权限，如弹出元素与 INCR
+const RW: &[KeyAccess] = &[KeyAccess::ReadWrite];
+// 弹出并返回元素的源 key 需要读写权限，目标 key 只需写权限
+const MOVE: &[KeyAccess] = &[KeyAccess::ReadWrite, KeyAccess::Write];
+// 第一个 key 为目标，其余为只读的源
+const STORE: &[KeyAccess] = &[KeyAccess::Write, KeyAccess::Read];
+
+const W: &[&str] = &["write"];
+// denyoom：内存超过 maxmemory 时拒绝执行
+const WM: &[&str] = &["write", "denyoom"];
+const WMB: &[&str] = &["write", "denyoom", "blocking"];
+const WD: &[&str] = &["write", "dangerous"];
+const R: &[&str] = &["readonly"];
+// no-touch：读取 key 时不更新 LRU/LFU 访问信息
+const RN: &[&str] = &["readonly", "no-touch"];
+const WB: &[&str] = &["write", "blocking"];
+const ADMIN: &[&str] = &["admin"];
+const AN: &[&str] = &["admin", "no-multi"];
+const SUB: &[&str] = &["pubsub", "no-multi"];
+const PUB: &[&str] = &["pubsub"];
+const NOAUTH: &[&str] = &["no-auth"];
+const NONE: &[&str] = &[];
+
+pub const COMMANDS: &[CommandSpec] = &[
+    // System
+    spec("PING", -1, NONE, 0, 0, 0, "connection"),
+    spec("ECHO", 2, NONE, 0, 0, 0, "connection"),
+    spec("CLEAN", 1, ADMIN, 0, 0, 0, "admin"),
+    spec("FLUSHDB", 1, WD, 0, 0, 0, "keyspace"),
+    spec("OBJECT", -2, RN, 2, 2, 1, "keyspace"),
+    spec("MEMORY", -2, RN, 2, 2, 1, "keyspace"),
+    spec("INFO", -1, NONE, 0, 0, 0, "dangerous"),
+    spec("CONFIG", -2, ADMIN, 0, 0, 0, "admin"),
+    spec("SLOWLOG", -2, ADMIN, 0, 0, 0, "admin"),
+    spec("LATENCY", -2, ADMIN, 0, 0, 0, "admin"),
+    spec("MONITOR", 1, AN, 0, 0, 0, "admin"),
+    spec("SHUTDOWN", -1, AN, 0, 0, 0, "admin"),
+    spec("ACL", -2, ADMIN, 0, 0, 0, "admin"),
+    spec("HELLO", -1, NOAUTH, 0, 0, 0, "connection"),
+    spec("AUTH", -2, NOAUTH, 0, 0, 0, "connection"),
+    spec("QUIT", -1, NOAUTH, 0, 0, 0, "connection"),
+    spec("RESET", 1, NOAUTH, 0, 0, 0, "connection"),
+    spec("CLIENT", -2, NONE, 0, 0, 0, "connection"),
+    // Transaction
+    spec("MULTI", 1, NONE, 0, 0, 0, "transaction"),
+    spec("EXEC", 1, NONE, 0, 0, 0, "transaction"),
+    spec("DISCARD", 1, NONE, 0, 0, 0, "transaction"),
+    spec("WATCH", -2, NONE, 1, -1, 1, "transaction"),
+    spec("UNWATCH", 1, NONE, 0, 0, 0, "transaction"),
+    // String
+    spec("SET", -3, WM, 1, 1, 1, "string"),
+    spec("GET", 2, R, 1, 1, 1, "string"),
+    spec("DEL", -2, W, 1, -1, 1, "keyspace"),
+    spec("EXISTS", -2, R, 1, -1, 1, "keyspace"),
+    access(spec("INCR", 2, WM, 1, 1, 1, "string"), RW),
+    access(spec("DECR", 2, WM, 1, 1, 1, "string"), RW),
+    // Hash
+    spec("HSET", -4, WM, 1, 1, 1, "hash"),
+    spec("HGET", 3, R, 1, 1, 1, "hash"),
+    spec("HGETALL", 2, R, 1, 1, 1, "hash"),
+    spec("HDEL", -3, W, 1, 1, 1, "hash"),
+    spec("HEXISTS", 3, R, 1, 1, 1, "hash"),
+    spec("HLEN", 2, R, 1, 1, 1, "hash"),
+    spec("HKEYS", 2, R, 1, 1, 1, "hash"),
+    spec("HVALS", 2, R, 1, 1, 1, "hash"),
+    spec("HEXPIRE", -6, WM, 1, 1, 1, "hash"),
+    spec("HPEXPIRE", -6, WM, 1, 1, 1, "hash"),
+    spec("HEXPIREAT", -6, WM, 1, 1, 1, "hash"),
+    spec("HPEXPIREAT", -6, WM, 1, 1, 1, "hash"),
+    spec("HTTL", -5, R, 1, 1, 1, "hash"),
+    spec("HPTTL", -5, R, 1, 1, 1, "hash"),
+    spec("HEXPIRETIME", -5, R, 1, 1, 1, "hash"),
+    spec("HPEXPIRETIME", -5, R, 1, 1, 1, "hash"),
+    spec("HPERSIST", -5, W, 1, 1, 1, "hash"),
+    access(spec("HGETEX", -5, W, 1, 1, 1, "hash"), RW),
+    // List
+    spec("LPUSH", -3, WM, 1, 1, 1, "list"),
+    spec("RPUSH", -3, WM, 1, 1, 1, "list"),
+    spec("LPUSHX", -3, WM, 1, 1, 1, "list"),
+    spec("RPUSHX", -3, WM, 1, 1, 1, "list"),
+    access(spec("LPOP", -2, W, 1, 1, 1, "list"), RW),
+    access(spec("RPOP", -2, W, 1, 1, 1, "list"), RW),
+    spec("LLEN", 2, R, 1, 1, 1, "list"),
+    spec("LINDEX", 3, R, 1, 1, 1, "list"),
+    spec("LSET", 4, WM, 1, 1, 1, "list"),
+    spec("LRANGE", 4, R, 1, 1, 1, "list"),
+    spec("LREM", 4, W, 1, 1, 1, "list"),
+    spec("LTRIM", 4, W, 1, 1, 1, "list"),
+    spec("LINSERT", 5, WM, 1, 1, 1, "list"),
+    spec("LPOS", -3, R, 1, 1, 1, "list"),
+    access(spec("LMOVE", 5, WM, 1, 2, 1, "list"), MOVE),
+    access(spec("RPOPLPUSH", 3, WM, 1, 2, 1, "list"), MOVE),
+    access(spec("LMPOP", -4, W, 0, 0, 0, "list"), RW),
+    access(spec("BLPOP", -3, WB, 1, -2, 1, "list"), RW),
+    access(spec("BRPOP", -3, WB, 1, -2, 1, "list"), RW),
+    access(spec("BLMOVE", 6, WMB, 1, 2, 1, "list"), MOVE),
+    access(spec("BLMPOP", -5, WB, 0, 0, 0, "list"), RW),
+    // Set
+    spec("SADD", -3, WM, 1, 1, 1, "set"),
+    spec("SCARD", 2, R, 1, 1, 1, "set"),
+    spec("SMEMBERS", 2, R, 1, 1, 1, "set"),
+    spec("SREM", -3, W, 1, 1, 1, "set"),
+    spec("SISMEMBER", 3, R, 1, 1, 1, "set"),
+    spec("SMISMEMBER", -3, R, 1, 1, 1, "set"),
+    access(spec("SPOP", -2, W, 1, 1, 1, "set"), RW),
+    spec("SRANDMEMBER", -2, R, 1, 1, 1, "set"),
+    access(spec("SMOVE", 4, W, 1, 2, 1, "set"), MOVE),
+    spec("SINTERCARD", -3, R, 0, 0, 0, "set"),
+    spec("SSCAN", -3, R, 1, 1, 1, "set"),
+    spec("SINTER", -2, R, 1, -1, 1, "set"),
+    access(spec("SINTERSTORE", -3, WM, 1, -1, 1, "set"), STORE),
+    spec("SUNION", -2, R, 1, -1, 1, "set"),
+    access(spec("SUNIONSTORE", -3, WM, 1, -1, 1, "set"), STORE),
+    spec("SDIFF", -2, R, 1, -1, 1, "set"),
+    access(spec("SDIFFSTORE", -3, WM, 1, -1, 1, "set"), STORE),
+    // Pub/Sub
+    spec("SUBSCRIBE", -2, SUB, 0, 0, 0, "pubsub"),
+    spec("UNSUBSCRIBE", -1, SUB, 0, 0, 0, "pubsub"),
+    spec("PSUBSCRIBE", -2, SUB, 0, 0, 0, "pubsub"),
+    spec("PUNSUBSCRIBE", -1, SUB, 0, 0, 0, "pubsub"),
+    spec("SSUBSCRIBE", -2, SUB, 0, 0, 0, "pubsub"),
+    spec("SUNSUBSCRIBE", -1, SUB, 0, 0, 0, "pubsub"),
+    spec("PUBLISH", 3, PUB, 0, 0, 0, "pubsub"),
+    spec("SPUBLISH", 3, PUB, 0, 0, 0, "pubsub"),
+    spec("PUBSUB", -2, PUB, 0, 0, 0, "pubsub"),
+];
+
+/// 不随所属命令授权的子命令，可以用 +client|kill 单独授权
+pub const SUBCOMMANDS: &[&str] = &[
+    "client|kill",
+    "client|pause",
+    "client|unpause",
+    "client|no-evict",
+    "client|list",
+    "client|info",
+    "acl|whoami",
+    "acl|cat",
+];
+
+/// 所属命令属于 @admin，自身不属于 @admin 的子命令
+const NON_ADMIN_SUBCOMMANDS: &[&str] = &["acl|whoami", "acl|cat"];
+
+/// 子命令的 ACL 分类：所属命令的分类加上 admin 与 dangerous，
+/// NON_ADMIN_SUBCOMMANDS 则去掉 admin 与 dangerous
+pub fn subcommand_categories(name: &str) -> Vec<&'static str> {
+    let parent = name.split('|').next().unwrap_or_default().to_uppercase();
+    let mut categories = lookup(&parent).map_or_else(Vec::new, CommandSpec::categories);
+    if NON_ADMIN_SUBCOMMANDS.contains(&name) {
+        categories.retain(|category| !["admin", "dangerous"].contains(category));
+        return categories;
+    }
+    for category in ["admin", "dangerous"] {
+        if !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    categories
+}
+
+/// ACL CAT 列出的全部分类
+pub const CATEGORIES: &[&str] = &[
+    "keyspace",
+    "read",
+    "write",
+    "string",
+    "hash",
+    "list",
+    "set",
+    "pubsub",
+    "admin",
+    "dangerous",
+    "connection",
+    "transaction",
+    "blocking",
+];
+
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.name == name)
+}
+
+/// 命令的调用统计，CONFIG RESETSTAT 清零
+#[derive(Default)]
+pub struct CommandStat {
+    pub calls: AtomicU64,
+    /// 累计执行时间（微秒）
+    pub usec: AtomicU64,
+    /// 执行前被拒绝：参数个数错误、没有权限、内存不足等
+    pub rejected_calls: AtomicU64,
+    /// 执行后返回了错误
+    pub failed_calls: AtomicU64,
+    /// 耗时分布，第 i 个桶统计 (LATENCY_BUCKETS[i-1], LATENCY_BUCKETS[i]] 微秒内完成的调用
+    pub latency: [AtomicU64; LATENCY_BUCKETS.len()],
+}
+
+/// 耗时直方图各个桶的上限（微秒），超过最后一个上限的调用只计入 calls
+pub const LATENCY_BUCKETS: [u64; 12] = [
+    10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 100_000, 1_000_000,
+];
+
+impl CommandStat {
+    pub fn record(&self, elapsed: Duration, failed: bool) {
+        let usec = elapsed.as_micros() as u64;
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.usec.fetch_add(usec, Ordering::Relaxed);
+        if failed {
+            self.failed_calls.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&bound| usec <= bound) {
+            self.latency[i].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+static COMMAND_STATS: LazyLock<HashMap<&'static str, CommandStat>> = LazyLock::new(|| {
+    COMMANDS
+        .iter()
+        .map(|spec| (spec.name, CommandStat::default()))
+        .collect()
+});
+
+impl CommandSpec {
+    pub fn stat(&self) -> &'static CommandStat {
+        &COMMAND_STATS[self.name]
+    }
+}
+
+/// 执行过的命令的统计，按命令名排序
+pub fn command_stats() -> Vec<(&'static str, &'static CommandStat)> {
+    let mut stats: Vec<_> = COMMAND_STATS
+        .iter()
+        .filter(|(_, stat)| {
+            stat.calls.load(Ordering::Relaxed) > 0
+                || stat.rejected_calls.load(Ordering::Relaxed) > 0
+        })
+        .map(|(name, stat)| (*name, stat))
+        .collect();
+    stats.sort_by_key(|(name, _)| *name);
+    stats
+}
+
+pub fn reset_stats() {
+    for stat in COMMAND_STATS.values() {
+        for counter in [
+            &stat.calls,
+            &stat.usec,
+            &stat.rejected_calls,
+            &stat.failed_calls,
+        ]
+        .into_iter()
+        .chain(&stat.latency)
+        {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn f1_keys() {
+        let get = lookup("GET").unwrap();
+        assert_eq!(get.keys(&args(&["k"])), vec!["k"]);
+        let del = lookup("DEL").unwrap();
+        assert_eq!(del.keys(&args(&["a", "b"])), vec!["a", "b"]);
+        let blpop = lookup("BLPOP").unwrap();
+        assert_eq!(blpop.keys(&args(&["a", "b", "0"])), vec!["a", "b"]);
+        let lmpop = lookup("BLMPOP").unwrap();
+        assert_eq!(
+            lmpop.keys(&args(&["0", "2", "a", "b", "LEFT"])),
+            vec!["a", "b"]
+        );
+        assert!(lookup("PING").unwrap().keys(&args(&["x"])).is_empty());
+    }
+
+    #[test]
+    fn f2_key_access() {
+        let store = lookup("SINTERSTORE").unwrap();
+        assert_eq!(
+            store.keys_with_access(&args(&["dst", "a", "b"])),
+            vec![
+                ("dst", KeyAccess::Write),
+                ("a", KeyAccess::Read),
+                ("b", KeyAccess::Read)
+            ]
+        );
+        let lmove = lookup("LMOVE").unwrap();
+        assert_eq!(
+            lmove.keys_with_access(&args(&["src", "dst", "LEFT", "RIGHT"])),
+            vec![("src", KeyAccess::ReadWrite), ("dst", KeyAccess::Write)]
+        );
+        let get = lookup("GET").unwrap();
+        assert_eq!(
+            get.keys_with_access(&args(&["k"])),
+            vec![("k", KeyAccess::Read)]
+        );
+        let blpop = lookup("BLPOP").unwrap();
+        assert_eq!(
+            blpop.keys_with_access(&args(&["a", "b", "0"])),
+            vec![("a", KeyAccess::ReadWrite), ("b", KeyAccess::ReadWrite)]
+        );
+    }
+}
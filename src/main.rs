@@ -2,16 +2,45 @@ mod command;
 mod protocol;
 mod server;
 mod storage;
+mod util;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut addr = "127.0.0.1:6379".to_string();
-    let mut args = std::env::args();
-    if args.len() == 2 {
-        addr = args.next_back().unwrap();
+    // [config-file] [host:port] [--name value ...]...
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut i = 0;
+    if let Some(first) = args.first()
+        && !first.starts_with("--")
+        && std::path::Path::new(first).is_file()
+    {
+        command::load_config(first)?;
+        i = 1;
+    }
+    // 命令行参数在配置文件之后生效
+    while i < args.len() {
+        let arg = &args[i];
+        i += 1;
+        if let Some(name) = arg.strip_prefix("--") {
+            let start = i;
+            while i < args.len() && !args[i].starts_with("--") {
+                i += 1;
+            }
+            command::set_config(name, &args[start..i].join(" "))?;
+        } else {
+            let (host, port) = arg
+                .rsplit_once(':')
+                .ok_or_else(|| format!("invalid address '{}', expected host:port", arg))?;
+            command::set_config("bind", host)?;
+            command::set_config("port", port)?;
+        }
+    }
+
+    if !command::acl::ACLFILE.read().unwrap().is_empty() {
+        command::acl::load_file()?;
     }
 
-    let mut redis_server = server::RedisServer::new(&addr).await?;
+    let mut redis_server = server::RedisServer::new().await?;
+    util::record_startup_memory();
     redis_server.run().await?;
 
     Ok(())
@@ -1,9 +1,13 @@
 // src/protocol/resp.rs
 use bytes::{Buf, BytesMut};
+use std::sync::atomic::{AtomicU64, Ordering};
 // use std::io::Cursor;
 
 pub type GeneralError = dyn std::error::Error + Send + Sync;
 
+/// 客户端发送的单个 bulk string 的最大长度，CONFIG SET proto-max-bulk-len 修改
+pub static PROTO_MAX_BULK_LEN: AtomicU64 = AtomicU64::new(512 * 1024 * 1024);
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RespValue {
     SimpleString(String),
@@ -12,12 +16,48 @@ pub enum RespValue {
     BulkString(Option<String>),
     Array(Vec<RespValue>),
     Null,
+    /// RESP2 的空数组 *-1，RESP3 连接使用 Null
+    NullArray,
+    // RESP3
+    Map(Vec<(RespValue, RespValue)>),
+    Push(Vec<RespValue>),
+}
+
+impl RespValue {
+    /// 转换为 RESP2 客户端能够理解的类型
+    pub fn into_resp2(self) -> RespValue {
+        match self {
+            RespValue::Null => RespValue::BulkString(None),
+            RespValue::Array(values) | RespValue::Push(values) => {
+                RespValue::Array(values.into_iter().map(RespValue::into_resp2).collect())
+            }
+            RespValue::Map(pairs) => RespValue::Array(
+                pairs
+                    .into_iter()
+                    .flat_map(|(k, v)| [k.into_resp2(), v.into_resp2()])
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
 }
 
 pub struct RespParser;
 
 impl RespParser {
     pub fn parse(buf: &mut BytesMut) -> Result<Option<RespValue>, Box<GeneralError>> {
+        // 在切片上解析，得到完整的值之后才从缓冲区移除，不完整时缓冲区保持不变
+        match Self::parse_frame(buf)? {
+            Some((value, consumed)) => {
+                buf.advance(consumed);
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 解析 buf 开头的一个值，返回值与它占用的字节数
+    fn parse_frame(buf: &[u8]) -> Result<Option<(RespValue, usize)>, Box<GeneralError>> {
         if buf.is_empty() {
             return Ok(None);
         }
@@ -33,132 +73,93 @@ impl RespParser {
         }
     }
 
-    fn parse_simple_string(buf: &mut BytesMut) -> Result<Option<RespValue>, Box<GeneralError>> {
+    /// 第一行的内容（跳过类型字节）与下一行的起始位置
+    fn parse_line(buf: &[u8]) -> Option<(&[u8], usize)> {
         // 查找 \r\n
-        if let Some(pos) = buf.iter().position(|&b| b == b'\r') {
-            // 确保下一个字节是 \n
-            if pos + 1 < buf.len() && buf[pos + 1] == b'\n' {
-                // 提取字符串内容（跳过 '+' 和 \r\n）
-                let content = String::from_utf8(buf[1..pos].to_vec())?;
-                // 移除已解析的数据
-                buf.advance(pos + 2);
-                return Ok(Some(RespValue::SimpleString(content)));
-            }
+        let pos = buf.iter().position(|&b| b == b'\r')?;
+        // 确保下一个字节是 \n
+        if pos + 1 < buf.len() && buf[pos + 1] == b'\n' {
+            Some((&buf[1..pos], pos + 2))
+        } else {
+            None // 数据不完整
         }
-        Ok(None) // 数据不完整
     }
 
-    fn parse_error(buf: &mut BytesMut) -> Result<Option<RespValue>, Box<GeneralError>> {
-        // 查找 \r\n
-        if let Some(pos) = buf.iter().position(|&b| b == b'\r') {
-            // 确保下一个字节是 \n
-            if pos + 1 < buf.len() && buf[pos + 1] == b'\n' {
-                // 提取错误内容（跳过 '-' 和 \r\n）
-                let content = String::from_utf8(buf[1..pos].to_vec())?;
-                // 移除已解析的数据
-                buf.advance(pos + 2);
-                return Ok(Some(RespValue::Error(content)));
-            }
-        }
-        Ok(None) // 数据不完整
+    fn parse_simple_string(buf: &[u8]) -> Result<Option<(RespValue, usize)>, Box<GeneralError>> {
+        let Some((line, consumed)) = Self::parse_line(buf) else {
+            return Ok(None);
+        };
+        let content = String::from_utf8(line.to_vec())?;
+        Ok(Some((RespValue::SimpleString(content), consumed)))
     }
 
-    fn parse_integer(buf: &mut BytesMut) -> Result<Option<RespValue>, Box<GeneralError>> {
-        // 查找 \r\n
-        if let Some(pos) = buf.iter().position(|&b| b == b'\r') {
-            // 确保下一个字节是 \n
-            if pos + 1 < buf.len() && buf[pos + 1] == b'\n' {
-                // 提取整数内容（跳过 ':' 和 \r\n）
-                let content = std::str::from_utf8(&buf[1..pos])?;
-                let number = content.parse::<i64>()?;
-                // 移除已解析的数据
-                buf.advance(pos + 2);
-                return Ok(Some(RespValue::Integer(number)));
-            }
-        }
-        Ok(None) // 数据不完整
+    fn parse_error(buf: &[u8]) -> Result<Option<(RespValue, usize)>, Box<GeneralError>> {
+        let Some((line, consumed)) = Self::parse_line(buf) else {
+            return Ok(None);
+        };
+        let content = String::from_utf8(line.to_vec())?;
+        Ok(Some((RespValue::Error(content), consumed)))
     }
 
-    fn parse_bulk_string(buf: &mut BytesMut) -> Result<Option<RespValue>, Box<GeneralError>> {
-        // 查找第一个 \r\n 来获取长度
-        if let Some(pos) = buf.iter().position(|&b| b == b'\r') {
-            // 确保下一个字节是 \n
-            if pos + 1 < buf.len() && buf[pos + 1] == b'\n' {
-                // 提取长度（跳过 '$' 和 \r\n）
-                let length_str = std::str::from_utf8(&buf[1..pos])?;
-
-                if length_str == "-1" {
-                    // Null bulk string
-                    buf.advance(pos + 2);
-                    return Ok(Some(RespValue::Null));
-                }
-
-                let length = length_str.parse::<usize>()?;
-
-                // 检查是否有足够的数据（长度 + \r\n + 数据 + \r\n）
-                let total_needed = pos + 2 + length + 2;
-                if buf.len() < total_needed {
-                    return Ok(None); // 数据不完整
-                }
+    fn parse_integer(buf: &[u8]) -> Result<Option<(RespValue, usize)>, Box<GeneralError>> {
+        let Some((line, consumed)) = Self::parse_line(buf) else {
+            return Ok(None);
+        };
+        let number = std::str::from_utf8(line)?.parse::<i64>()?;
+        Ok(Some((RespValue::Integer(number), consumed)))
+    }
 
-                // 提取字符串数据
-                let data_start = pos + 2;
-                let data_end = data_start + length;
+    fn parse_bulk_string(buf: &[u8]) -> Result<Option<(RespValue, usize)>, Box<GeneralError>> {
+        // 第一行是长度
+        let Some((line, data_start)) = Self::parse_line(buf) else {
+            return Ok(None);
+        };
+        let length_str = std::str::from_utf8(line)?;
+        if length_str == "-1" {
+            // Null bulk string
+            return Ok(Some((RespValue::Null, data_start)));
+        }
+        let length = length_str.parse::<usize>()?;
+        const INVALID: &str = "Protocol error: invalid bulk length";
+        if length as u64 > PROTO_MAX_BULK_LEN.load(Ordering::Relaxed) {
+            return Err(INVALID.into());
+        }
 
-                // 验证后面是否有 \r\n
-                if data_end + 1 < buf.len() && buf[data_end] == b'\r' && buf[data_end + 1] == b'\n'
-                {
-                    let content = String::from_utf8(buf[data_start..data_end].to_vec())?;
-                    buf.advance(total_needed);
-                    return Ok(Some(RespValue::BulkString(Some(content))));
-                }
-            }
+        // 检查是否有足够的数据（数据 + \r\n），只比较长度，不扫描数据本身
+        let data_end = data_start.checked_add(length).ok_or(INVALID)?;
+        let total_needed = data_end.checked_add(2).ok_or(INVALID)?;
+        if buf.len() < total_needed {
+            return Ok(None); // 数据不完整
+        }
+        if &buf[data_end..total_needed] != b"\r\n" {
+            return Err("Bulk string is not terminated by CRLF".into());
         }
-        Ok(None) // 数据不完整
+        let content = String::from_utf8(buf[data_start..data_end].to_vec())?;
+        Ok(Some((RespValue::BulkString(Some(content)), total_needed)))
     }
 
-    fn parse_array(buf: &mut BytesMut) -> Result<Option<RespValue>, Box<GeneralError>> {
-        // 查找第一个 \r\n 来获取数组长度
-        if let Some(pos) = buf.iter().position(|&b| b == b'\r') {
-            // 确保下一个字节是 \n
-            if pos + 1 < buf.len() && buf[pos + 1] == b'\n' {
-                // 提取数组长度（跳过 '*' 和 \r\n）
-                let length_str = std::str::from_utf8(&buf[1..pos])?;
-                let length = length_str.parse::<i64>()?;
-
-                // 移除长度行
-                buf.advance(pos + 2);
-
-                if length == -1 {
-                    // Null array
-                    return Ok(Some(RespValue::Null));
-                }
-
-                let length = length as usize;
-                let mut elements = Vec::with_capacity(length);
-                let mut parsed_count = 0;
-
-                // 临时缓冲区用于回滚
-                // let original_len = buf.len();
-
-                // 解析数组元素
-                while parsed_count < length {
-                    match Self::parse(buf)? {
-                        Some(value) => {
-                            elements.push(value);
-                            parsed_count += 1;
-                        }
-                        None => {
-                            // 数据不完整，回滚并返回 None
-                            return Ok(None);
-                        }
-                    }
-                }
+    fn parse_array(buf: &[u8]) -> Result<Option<(RespValue, usize)>, Box<GeneralError>> {
+        // 第一行是数组长度
+        let Some((line, mut offset)) = Self::parse_line(buf) else {
+            return Ok(None);
+        };
+        let length = std::str::from_utf8(line)?.parse::<i64>()?;
+        if length == -1 {
+            // Null array
+            return Ok(Some((RespValue::Null, offset)));
+        }
 
-                return Ok(Some(RespValue::Array(elements)));
-            }
+        // 元素不完整时直接返回 None，调用方的缓冲区没有被修改
+        let length = length as usize;
+        let mut elements = Vec::with_capacity(length.min(1024));
+        while elements.len() < length {
+            let Some((value, consumed)) = Self::parse_frame(&buf[offset..])? else {
+                return Ok(None);
+            };
+            elements.push(value);
+            offset += consumed;
         }
-        Ok(None) // 数据不完整
+        Ok(Some((RespValue::Array(elements), offset)))
     }
 
     pub fn serializer(response: RespValue) -> Vec<u8> {
@@ -226,6 +227,25 @@ impl RespParser {
             }
 
             RespValue::Null => b"_\r\n".to_vec(),
+
+            RespValue::NullArray => b"*-1\r\n".to_vec(),
+
+            RespValue::Map(pairs) => {
+                let mut result = format!("%{}\r\n", pairs.len()).into_bytes();
+                for (key, value) in pairs {
+                    result.extend_from_slice(&Self::serializer(key));
+                    result.extend_from_slice(&Self::serializer(value));
+                }
+                result
+            }
+
+            RespValue::Push(values) => {
+                let mut result = format!(">{}\r\n", values.len()).into_bytes();
+                for value in values {
+                    result.extend_from_slice(&Self::serializer(value));
+                }
+                result
+            }
         }
     }
 }
@@ -239,4 +259,59 @@ mod test {
         let resp_value = RespParser::parse(&mut buf).unwrap().unwrap();
         println!("{:?}", resp_value);
     }
+
+    #[test]
+    fn f2_partial_array() {
+        let mut buf = bytes::BytesMut::from("*2\r\n$3\r\nGET\r\n$3\r\nk");
+        assert_eq!(RespParser::parse(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"ey\r\n");
+        let resp_value = RespParser::parse(&mut buf).unwrap().unwrap();
+        assert_eq!(
+            resp_value,
+            RespValue::Array(vec![
+                RespValue::BulkString(Some("GET".into())),
+                RespValue::BulkString(Some("key".into())),
+            ])
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn f3_large_bulk_string() {
+        // 大的值分多次到达，每次解析只检查长度，不复制已缓存的数据
+        let value = "x".repeat(16 << 20);
+        let frame = format!(
+            "*3\r\n$3\r\nSET\r\n$1\r\nk\r\n${}\r\n{}\r\n",
+            value.len(),
+            value
+        );
+        let mut buf = BytesMut::new();
+        let start = std::time::Instant::now();
+        for chunk in frame.as_bytes().chunks(1024) {
+            buf.extend_from_slice(chunk);
+            if let Some(RespValue::Array(items)) = RespParser::parse(&mut buf).unwrap() {
+                assert_eq!(items[2], RespValue::BulkString(Some(value.clone())));
+            }
+        }
+        assert!(buf.is_empty());
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+
+        let mut buf = BytesMut::from("$3\r\nabcde\r\n");
+        assert!(RespParser::parse(&mut buf).is_err());
+    }
+
+    #[test]
+    fn f4_invalid_bulk_length() {
+        let frame = format!("*1\r\n${}\r\nab\r\n", usize::MAX);
+        let mut buf = BytesMut::from(frame.as_str());
+        let error = RespParser::parse(&mut buf).unwrap_err();
+        assert_eq!(error.to_string(), "Protocol error: invalid bulk length");
+
+        // 超过 proto-max-bulk-len 时不等待数据到达，直接报错
+        let max = PROTO_MAX_BULK_LEN.load(Ordering::Relaxed);
+        let mut buf = BytesMut::from(format!("${}\r\n", max + 1).as_str());
+        assert!(RespParser::parse(&mut buf).is_err());
+        let mut buf = BytesMut::from(format!("${}\r\n", max).as_str());
+        assert_eq!(RespParser::parse(&mut buf).unwrap(), None);
+    }
 }
@@ -0,0 +1,50 @@
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+/// 服务器运行参数
+pub struct ServerConfig {
+    /// 后台定时任务每秒执行的次数
+    pub hz: AtomicU32,
+    /// 客户端空闲多少秒后断开，0 表示不断开
+    pub timeout: AtomicU64,
+    /// 同时连接的客户端上限，超出时拒绝新连接
+    pub maxclients: AtomicUsize,
+    /// TCP keepalive 探测间隔（秒），0 表示不开启
+    pub tcp_keepalive: AtomicU64,
+    pub databases: AtomicU32,
+    /// 启动时写入进程号的文件，为空时不写
+    pub pidfile: RwLock<String>,
+    /// 目前没有持久化，save 与 appendonly 只为兼容 redis.conf 而保存
+    pub save: RwLock<String>,
+    pub appendonly: AtomicBool,
+}
+
+pub static SERVER_CONFIG: ServerConfig = ServerConfig {
+    hz: AtomicU32::new(10),
+    timeout: AtomicU64::new(0),
+    maxclients: AtomicUsize::new(10000),
+    tcp_keepalive: AtomicU64::new(300),
+    databases: AtomicU32::new(16),
+    pidfile: RwLock::new(String::new()),
+    save: RwLock::new(String::new()),
+    appendonly: AtomicBool::new(false),
+};
+
+impl ServerConfig {
+    pub fn hz(&self) -> u32 {
+        self.hz.load(Ordering::Relaxed)
+    }
+
+    pub fn set_hz(&self, value: &str) -> Result<(), String> {
+        let hz = value
+            .parse::<u32>()
+            .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+        // 与 Redis 一样把超出范围的值限制在 1..=500
+        self.hz.store(hz.clamp(1, 500), Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn pidfile(&self) -> String {
+        self.pidfile.read().unwrap().clone()
+    }
+}
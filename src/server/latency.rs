@@ -0,0 +1,67 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 耗时达到该值（毫秒）的事件被记录，0 表示关闭延迟监控
+pub static LATENCY_MONITOR_THRESHOLD: AtomicU64 = AtomicU64::new(0);
+
+/// 每个事件保留的样本数
+const HISTORY_LEN: usize = 160;
+
+/// 一类事件的延迟样本，同一秒内只保留最大值
+#[derive(Debug, Default)]
+pub struct LatencyEvent {
+    /// (Unix 时间（秒）, 延迟毫秒数)，最新的在后
+    pub history: VecDeque<(u64, u64)>,
+    /// 有记录以来的最大延迟
+    pub max: u64,
+}
+
+/// 事件名 -> 样本，事件名如 command、expire-cycle、eviction-cycle
+pub static LATENCY: Mutex<BTreeMap<&'static str, LatencyEvent>> = Mutex::new(BTreeMap::new());
+
+/// 记录一次事件的耗时，未达到阈值时忽略
+pub fn add_sample(event: &'static str, elapsed: Duration) {
+    let threshold = LATENCY_MONITOR_THRESHOLD.load(Ordering::Relaxed);
+    let latency = elapsed.as_millis() as u64;
+    if threshold == 0 || latency < threshold {
+        return;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let mut events = LATENCY.lock().unwrap();
+    let samples = events.entry(event).or_default();
+    samples.max = samples.max.max(latency);
+    match samples.history.back_mut() {
+        Some((time, max)) if *time == now => *max = (*max).max(latency),
+        _ => {
+            samples.history.push_back((now, latency));
+            if samples.history.len() > HISTORY_LEN {
+                samples.history.pop_front();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_add_sample() {
+        LATENCY_MONITOR_THRESHOLD.store(10, Ordering::Relaxed);
+        add_sample("test-event", Duration::from_millis(5));
+        assert!(!LATENCY.lock().unwrap().contains_key("test-event"));
+        add_sample("test-event", Duration::from_millis(20));
+        add_sample("test-event", Duration::from_millis(30));
+        let events = LATENCY.lock().unwrap();
+        let samples = &events["test-event"];
+        assert_eq!(samples.max, 30);
+        // 同一秒内的样本合并
+        assert!(samples.history.len() <= 2);
+        assert_eq!(samples.history.back().unwrap().1, 30);
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+
+/// 监听地址配置，只在启动时读取
+pub struct ListenConfig {
+    /// 空格分隔的多个地址，为空时使用 127.0.0.1
+    pub bind: RwLock<String>,
+    /// 0 表示不监听 TCP 端口
+    pub port: AtomicU16,
+    /// 为空时不监听 Unix socket
+    pub unixsocket: RwLock<String>,
+    /// socket 文件的权限，0 表示保持默认
+    pub unixsocketperm: AtomicU32,
+    /// 提供 Prometheus /metrics 的 HTTP 端口，0 表示不启用
+    pub metrics_port: AtomicU16,
+}
+
+pub static LISTEN_CONFIG: ListenConfig = ListenConfig {
+    bind: RwLock::new(String::new()),
+    port: AtomicU16::new(6379),
+    unixsocket: RwLock::new(String::new()),
+    unixsocketperm: AtomicU32::new(0),
+    metrics_port: AtomicU16::new(0),
+};
+
+impl ListenConfig {
+    pub fn bind(&self) -> String {
+        let bind = self.bind.read().unwrap();
+        if bind.is_empty() {
+            "127.0.0.1".to_string()
+        } else {
+            bind.clone()
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port.load(Ordering::Relaxed)
+    }
+
+    pub fn unixsocket(&self) -> String {
+        self.unixsocket.read().unwrap().clone()
+    }
+
+    pub fn unixsocketperm(&self) -> u32 {
+        self.unixsocketperm.load(Ordering::Relaxed)
+    }
+
+    pub fn metrics_port(&self) -> u16 {
+        self.metrics_port.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 日志级别，由 loglevel 配置决定输出哪些日志
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Verbose,
+    Notice,
+    Warning,
+    /// 只用于配置，关闭全部日志
+    Nothing,
+}
+
+const LEVELS: [(LogLevel, &str); 5] = [
+    (LogLevel::Debug, "debug"),
+    (LogLevel::Verbose, "verbose"),
+    (LogLevel::Notice, "notice"),
+    (LogLevel::Warning, "warning"),
+    (LogLevel::Nothing, "nothing"),
+];
+
+static LOGLEVEL: AtomicU8 = AtomicU8::new(LogLevel::Notice as u8);
+
+pub fn loglevel() -> &'static str {
+    LEVELS[LOGLEVEL.load(Ordering::Relaxed) as usize].1
+}
+
+pub fn set_loglevel(value: &str) -> Result<(), String> {
+    let value = value.to_lowercase();
+    let Some((level, _)) = LEVELS.iter().find(|(_, name)| *name == value) else {
+        return Err(
+            "argument(s) must be one of the following: debug, verbose, notice, warning, nothing"
+                .to_string(),
+        );
+    };
+    LOGLEVEL.store(*level as u8, Ordering::Relaxed);
+    Ok(())
+}
+
+fn enabled(level: LogLevel) -> bool {
+    level != LogLevel::Nothing && level as u8 >= LOGLEVEL.load(Ordering::Relaxed)
+}
+
+/// 低于 loglevel 的日志不输出，warning 写到标准错误
+pub fn write_log(level: LogLevel, args: Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    if level >= LogLevel::Warning {
+        eprintln!("{}", args);
+    } else {
+        println!("{}", args);
+    }
+}
+
+/// log!(Warning, "...", args)
+macro_rules! log {
+    ($level:ident, $($arg:tt)*) => {
+        $crate::server::write_log($crate::server::LogLevel::$level, format_args!($($arg)*))
+    };
+}
+pub(crate) use log;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_loglevel() {
+        let _serial = crate::server::SERIAL.blocking_lock();
+        assert_eq!(loglevel(), "notice");
+        assert!(enabled(LogLevel::Notice) && !enabled(LogLevel::Verbose));
+        set_loglevel("WARNING").unwrap();
+        assert_eq!(loglevel(), "warning");
+        assert!(!enabled(LogLevel::Notice) && enabled(LogLevel::Warning));
+        set_loglevel("nothing").unwrap();
+        assert!(!enabled(LogLevel::Warning));
+        assert!(set_loglevel("loud").is_err());
+        assert_eq!(loglevel(), "nothing");
+        set_loglevel("notice").unwrap();
+    }
+}
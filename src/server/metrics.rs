@@ -0,0 +1,394 @@
+use std::fmt::{Display, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use super::{RedisServer, STATS, log, uptime};
+use crate::command::{CommandStat, LATENCY_BUCKETS, command_stats};
+use crate::storage::{Database, MAXMEMORY};
+use crate::util::{cpu_times, resident_memory, used_memory, used_memory_peak};
+
+/// 请求头的最大长度，超过时直接断开
+const MAX_REQUEST: usize = 8192;
+/// 读取请求头的超时时间，超时直接断开
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Prometheus 文本格式的输出
+#[derive(Default)]
+struct Metrics(String);
+
+impl Metrics {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {} {}", name, help);
+        let _ = writeln!(self.0, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &str, value: impl Display) {
+        if labels.is_empty() {
+            let _ = writeln!(self.0, "{} {}", name, value);
+        } else {
+            let _ = writeln!(self.0, "{}{{{}}} {}", name, labels, value);
+        }
+    }
+
+    /// 只有一个样本的指标
+    fn single(&mut self, name: &str, kind: &str, help: &str, value: impl Display) {
+        self.family(name, kind, help);
+        self.sample(name, "", value);
+    }
+}
+
+/// 与 INFO 使用同一组统计生成 /metrics 的内容
+pub fn render(db: &mut Database) -> String {
+    let mut m = Metrics::default();
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+    m.single(
+        "redis_uptime_in_seconds",
+        "gauge",
+        "Seconds since the server started.",
+        uptime().as_secs(),
+    );
+    m.single(
+        "redis_connected_clients",
+        "gauge",
+        "Number of client connections.",
+        db.clients.len(),
+    );
+    m.single(
+        "redis_blocked_clients",
+        "gauge",
+        "Clients blocked on a blocking command.",
+        db.blocking.blocked_count(),
+    );
+
+    let (keys, expires, _) = db.keyspace_stats();
+    m.family("redis_db_keys", "gauge", "Number of keys per database.");
+    m.sample("redis_db_keys", "db=\"db0\"", keys);
+    m.family(
+        "redis_db_keys_expiring",
+        "gauge",
+        "Number of keys with an expiration per database.",
+    );
+    m.sample("redis_db_keys_expiring", "db=\"db0\"", expires);
+
+    m.single(
+        "redis_memory_used_bytes",
+        "gauge",
+        "Bytes allocated by the server.",
+        used_memory(),
+    );
+    m.single(
+        "redis_memory_used_rss_bytes",
+        "gauge",
+        "Resident set size of the process.",
+        resident_memory(),
+    );
+    m.single(
+        "redis_memory_used_peak_bytes",
+        "gauge",
+        "Peak of bytes allocated by the server.",
+        used_memory_peak(),
+    );
+    m.single(
+        "redis_memory_used_dataset_bytes",
+        "gauge",
+        "Estimated bytes used by keys and values.",
+        db.dataset_bytes(),
+    );
+    m.single(
+        "redis_memory_max_bytes",
+        "gauge",
+        "Value of maxmemory, 0 means no limit.",
+        load(&MAXMEMORY),
+    );
+
+    for (name, help, counter) in [
+        (
+            "redis_connections_received_total",
+            "Connections accepted by the server.",
+            &STATS.total_connections_received,
+        ),
+        (
+            "redis_rejected_connections_total",
+            "Connections rejected by protected mode.",
+            &STATS.rejected_connections,
+        ),
+        (
+            "redis_commands_processed_total",
+            "Commands processed by the server.",
+            &STATS.total_commands_processed,
+        ),
+        (
+            "redis_expired_keys_total",
+            "Keys removed because they expired.",
+            &STATS.expired_keys,
+        ),
+        (
+            "redis_evicted_keys_total",
+            "Keys evicted because of maxmemory.",
+            &STATS.evicted_keys,
+        ),
+        (
+            "redis_keyspace_hits_total",
+            "Successful key lookups.",
+            &STATS.keyspace_hits,
+        ),
+        (
+            "redis_keyspace_misses_total",
+            "Failed key lookups.",
+            &STATS.keyspace_misses,
+        ),
+        (
+            "redis_net_input_bytes_total",
+            "Bytes read from clients.",
+            &STATS.net_input_bytes,
+        ),
+        (
+            "redis_net_output_bytes_total",
+            "Bytes written to clients.",
+            &STATS.net_output_bytes,
+        ),
+        (
+            "redis_errors_total",
+            "Error replies sent to clients.",
+            &STATS.total_error_replies,
+        ),
+    ] {
+        m.single(name, "counter", help, load(counter));
+    }
+
+    let [user, sys, _, _] = cpu_times();
+    m.single(
+        "redis_cpu_user_seconds_total",
+        "counter",
+        "User CPU time consumed by the server.",
+        user,
+    );
+    m.single(
+        "redis_cpu_sys_seconds_total",
+        "counter",
+        "System CPU time consumed by the server.",
+        sys,
+    );
+
+    // 目前没有持久化，与 INFO persistence 一致
+    m.single(
+        "redis_loading_dump_file",
+        "gauge",
+        "Whether a dump file is being loaded.",
+        0,
+    );
+    m.single(
+        "redis_rdb_bgsave_in_progress",
+        "gauge",
+        "Whether a background save is running.",
+        0,
+    );
+    m.single(
+        "redis_rdb_last_bgsave_status",
+        "gauge",
+        "Whether the last background save succeeded.",
+        1,
+    );
+    m.single(
+        "redis_aof_enabled",
+        "gauge",
+        "Whether the append only file is enabled.",
+        0,
+    );
+
+    let stats = command_stats();
+    type Counter = fn(&CommandStat) -> &AtomicU64;
+    for (family, help, counter) in [
+        (
+            "redis_commands_total",
+            "Calls per command.",
+            (|stat| &stat.calls) as Counter,
+        ),
+        (
+            "redis_commands_rejected_calls_total",
+            "Calls rejected before execution per command.",
+            |stat| &stat.rejected_calls,
+        ),
+        (
+            "redis_commands_failed_calls_total",
+            "Calls that replied with an error per command.",
+            |stat| &stat.failed_calls,
+        ),
+    ] {
+        m.family(family, "counter", help);
+        for (name, stat) in &stats {
+            m.sample(
+                family,
+                &format!("cmd=\"{}\"", name.to_lowercase()),
+                load(counter(stat)),
+            );
+        }
+    }
+    m.family(
+        "redis_commands_duration_seconds",
+        "histogram",
+        "Command execution time.",
+    );
+    for (name, stat) in &stats {
+        let cmd = name.to_lowercase();
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&stat.latency) {
+            cumulative += load(count);
+            m.sample(
+                "redis_commands_duration_seconds_bucket",
+                &format!("cmd=\"{}\",le=\"{}\"", cmd, *bound as f64 / 1e6),
+                cumulative,
+            );
+        }
+        let calls = load(&stat.calls);
+        m.sample(
+            "redis_commands_duration_seconds_bucket",
+            &format!("cmd=\"{}\",le=\"+Inf\"", cmd),
+            calls,
+        );
+        m.sample(
+            "redis_commands_duration_seconds_sum",
+            &format!("cmd=\"{}\"", cmd),
+            load(&stat.usec) as f64 / 1e6,
+        );
+        m.sample(
+            "redis_commands_duration_seconds_count",
+            &format!("cmd=\"{}\"", cmd),
+            calls,
+        );
+    }
+    m.0
+}
+
+/// 解析请求行，返回状态行、额外的响应头以及是否带上指标内容
+fn route(request: &str) -> (&'static str, &'static str, bool) {
+    let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = target.split('?').next().unwrap_or("");
+    match (method, path) {
+        (_, path) if path != "/metrics" => ("404 Not Found", "", false),
+        ("GET" | "HEAD", _) => ("200 OK", "", true),
+        _ => ("405 Method Not Allowed", "Allow: GET, HEAD\r\n", false),
+    }
+}
+
+/// 读到空行为止，超时、出错或过长时返回 None
+async fn read_request<R: AsyncRead + Unpin>(socket: &mut R, limit: Duration) -> Option<Vec<u8>> {
+    let read = async {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            match socket.read(&mut buf).await {
+                Ok(n) if n > 0 && request.len() + n <= MAX_REQUEST => {
+                    request.extend_from_slice(&buf[..n])
+                }
+                _ => return None,
+            }
+        }
+        Some(request)
+    };
+    tokio::time::timeout(limit, read).await.ok().flatten()
+}
+
+impl RedisServer {
+    pub(super) async fn accept_metrics(listener: TcpListener, db: Arc<Mutex<Database>>) {
+        loop {
+            match listener.accept().await {
+                Ok((socket, _)) => {
+                    tokio::spawn(Self::serve_metrics(socket, db.clone()));
+                }
+                Err(e) => log!(Warning, "Error accepting metrics connection: {}", e),
+            }
+        }
+    }
+
+    /// 每个连接只处理一个请求，响应后关闭
+    async fn serve_metrics(mut socket: TcpStream, db: Arc<Mutex<Database>>) {
+        let Some(request) = read_request(&mut socket, REQUEST_TIMEOUT).await else {
+            return;
+        };
+        let request = String::from_utf8_lossy(&request);
+        let (status, headers, metrics) = route(&request);
+        let body = if !metrics {
+            format!("{}\n", status)
+        } else {
+            render(&mut *db.lock().await)
+        };
+        let content_type = if metrics {
+            "text/plain; version=0.0.4; charset=utf-8"
+        } else {
+            "text/plain; charset=utf-8"
+        };
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+            status,
+            content_type,
+            body.len(),
+            headers
+        );
+        if !request.starts_with("HEAD ") {
+            response.push_str(&body);
+        }
+        let _ = socket.write_all(response.as_bytes()).await;
+        let _ = socket.shutdown().await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::Value;
+
+    #[test]
+    fn f1_route() {
+        assert_eq!(route("GET /metrics HTTP/1.1\r\n\r\n"), ("200 OK", "", true));
+        assert!(route("HEAD /metrics?x=1 HTTP/1.1\r\n\r\n").2);
+        assert_eq!(route("GET / HTTP/1.1\r\n\r\n").0, "404 Not Found");
+        assert_eq!(
+            route("POST /metrics HTTP/1.1\r\n\r\n").0,
+            "405 Method Not Allowed"
+        );
+    }
+
+    #[test]
+    fn f2_render() {
+        let mut db = Database::new();
+        db.set("a".into(), Value::String("1".into()));
+        let text = render(&mut db);
+        assert!(text.contains("# TYPE redis_db_keys gauge\nredis_db_keys{db=\"db0\"} 1\n"));
+        assert!(text.contains("redis_db_keys_expiring{db=\"db0\"} 0\n"));
+        assert!(text.contains("# TYPE redis_commands_duration_seconds histogram\n"));
+        // 每个样本行都是 "名称[{标签}] 值"
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            assert_eq!(
+                line.rsplit_once(' ').map(|(_, v)| v.parse::<f64>().is_ok()),
+                Some(true)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn f3_request_timeout() {
+        let limit = Duration::from_millis(50);
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client
+            .write_all(b"GET /metrics HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(read_request(&mut server, limit).await.is_some());
+        // 请求头一直不完整时超时返回
+        client
+            .write_all(b"GET /metrics HTTP/1.1\r\n")
+            .await
+            .unwrap();
+        let started = std::time::Instant::now();
+        assert_eq!(read_request(&mut server, limit).await, None);
+        assert!(started.elapsed() >= limit);
+    }
+}
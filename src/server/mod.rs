@@ -1,64 +1,874 @@
 // src/server/mod.rs
-use crate::command::Command;
-use crate::protocol::{GeneralError, RespParser};
-use crate::storage::Database;
+mod config;
+mod latency;
+mod listen;
+mod logging;
+mod metrics;
+mod shutdown;
+mod stats;
+mod stream;
+mod tls;
+pub use config::SERVER_CONFIG;
+pub use latency::{LATENCY, LATENCY_MONITOR_THRESHOLD, LatencyEvent, add_sample};
+pub use listen::LISTEN_CONFIG;
+pub(crate) use logging::log;
+pub use logging::{LogLevel, loglevel, set_loglevel, write_log};
+pub use shutdown::request_shutdown;
+pub use stats::{RUN_ID, STATS, uptime};
+pub use stream::Stream;
+pub use tls::TLS_CONFIG;
+
+use crate::command::{Command, Session, auth};
+use crate::protocol::{GeneralError, RespParser, RespValue};
+use crate::storage::{Database, Outbox};
+use socket2::{SockRef, TcpKeepalive};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::Mutex;
+use tokio::net::{TcpListener, TcpStream, UnixListener};
+use tokio::sync::{Mutex, mpsc};
+use tokio_rustls::TlsAcceptor;
+
+const MAX_CLIENTS_REACHED: &str = "-ERR max number of clients reached\r\n";
+
+const PROTECTED_MODE_DENIED: &str = "-DENIED Redis is running in protected mode because protected mode is enabled and no password is set for the default user. In this mode connections are only accepted from the loopback interface. If you want to connect from external computers, either disable protected mode with 'CONFIG SET protected-mode no' from the loopback interface, restart the server with the '--protected-mode no' option, or set up a password with 'CONFIG SET requirepass <password>'.\r\n";
 
 pub struct RedisServer {
     db: Arc<Mutex<Database>>,
-    listener: TcpListener,
+    /// TCP 监听，TLS 端口带有握手器
+    listeners: Vec<(TcpListener, Option<TlsAcceptor>)>,
+    unix: Option<UnixListener>,
+    /// 退出时删除 socket 文件
+    unix_path: Option<String>,
+    /// Prometheus /metrics 的 HTTP 监听
+    metrics: Vec<TcpListener>,
 }
 
 impl RedisServer {
-    pub async fn new(addr: &str) -> Result<Self, Box<GeneralError>> {
-        let listener = TcpListener::bind(addr).await?;
+    /// 按 LISTEN_CONFIG 与 TLS_CONFIG 创建监听
+    pub async fn new() -> Result<Self, Box<GeneralError>> {
         let db = Arc::new(Mutex::new(Database::new()));
+        // 从这里开始计算 uptime
+        uptime();
+
+        let mut listeners = Vec::new();
+        if LISTEN_CONFIG.port() != 0 {
+            for listener in Self::bind_tcp(LISTEN_CONFIG.port()).await? {
+                listeners.push((listener, None));
+            }
+        }
+        // TLS 端口与明文端口监听同样的地址
+        if TLS_CONFIG.port() != 0 {
+            let acceptor = TLS_CONFIG.acceptor()?;
+            for listener in Self::bind_tcp(TLS_CONFIG.port()).await? {
+                listeners.push((listener, Some(acceptor.clone())));
+            }
+        }
+        let unix_path = Some(LISTEN_CONFIG.unixsocket()).filter(|path| !path.is_empty());
+        let unix = match &unix_path {
+            Some(path) => Some(Self::bind_unix(path)?),
+            None => None,
+        };
+        if listeners.is_empty() && unix.is_none() {
+            return Err("no port, tls-port or unixsocket configured".into());
+        }
+        let metrics = match LISTEN_CONFIG.metrics_port() {
+            0 => Vec::new(),
+            port => Self::bind_tcp(port).await?,
+        };
+
+        Ok(Self {
+            db,
+            listeners,
+            unix,
+            unix_path,
+            metrics,
+        })
+    }
+
+    /// bind 可以包含多个地址，以 "-" 开头的地址不可用时跳过
+    async fn bind_tcp(port: u16) -> Result<Vec<TcpListener>, Box<GeneralError>> {
+        let mut listeners = Vec::new();
+        for addr in LISTEN_CONFIG.bind().split_whitespace() {
+            let (optional, addr) = match addr.strip_prefix('-') {
+                Some(addr) => (true, addr),
+                None => (false, addr),
+            };
+            let host = match addr {
+                "*" => "0.0.0.0",
+                "::*" => "::",
+                host => host,
+            };
+            match TcpListener::bind((host, port)).await {
+                Ok(listener) => listeners.push(listener),
+                Err(e) if optional => log!(Warning, "Skipping bind address {}: {}", addr, e),
+                Err(e) => return Err(format!("Could not bind {}:{}: {}", host, port, e).into()),
+            }
+        }
+        if listeners.is_empty() {
+            return Err(format!("Could not bind any address on port {}", port).into());
+        }
+        Ok(listeners)
+    }
 
-        Ok(Self { db, listener })
+    fn bind_unix(path: &str) -> Result<UnixListener, Box<GeneralError>> {
+        // 清理上次异常退出时残留的 socket 文件
+        if let Ok(meta) = std::fs::symlink_metadata(path)
+            && meta.file_type().is_socket()
+        {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let perm = LISTEN_CONFIG.unixsocketperm();
+        if perm != 0 {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(perm))?;
+        }
+        Ok(listener)
     }
 
+    /// 运行到收到 SHUTDOWN 命令或 SIGINT / SIGTERM，然后有序退出
     pub async fn run(&mut self) -> Result<(), Box<GeneralError>> {
-        println!("Redis server listening on {}", self.listener.local_addr()?);
+        let mut tasks = Vec::new();
+        for (listener, acceptor) in std::mem::take(&mut self.listeners) {
+            let tls = if acceptor.is_some() { " (TLS)" } else { "" };
+            log!(
+                Notice,
+                "Redis server listening on {}{}",
+                listener.local_addr()?,
+                tls
+            );
+            tasks.push(tokio::spawn(Self::accept_tcp(
+                listener,
+                acceptor,
+                self.db.clone(),
+            )));
+        }
+        if let (Some(listener), Some(path)) = (self.unix.take(), &self.unix_path) {
+            log!(Notice, "Redis server listening on {}", path);
+            tasks.push(tokio::spawn(Self::accept_unix(listener, self.db.clone())));
+        }
+        for listener in std::mem::take(&mut self.metrics) {
+            log!(
+                Notice,
+                "Metrics listening on http://{}/metrics",
+                listener.local_addr()?
+            );
+            tasks.push(tokio::spawn(Self::accept_metrics(
+                listener,
+                self.db.clone(),
+            )));
+        }
+        let pidfile = SERVER_CONFIG.pidfile();
+        if !pidfile.is_empty() {
+            std::fs::write(&pidfile, format!("{}\n", std::process::id()))
+                .map_err(|e| format!("Could not write pidfile {}: {}", pidfile, e))?;
+        }
+
+        tokio::select! {
+            _ = Self::server_cron(self.db.clone()) => {}
+            result = Self::wait_shutdown() => result?,
+        }
+        self.shutdown(tasks).await;
+        Ok(())
+    }
 
+    async fn accept_tcp(
+        listener: TcpListener,
+        acceptor: Option<TlsAcceptor>,
+        db: Arc<Mutex<Database>>,
+    ) {
         loop {
-            let (socket, addr) = self.listener.accept().await?;
-            let db = self.db.clone();
+            let socket = match listener.accept().await {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    log!(Warning, "Error accepting connection: {}", e);
+                    continue;
+                }
+            };
+            Self::configure_tcp(&socket);
+            let db = db.clone();
+            match acceptor.clone() {
+                None => {
+                    tokio::spawn(Self::serve(socket, db));
+                }
+                Some(acceptor) => {
+                    tokio::spawn(async move {
+                        let handshake = tls::handshake(&acceptor, socket, tls::HANDSHAKE_TIMEOUT);
+                        if let Some(stream) = handshake.await {
+                            Self::serve(stream, db).await;
+                        }
+                    });
+                }
+            }
+        }
+    }
 
-            tokio::spawn(async move {
-                if let Err(e) = Self::handle_connection(socket, db).await {
-                    eprintln!("Error handling connection {}: {}", addr, e);
+    /// 关闭 Nagle 算法，并按 tcp-keepalive 开启探测
+    fn configure_tcp(socket: &TcpStream) {
+        if let Err(e) = socket.set_nodelay(true) {
+            log!(Warning, "Error setting TCP_NODELAY: {}", e);
+        }
+        let interval = SERVER_CONFIG.tcp_keepalive.load(Ordering::Relaxed);
+        if interval == 0 {
+            return;
+        }
+        // 与 Redis 一样空闲 interval 秒后开始探测，每 interval/3 秒探测一次，共 3 次
+        let keepalive = TcpKeepalive::new()
+            .with_time(Duration::from_secs(interval))
+            .with_interval(Duration::from_secs((interval / 3).max(1)))
+            .with_retries(3);
+        if let Err(e) = SockRef::from(socket).set_tcp_keepalive(&keepalive) {
+            log!(Warning, "Error setting TCP keepalive: {}", e);
+        }
+    }
+
+    async fn accept_unix(listener: UnixListener, db: Arc<Mutex<Database>>) {
+        loop {
+            match listener.accept().await {
+                Ok((socket, _)) => {
+                    tokio::spawn(Self::serve(socket, db.clone()));
                 }
-            });
+                Err(e) => log!(Warning, "Error accepting connection: {}", e),
+            }
         }
     }
 
-    async fn handle_connection(
-        mut socket: TcpStream,
+    /// 处理单个客户端连接，直到连接关闭
+    pub async fn serve<S: Stream>(mut socket: S, db: Arc<Mutex<Database>>) {
+        // 保护模式下，没有设置密码时只接受本机连接
+        if auth::protected_mode() && auth::default_user_nopass() && !socket.is_local() {
+            STATS.rejected_connections.fetch_add(1, Ordering::Relaxed);
+            let _ = socket.write_all(PROTECTED_MODE_DENIED.as_bytes()).await;
+            return;
+        }
+        let (addr, laddr) = socket.addrs();
+        let (outbox, pushes) = Outbox::new();
+        let session = Session::new(outbox, addr, laddr);
+        // 检查与登记在同一次加锁中完成，并发连接不会超出上限
+        let full = {
+            let mut db = db.lock().await;
+            let full = db.clients.len() >= SERVER_CONFIG.maxclients.load(Ordering::Relaxed);
+            if !full {
+                session.open(&mut db);
+            }
+            full
+        };
+        if full {
+            STATS.rejected_connections.fetch_add(1, Ordering::Relaxed);
+            let _ = socket.write_all(MAX_CLIENTS_REACHED.as_bytes()).await;
+            return;
+        }
+        STATS
+            .total_connections_received
+            .fetch_add(1, Ordering::Relaxed);
+        // 命令处理 panic 时也要注销连接，否则它会一直占用 maxclients 的名额
+        let mut registered = Registered {
+            session: Some(session),
+            db: db.clone(),
+        };
+        let session = registered.session.as_mut().unwrap();
+        if let Err(e) = Self::handle_connection(socket, db, session, pushes).await {
+            log!(Warning, "Error handling connection {}: {}", session.addr, e);
+        }
+    }
+
+    /// 后台定时任务，抽样清理过期的 key 与哈希字段，每秒执行 hz 次
+    async fn server_cron(db: Arc<Mutex<Database>>) {
+        loop {
+            let period = Duration::from_millis(1000 / SERVER_CONFIG.hz() as u64);
+            tokio::time::sleep(period).await;
+            let expired = {
+                let mut db = db.lock().await;
+                let start = Instant::now();
+                // 与 Redis 一样，每次最多占用周期的 25%
+                let expired = db.active_expire_cycle(period / 4);
+                add_sample("expire-cycle", start.elapsed());
+                Self::close_idle_clients(&db);
+                expired
+            };
+            STATS
+                .expired_keys
+                .fetch_add(expired as u64, Ordering::Relaxed);
+            STATS.track_instantaneous();
+        }
+    }
+
+    /// 断开空闲超过 timeout 秒的连接，阻塞、订阅与 MONITOR 连接除外
+    fn close_idle_clients(db: &Database) {
+        let timeout = SERVER_CONFIG.timeout.load(Ordering::Relaxed);
+        if timeout == 0 {
+            return;
+        }
+        for client in db.clients.values() {
+            let idle = {
+                let state = client.state();
+                !state.blocked
+                    && !state.monitor
+                    && state.sub + state.psub + state.ssub == 0
+                    && state.last_interaction.elapsed().as_secs() > timeout
+            };
+            if idle {
+                client.outbox.close();
+            }
+        }
+    }
+
+    async fn handle_connection<S: Stream>(
+        mut socket: S,
         db: Arc<Mutex<Database>>,
+        session: &mut Session,
+        mut pushes: mpsc::UnboundedReceiver<Vec<u8>>,
     ) -> Result<(), Box<GeneralError>> {
         let mut buf = bytes::BytesMut::with_capacity(1024);
+        let outbox = session.outbox.clone();
 
         loop {
             let mut temp_buf = [0u8; 1024];
-            let n = socket.read(&mut temp_buf).await?;
+            let n = tokio::select! {
+                n = socket.read(&mut temp_buf) => match n {
+                    // TLS 客户端没有发送 close_notify 就断开了连接
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => 0,
+                    n => n?,
+                },
+                Some(message) = pushes.recv() => {
+                    Self::write_push(&mut socket, &outbox, message).await?;
+                    continue;
+                }
+                _ = outbox.killed() => return Self::disconnected(&outbox),
+            };
 
             if n == 0 {
                 return Ok(());
             }
+            STATS.net_input_bytes.fetch_add(n as u64, Ordering::Relaxed);
 
             buf.extend_from_slice(&temp_buf[..n]);
 
             // 处理完整命令
             while let Some(command_respvalue) = RespParser::parse(&mut buf)? {
                 let command = Command::parse(command_respvalue)?;
-                let response = Command::handle(db.clone(), command).await;
-                let response_bytes = RespParser::serializer(response);
-                socket.write_all(&response_bytes).await?;
+                STATS
+                    .total_commands_processed
+                    .fetch_add(1, Ordering::Relaxed);
+                // 阻塞命令等待期间客户端断开或被 CLIENT KILL 时，放弃等待
+                // 其他命令已经完整收到，总是执行完毕
+                let response = if command.blocks(session) {
+                    tokio::select! {
+                        biased;
+                        response = Command::handle(db.clone(), session, command) => response,
+                        _ = socket.closed() => return Ok(()),
+                        _ = outbox.killed() => return Self::disconnected(&outbox),
+                    }
+                } else {
+                    Command::handle(db.clone(), session, command).await
+                };
+                // 先写出已入队的推送消息，保证与命令回复的先后顺序
+                while let Ok(message) = pushes.try_recv() {
+                    Self::write_push(&mut socket, &outbox, message).await?;
+                }
+                if let Some(response) = response {
+                    if let RespValue::Error(_) = response {
+                        STATS.total_error_replies.fetch_add(1, Ordering::Relaxed);
+                    }
+                    let response_bytes = Self::encode(&outbox, response);
+                    STATS
+                        .net_output_bytes
+                        .fetch_add(response_bytes.len() as u64, Ordering::Relaxed);
+                    socket.write_all(&response_bytes).await?;
+                }
                 socket.flush().await?;
+                // QUIT 或 CLIENT KILL 自己：回复写出后不再处理后续命令
+                if outbox.is_closed() {
+                    return Ok(());
+                }
+            }
+            session.client.set_query_buffer(buf.len());
+        }
+    }
+
+    /// 输出缓冲区超限视为错误，CLIENT KILL 正常关闭
+    fn disconnected(outbox: &Outbox) -> Result<(), Box<GeneralError>> {
+        if outbox.is_killed() {
+            Err("client output buffer limit reached".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 按连接当前的协议版本序列化
+    fn encode(outbox: &Outbox, value: RespValue) -> Vec<u8> {
+        if outbox.protocol() >= 3 {
+            RespParser::serializer(value)
+        } else {
+            RespParser::serializer(value.into_resp2())
+        }
+    }
+
+    async fn write_push<S: Stream>(
+        socket: &mut S,
+        outbox: &Outbox,
+        bytes: Vec<u8>,
+    ) -> Result<(), Box<GeneralError>> {
+        // 客户端不读取时写入会一直等待，此时积压超限也要能断开
+        tokio::select! {
+            result = socket.write_all(&bytes) => result?,
+            _ = outbox.killed() => return Err("client output buffer limit reached".into()),
+        }
+        outbox.written(bytes.len());
+        STATS
+            .net_output_bytes
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// 连接数、超时、ACL 等配置是全局的，修改它们或建立连接的测试依次执行
+#[cfg(test)]
+pub static SERIAL: Mutex<()> = Mutex::const_new(());
+
+/// 已登记到 Database 的连接，连接任务结束（包括 panic）时注销
+struct Registered {
+    session: Option<Session>,
+    db: Arc<Mutex<Database>>,
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        let Some(mut session) = self.session.take() else {
+            return;
+        };
+        if let Ok(mut db) = self.db.try_lock() {
+            session.close(&mut db);
+            return;
+        }
+        // 锁被其他连接持有时，在后台等待锁之后注销
+        let db = self.db.clone();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move { session.close(&mut *db.lock().await) });
+        }
+    }
+}
+
+impl Drop for RedisServer {
+    fn drop(&mut self) {
+        if let Some(path) = &self.unix_path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::BytesMut;
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio::net::UnixStream;
+
+    struct Client<S> {
+        stream: S,
+        buf: BytesMut,
+    }
+
+    impl<S: AsyncRead + AsyncWrite + Unpin> Client<S> {
+        fn new(stream: S) -> Self {
+            Self {
+                stream,
+                buf: BytesMut::new(),
+            }
+        }
+
+        async fn call(&mut self, args: &[&str]) -> Option<RespValue> {
+            let request = RespValue::Array(
+                args.iter()
+                    .map(|arg| RespValue::BulkString(Some(arg.to_string())))
+                    .collect(),
+            );
+            self.stream
+                .write_all(&RespParser::serializer(request))
+                .await
+                .unwrap();
+            self.reply().await
+        }
+
+        /// 读取一个完整的回复，连接关闭时返回 None
+        async fn reply(&mut self) -> Option<RespValue> {
+            loop {
+                if let Some(value) = RespParser::parse(&mut self.buf).unwrap() {
+                    return Some(value);
+                }
+                let read = self.stream.read_buf(&mut self.buf);
+                let n = tokio::time::timeout(Duration::from_secs(5), read)
+                    .await
+                    .expect("no reply within 5s")
+                    .unwrap_or(0);
+                if n == 0 {
+                    return None;
+                }
             }
         }
+
+        /// 发送命令并读取原始回复，用于区分解析后相同的空值
+        async fn raw(&mut self, args: &[&str], len: usize) -> Vec<u8> {
+            let request = RespValue::Array(
+                args.iter()
+                    .map(|arg| RespValue::BulkString(Some(arg.to_string())))
+                    .collect(),
+            );
+            self.stream
+                .write_all(&RespParser::serializer(request))
+                .await
+                .unwrap();
+            while self.buf.len() < len {
+                assert!(self.stream.read_buf(&mut self.buf).await.unwrap() > 0);
+            }
+            self.buf.split_to(len).to_vec()
+        }
+    }
+
+    async fn start() -> (Arc<Mutex<Database>>, String) {
+        let db = Arc::new(Mutex::new(Database::new()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(RedisServer::accept_tcp(listener, None, db.clone()));
+        (db, addr)
+    }
+
+    async fn connect(addr: &str) -> Client<TcpStream> {
+        Client::new(TcpStream::connect(addr).await.unwrap())
+    }
+
+    fn ok(s: &str) -> Option<RespValue> {
+        Some(RespValue::SimpleString(s.to_string()))
+    }
+
+    fn bulk(reply: Option<RespValue>) -> String {
+        match reply {
+            Some(RespValue::BulkString(Some(s))) => s,
+            other => panic!("expected a bulk string, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn f1_unregister_on_panic() {
+        let db = Arc::new(Mutex::new(Database::new()));
+        let (outbox, _pushes) = Outbox::new();
+        let session = Session::new(outbox, "a:1".to_string(), "b:2".to_string());
+        session.open(&mut *db.lock().await);
+        assert_eq!(db.lock().await.clients.len(), 1);
+
+        let registered = Registered {
+            session: Some(session),
+            db: db.clone(),
+        };
+        let task = tokio::spawn(async move {
+            let _registered = registered;
+            panic!("handler panicked");
+        });
+        assert!(task.await.is_err());
+        assert!(db.lock().await.clients.is_empty());
+    }
+
+    #[tokio::test]
+    async fn f2_unix_socket() {
+        let _serial = SERIAL.lock().await;
+        let path = std::env::temp_dir().join(format!("rustezredis-{}.sock", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        // 上次异常退出残留的 socket 文件会被清理
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(std::fs::exists(&path).unwrap());
+        let listener = RedisServer::bind_unix(&path).unwrap();
+        let db = Arc::new(Mutex::new(Database::new()));
+        tokio::spawn(RedisServer::accept_unix(listener, db));
+
+        let mut client = Client::new(UnixStream::connect(&path).await.unwrap());
+        let list = bulk(client.call(&["CLIENT", "LIST"]).await);
+        assert!(list.contains(&format!("addr={}:0 laddr={}:0 ", path, path)));
+        std::fs::remove_file(&path).unwrap();
+
+        // 不是 socket 的文件不会被删除
+        std::fs::write(&path, "data").unwrap();
+        assert!(RedisServer::bind_unix(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn f3_maxclients() {
+        let _serial = SERIAL.lock().await;
+        let (db, addr) = start().await;
+        let max = SERVER_CONFIG.maxclients.swap(1, Ordering::Relaxed);
+        let mut first = connect(&addr).await;
+        assert_eq!(first.call(&["PING"]).await, ok("PONG"));
+        let mut second = connect(&addr).await;
+        assert_eq!(
+            second.reply().await,
+            Some(RespValue::Error(
+                "ERR max number of clients reached".to_string()
+            ))
+        );
+        assert_eq!(second.reply().await, None);
+        assert_eq!(db.lock().await.clients.len(), 1);
+        SERVER_CONFIG.maxclients.store(max, Ordering::Relaxed);
+    }
+
+    #[tokio::test]
+    async fn f4_idle_timeout() {
+        let _serial = SERIAL.lock().await;
+        let (db, addr) = start().await;
+        let mut idle = connect(&addr).await;
+        assert_eq!(idle.call(&["PING"]).await, ok("PONG"));
+        let mut subscriber = connect(&addr).await;
+        subscriber.call(&["SUBSCRIBE", "ch"]).await;
+
+        let timeout = SERVER_CONFIG.timeout.swap(1, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+        RedisServer::close_idle_clients(&*db.lock().await);
+        SERVER_CONFIG.timeout.store(timeout, Ordering::Relaxed);
+        // 空闲的普通连接被断开，订阅连接保留
+        assert_eq!(idle.reply().await, None);
+        let pong = subscriber.call(&["PING"]).await;
+        assert!(matches!(pong, Some(RespValue::Array(items)) if items.len() == 2));
+    }
+
+    #[tokio::test]
+    async fn f5_quit() {
+        let _serial = SERIAL.lock().await;
+        let (_db, addr) = start().await;
+        let mut client = connect(&addr).await;
+        // 同一批中 QUIT 之后的命令不再执行
+        client
+            .stream
+            .write_all(b"*1\r\n$4\r\nQUIT\r\n*1\r\n$4\r\nPING\r\n")
+            .await
+            .unwrap();
+        assert_eq!(client.reply().await, ok("OK"));
+        assert_eq!(client.reply().await, None);
+
+        let mut subscriber = connect(&addr).await;
+        subscriber.call(&["SUBSCRIBE", "ch"]).await;
+        assert_eq!(subscriber.call(&["QUIT"]).await, ok("OK"));
+        assert_eq!(subscriber.reply().await, None);
+    }
+
+    #[tokio::test]
+    async fn f6_reset() {
+        let _serial = SERIAL.lock().await;
+        let (_db, addr) = start().await;
+        let mut client = connect(&addr).await;
+        client.call(&["CLIENT", "SETNAME", "conn"]).await;
+        client.call(&["MULTI"]).await;
+        assert_eq!(client.call(&["SET", "k", "v"]).await, ok("QUEUED"));
+        assert_eq!(client.call(&["RESET"]).await, ok("RESET"));
+        assert_eq!(
+            client.call(&["CLIENT", "GETNAME"]).await,
+            Some(RespValue::Null)
+        );
+        assert_eq!(
+            client.call(&["EXEC"]).await,
+            Some(RespValue::Error("ERR EXEC without MULTI".to_string()))
+        );
+        assert_eq!(client.call(&["GET", "k"]).await, Some(RespValue::Null));
+
+        client.call(&["SUBSCRIBE", "ch"]).await;
+        assert_eq!(client.call(&["RESET"]).await, ok("RESET"));
+        assert_eq!(client.call(&["PING"]).await, ok("PONG"));
+    }
+
+    /// 等待连接任务处理完毕，最多等待 5 秒
+    async fn blocked_count(db: &Arc<Mutex<Database>>, expected: usize) {
+        for _ in 0..500 {
+            if db.lock().await.blocking.blocked_count() == expected {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("blocked_count did not reach {}", expected);
+    }
+
+    #[tokio::test]
+    async fn f7_unblock_on_disconnect() {
+        let _serial = SERIAL.lock().await;
+        let (db, addr) = start().await;
+        let mut closed = connect(&addr).await;
+        let mut killed = connect(&addr).await;
+        let id = killed.call(&["CLIENT", "ID"]).await;
+        for client in [&mut closed, &mut killed] {
+            let request = b"*3\r\n$5\r\nBLPOP\r\n$1\r\nl\r\n$1\r\n0\r\n";
+            client.stream.write_all(request).await.unwrap();
+        }
+        blocked_count(&db, 2).await;
+
+        // 断开连接与 CLIENT KILL 都会取消阻塞登记
+        drop(closed);
+        let mut client = connect(&addr).await;
+        let Some(RespValue::Integer(id)) = id else {
+            panic!("CLIENT ID should return an integer");
+        };
+        let kill = client
+            .call(&["CLIENT", "KILL", "ID", &id.to_string()])
+            .await;
+        assert_eq!(kill, Some(RespValue::Integer(1)));
+        assert_eq!(killed.reply().await, None);
+        blocked_count(&db, 0).await;
+
+        assert_eq!(
+            client.call(&["RPUSH", "l", "a"]).await,
+            Some(RespValue::Integer(1))
+        );
+        assert_eq!(
+            client.call(&["LLEN", "l"]).await,
+            Some(RespValue::Integer(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn f8_half_close_runs_write() {
+        let _serial = SERIAL.lock().await;
+        let (db, addr) = start().await;
+        let mut client = connect(&addr).await;
+        assert_eq!(client.call(&["PING"]).await, ok("PONG"));
+        // 写命令等待数据库锁期间客户端关闭写端，命令仍然执行
+        let guard = db.lock().await;
+        let request = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n";
+        client.stream.write_all(request).await.unwrap();
+        client.stream.shutdown().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(guard);
+        assert_eq!(client.reply().await, ok("OK"));
+        assert!(db.lock().await.exists("k"));
+    }
+
+    /// INFO commandstats 中命令的调用次数
+    async fn calls(client: &mut Client<TcpStream>, name: &str) -> u64 {
+        let info = bulk(client.call(&["INFO", "commandstats"]).await);
+        let prefix = format!("cmdstat_{}:calls=", name);
+        info.lines()
+            .find_map(|line| line.strip_prefix(&prefix))
+            .map_or(0, |rest| rest.split(',').next().unwrap().parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn f9_blocking_replies() {
+        let _serial = SERIAL.lock().await;
+        let (_db, addr) = start().await;
+        let mut client = connect(&addr).await;
+        // 超时时 RESP2 返回空数组，BLMOVE 返回空字符串，RESP3 返回 Null
+        assert_eq!(client.raw(&["BLPOP", "l", "0.01"], 5).await, b"*-1\r\n");
+        assert_eq!(
+            client.raw(&["BLMPOP", "0.01", "1", "l", "LEFT"], 5).await,
+            b"*-1\r\n"
+        );
+        assert_eq!(
+            client
+                .raw(&["BLMOVE", "l", "d", "LEFT", "LEFT", "0.01"], 5)
+                .await,
+            b"$-1\r\n"
+        );
+        let mut resp3 = connect(&addr).await;
+        resp3
+            .stream
+            .write_all(b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n")
+            .await
+            .unwrap();
+        let request = b"*3\r\n$5\r\nBRPOP\r\n$1\r\nl\r\n$4\r\n0.01\r\n";
+        resp3.stream.write_all(request).await.unwrap();
+        // HELLO 的回复之后是 BRPOP 的回复
+        while !resp3.buf.ends_with(b"\r\n_\r\n") {
+            assert!(resp3.stream.read_buf(&mut resp3.buf).await.unwrap() > 0);
+        }
+
+        // 阻塞后被唤醒的命令只记录一次
+        let before = calls(&mut client, "blpop").await;
+        let mut waiter = connect(&addr).await;
+        let request = b"*3\r\n$5\r\nBLPOP\r\n$1\r\nl\r\n$1\r\n0\r\n";
+        waiter.stream.write_all(request).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.call(&["RPUSH", "l", "a"]).await;
+        let popped = waiter.reply().await;
+        assert!(matches!(popped, Some(RespValue::Array(items)) if items.len() == 2));
+        assert_eq!(calls(&mut client, "blpop").await, before + 1);
+    }
+
+    #[tokio::test]
+    async fn f10_serve_blocked_after_exec() {
+        let _serial = SERIAL.lock().await;
+        let (db, addr) = start().await;
+        let mut waiter = connect(&addr).await;
+        let request = b"*3\r\n$5\r\nBLPOP\r\n$1\r\nl\r\n$1\r\n0\r\n";
+        waiter.stream.write_all(request).await.unwrap();
+        blocked_count(&db, 1).await;
+
+        // 事务中写入的元素在 EXEC 结束之后才被阻塞的客户端取走
+        let mut client = connect(&addr).await;
+        client.call(&["MULTI"]).await;
+        client.call(&["LPUSH", "l", "x"]).await;
+        client.call(&["LLEN", "l"]).await;
+        assert_eq!(
+            client.call(&["EXEC"]).await,
+            Some(RespValue::Array(vec![
+                RespValue::Integer(1),
+                RespValue::Integer(1)
+            ]))
+        );
+        assert_eq!(
+            waiter.reply().await,
+            Some(RespValue::Array(vec![
+                RespValue::BulkString(Some("l".to_string())),
+                RespValue::BulkString(Some("x".to_string())),
+            ]))
+        );
+        assert_eq!(
+            client.call(&["LLEN", "l"]).await,
+            Some(RespValue::Integer(0))
+        );
+    }
+
+    #[tokio::test]
+    async fn f11_shutdown() {
+        let _serial = SERIAL.lock().await;
+        let dir = std::env::temp_dir();
+        let pidfile = dir.join(format!("rustezredis-{}.pid", std::process::id()));
+        let pidfile = pidfile.to_str().unwrap().to_string();
+        let socket = dir.join(format!("rustezredis-shutdown-{}.sock", std::process::id()));
+        let socket = socket.to_str().unwrap().to_string();
+        *SERVER_CONFIG.pidfile.write().unwrap() = pidfile.clone();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let mut server = RedisServer {
+            db: Arc::new(Mutex::new(Database::new())),
+            listeners: vec![(listener, None)],
+            unix: Some(RedisServer::bind_unix(&socket).unwrap()),
+            unix_path: Some(socket.clone()),
+            metrics: Vec::new(),
+        };
+        let db = server.db.clone();
+        let run = tokio::spawn(async move { server.run().await.is_ok() });
+
+        let mut waiter = Client::new(UnixStream::connect(&socket).await.unwrap());
+        let request = b"*3\r\n$5\r\nBLPOP\r\n$1\r\nl\r\n$1\r\n0\r\n";
+        waiter.stream.write_all(request).await.unwrap();
+        blocked_count(&db, 1).await;
+        assert_eq!(
+            std::fs::read_to_string(&pidfile).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+
+        // 成功时不回复，阻塞的客户端被唤醒，所有连接被断开
+        let mut client = connect(&addr).await;
+        assert_eq!(client.call(&["SHUTDOWN", "NOSAVE"]).await, None);
+        assert_eq!(
+            waiter.reply().await,
+            Some(RespValue::Error(
+                "UNBLOCKED server is shutting down".to_string()
+            ))
+        );
+        assert_eq!(waiter.reply().await, None);
+        assert!(run.await.unwrap());
+        SERVER_CONFIG.pidfile.write().unwrap().clear();
+        assert!(!std::fs::exists(&pidfile).unwrap());
+        assert!(!std::fs::exists(&socket).unwrap());
     }
 }
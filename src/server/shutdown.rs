@@ -0,0 +1,64 @@
+use std::sync::LazyLock;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use super::{RedisServer, SERVER_CONFIG, log};
+use crate::protocol::{GeneralError, RespValue};
+
+/// SHUTDOWN 命令通知主循环退出
+static SHUTDOWN: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// 等待连接断开的最长时间
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn request_shutdown() {
+    SHUTDOWN.notify_one();
+}
+
+impl RedisServer {
+    /// 等待 SHUTDOWN 命令、SIGINT 或 SIGTERM
+    pub(super) async fn wait_shutdown() -> Result<(), Box<GeneralError>> {
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            _ = SHUTDOWN.notified() => log!(Notice, "User requested shutdown..."),
+            _ = tokio::signal::ctrl_c() => log!(Notice, "Received SIGINT scheduling shutdown..."),
+            _ = terminate.recv() => log!(Notice, "Received SIGTERM scheduling shutdown..."),
+        }
+        Ok(())
+    }
+
+    /// 停止接受连接，唤醒阻塞的客户端，等正在执行的命令完成后断开所有连接
+    pub(super) async fn shutdown(&self, tasks: Vec<JoinHandle<()>>) {
+        for task in tasks {
+            task.abort();
+        }
+        {
+            // 拿到锁时没有命令正在修改数据
+            let mut db = self.db.lock().await;
+            db.blocking.unblock_all(RespValue::Error(
+                "UNBLOCKED server is shutting down".to_string(),
+            ));
+            for client in db.clients.values() {
+                client.outbox.close();
+            }
+        }
+        let deadline = Instant::now() + DRAIN_TIMEOUT;
+        while !self.db.lock().await.clients.is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        if !SERVER_CONFIG.save.read().unwrap().is_empty()
+            || SERVER_CONFIG.appendonly.load(Ordering::Relaxed)
+        {
+            log!(Notice, "Persistence is not implemented, nothing to save");
+        }
+        let pidfile = SERVER_CONFIG.pidfile();
+        if !pidfile.is_empty() {
+            let _ = std::fs::remove_file(&pidfile);
+        }
+        log!(Notice, "Redis is now ready to exit, bye bye...");
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+/// 运行统计，CONFIG RESETSTAT 清零
+pub struct Stats {
+    pub total_connections_received: AtomicU64,
+    pub total_commands_processed: AtomicU64,
+    /// 保护模式拒绝的连接
+    pub rejected_connections: AtomicU64,
+    /// 因 maxmemory 被淘汰的 key
+    pub evicted_keys: AtomicU64,
+    pub expired_keys: AtomicU64,
+    pub keyspace_hits: AtomicU64,
+    pub keyspace_misses: AtomicU64,
+    pub net_input_bytes: AtomicU64,
+    pub net_output_bytes: AtomicU64,
+    /// 返回给客户端的错误回复
+    pub total_error_replies: AtomicU64,
+    samples: Mutex<Samples>,
+}
+
+/// 用于 instantaneous_* 指标的采样：上次采样时的计数器，以及最近的每秒速率，
+/// 依次为命令数、输入字节与输出字节
+struct Samples {
+    last: Option<(Instant, [u64; 3])>,
+    rates: VecDeque<[f64; 3]>,
+}
+
+impl Samples {
+    const fn new() -> Self {
+        Self {
+            last: None,
+            rates: VecDeque::new(),
+        }
+    }
+}
+
+/// 计算瞬时速率时保留的采样个数
+const INSTANTANEOUS_SAMPLES: usize = 16;
+
+pub static STATS: Stats = Stats {
+    total_connections_received: AtomicU64::new(0),
+    total_commands_processed: AtomicU64::new(0),
+    rejected_connections: AtomicU64::new(0),
+    evicted_keys: AtomicU64::new(0),
+    expired_keys: AtomicU64::new(0),
+    keyspace_hits: AtomicU64::new(0),
+    keyspace_misses: AtomicU64::new(0),
+    net_input_bytes: AtomicU64::new(0),
+    net_output_bytes: AtomicU64::new(0),
+    total_error_replies: AtomicU64::new(0),
+    samples: Mutex::new(Samples::new()),
+};
+
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// 每次启动随机生成的 40 位十六进制 ID，INFO 中的 run_id
+pub static RUN_ID: LazyLock<String> = LazyLock::new(|| {
+    let mut rng = rand::rng();
+    (0..40)
+        .map(|_| char::from_digit(rng.random_range(0..16), 16).unwrap())
+        .collect()
+});
+
+/// 服务启动以来的时间
+pub fn uptime() -> Duration {
+    START.elapsed()
+}
+
+impl Stats {
+    pub fn reset(&self) {
+        for counter in [
+            &self.total_connections_received,
+            &self.total_commands_processed,
+            &self.rejected_connections,
+            &self.evicted_keys,
+            &self.expired_keys,
+            &self.keyspace_hits,
+            &self.keyspace_misses,
+            &self.net_input_bytes,
+            &self.net_output_bytes,
+            &self.total_error_replies,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        *self.samples.lock().unwrap() = Samples::new();
+    }
+
+    /// 由 server_cron 定期调用，记录与上次采样之间的速率
+    pub fn track_instantaneous(&self) {
+        let now = Instant::now();
+        let current = [
+            &self.total_commands_processed,
+            &self.net_input_bytes,
+            &self.net_output_bytes,
+        ]
+        .map(|counter| counter.load(Ordering::Relaxed));
+        let mut samples = self.samples.lock().unwrap();
+        if let Some((last_time, last)) = samples.last {
+            let seconds = (now - last_time).as_secs_f64();
+            if seconds > 0.0 {
+                let rates =
+                    std::array::from_fn(|i| current[i].saturating_sub(last[i]) as f64 / seconds);
+                samples.rates.push_back(rates);
+                if samples.rates.len() > INSTANTANEOUS_SAMPLES {
+                    samples.rates.pop_front();
+                }
+            }
+        }
+        samples.last = Some((now, current));
+    }
+
+    /// 最近采样的平均值：(每秒命令数, 每秒输入字节, 每秒输出字节)
+    pub fn instantaneous(&self) -> (f64, f64, f64) {
+        let samples = self.samples.lock().unwrap();
+        let n = samples.rates.len().max(1) as f64;
+        let average = |i: usize| samples.rates.iter().map(|rates| rates[i]).sum::<f64>() / n;
+        (average(0), average(1), average(2))
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::ErrorKind;
+use std::mem::MaybeUninit;
+
+use socket2::SockRef;
+use tokio::io::{AsyncRead, AsyncWrite, Interest};
+use tokio::net::{TcpStream, UnixStream};
+use tokio_rustls::server::TlsStream;
+
+/// TCP、TLS 与 Unix socket 连接共用同一套命令处理逻辑
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {
+    /// 客户端断开时返回；还有未读取的数据时无法判断连接状态，一直等待
+    fn closed(&self) -> impl Future<Output = ()> + Send + '_;
+
+    /// 对端与本端地址，Unix socket 为 "路径:0"
+    fn addrs(&self) -> (String, String);
+
+    /// 连接是否来自本机
+    fn is_local(&self) -> bool;
+}
+
+impl Stream for TcpStream {
+    async fn closed(&self) {
+        let mut probe = [0u8; 1];
+        match self.peek(&mut probe).await {
+            Ok(0) | Err(_) => {}
+            Ok(_) => std::future::pending().await,
+        }
+    }
+
+    fn addrs(&self) -> (String, String) {
+        let format = |addr: std::io::Result<std::net::SocketAddr>| {
+            addr.map_or_else(|_| "?:0".to_string(), |a| a.to_string())
+        };
+        (format(self.peer_addr()), format(self.local_addr()))
+    }
+
+    fn is_local(&self) -> bool {
+        self.peer_addr()
+            .is_ok_and(|addr| addr.ip().to_canonical().is_loopback())
+    }
+}
+
+impl Stream for TlsStream<TcpStream> {
+    fn closed(&self) -> impl Future<Output = ()> + Send + '_ {
+        self.get_ref().0.closed()
+    }
+
+    fn addrs(&self) -> (String, String) {
+        self.get_ref().0.addrs()
+    }
+
+    fn is_local(&self) -> bool {
+        self.get_ref().0.is_local()
+    }
+}
+
+impl Stream for UnixStream {
+    async fn closed(&self) {
+        // tokio 的 UnixStream 没有 peek，借助 socket2 在可读时窥探
+        let mut probe = [MaybeUninit::<u8>::uninit(); 1];
+        loop {
+            if self.readable().await.is_err() {
+                return;
+            }
+            match self.try_io(Interest::READABLE, || SockRef::from(self).peek(&mut probe)) {
+                Ok(0) => return,
+                Ok(_) => return std::future::pending().await,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
+                Err(_) => return,
+            }
+        }
+    }
+
+    fn addrs(&self) -> (String, String) {
+        let path = self
+            .local_addr()
+            .ok()
+            .and_then(|addr| addr.as_pathname().map(|p| p.display().to_string()))
+            .unwrap_or_default();
+        (format!("{}:0", path), format!("{}:0", path))
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
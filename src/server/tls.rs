@@ -0,0 +1,315 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use tokio::net::TcpStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::RootCertStore;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::server::TlsStream;
+
+use super::log;
+use crate::protocol::GeneralError;
+
+/// TLS 握手的最长时间，握手完成之前连接还没有登记，不受 maxclients 限制
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// TLS 相关配置，只在启动监听时读取
+pub struct TlsConfig {
+    /// 0 表示不开启 TLS 端口
+    pub port: AtomicU16,
+    pub cert_file: RwLock<String>,
+    pub key_file: RwLock<String>,
+    pub ca_cert_file: RwLock<String>,
+    /// yes | no | optional
+    pub auth_clients: RwLock<&'static str>,
+}
+
+pub static TLS_CONFIG: TlsConfig = TlsConfig {
+    port: AtomicU16::new(0),
+    cert_file: RwLock::new(String::new()),
+    key_file: RwLock::new(String::new()),
+    ca_cert_file: RwLock::new(String::new()),
+    auth_clients: RwLock::new("yes"),
+};
+
+impl TlsConfig {
+    pub fn port(&self) -> u16 {
+        self.port.load(Ordering::Relaxed)
+    }
+
+    pub fn auth_clients(&self) -> &'static str {
+        *self.auth_clients.read().unwrap()
+    }
+
+    pub fn set_auth_clients(&self, value: &str) -> Result<(), String> {
+        let value = match value.to_lowercase().as_str() {
+            "yes" => "yes",
+            "no" => "no",
+            "optional" => "optional",
+            _ => return Err("argument must be 'yes', 'no' or 'optional'".to_string()),
+        };
+        *self.auth_clients.write().unwrap() = value;
+        Ok(())
+    }
+
+    /// 按当前配置创建 TLS 握手器
+    pub fn acceptor(&self) -> Result<TlsAcceptor, Box<GeneralError>> {
+        build_acceptor(
+            &self.cert_file.read().unwrap(),
+            &self.key_file.read().unwrap(),
+            &self.ca_cert_file.read().unwrap(),
+            self.auth_clients(),
+        )
+    }
+}
+
+fn open(path: &str, name: &str) -> Result<BufReader<File>, Box<GeneralError>> {
+    if path.is_empty() {
+        return Err(format!("{} is required when tls-port is set", name).into());
+    }
+    let file = File::open(path).map_err(|e| format!("{} '{}': {}", name, path, e))?;
+    Ok(BufReader::new(file))
+}
+
+fn load_certs(path: &str, name: &str) -> Result<Vec<CertificateDer<'static>>, Box<GeneralError>> {
+    let certs = rustls_pemfile::certs(&mut open(path, name)?).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(format!("{} '{}' contains no certificate", name, path).into());
+    }
+    Ok(certs)
+}
+
+fn build_acceptor(
+    cert_file: &str,
+    key_file: &str,
+    ca_cert_file: &str,
+    auth_clients: &str,
+) -> Result<TlsAcceptor, Box<GeneralError>> {
+    let certs = load_certs(cert_file, "tls-cert-file")?;
+    let key: PrivateKeyDer<'static> =
+        rustls_pemfile::private_key(&mut open(key_file, "tls-key-file")?)?
+            .ok_or_else(|| format!("tls-key-file '{}' contains no private key", key_file))?;
+
+    let builder = ServerConfig::builder();
+    let builder = if auth_clients == "no" {
+        builder.with_no_client_auth()
+    } else {
+        // 客户端证书需要由 tls-ca-cert-file 中的 CA 签发
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(ca_cert_file, "tls-ca-cert-file")? {
+            roots.add(cert)?;
+        }
+        let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+        let verifier = if auth_clients == "optional" {
+            verifier.allow_unauthenticated().build()?
+        } else {
+            verifier.build()?
+        };
+        builder.with_client_cert_verifier(verifier)
+    };
+    let config = builder.with_single_cert(certs, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// 在 limit 内完成握手，失败或超时时记录日志并返回 None
+pub async fn handshake(
+    acceptor: &TlsAcceptor,
+    socket: TcpStream,
+    limit: Duration,
+) -> Option<TlsStream<TcpStream>> {
+    match tokio::time::timeout(limit, acceptor.accept(socket)).await {
+        Ok(Ok(stream)) => Some(stream),
+        Ok(Err(e)) => {
+            log!(Warning, "Error accepting TLS connection: {}", e);
+            None
+        }
+        Err(_) => {
+            log!(Warning, "Timeout accepting TLS connection");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::Mutex;
+    use tokio_rustls::TlsConnector;
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::rustls::pki_types::ServerName;
+
+    use super::*;
+    use crate::server::RedisServer;
+    use crate::storage::Database;
+
+    struct Pki {
+        dir: PathBuf,
+        ca: String,
+        client_cert: String,
+        client_key: String,
+    }
+
+    impl Pki {
+        /// 生成自签名 CA 以及由它签发的服务端、客户端证书
+        fn generate(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "rust-ez-redis-tls-{}-{}",
+                name,
+                std::process::id()
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let ca_key = KeyPair::generate().unwrap();
+            let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+            ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let ca = ca_params.self_signed(&ca_key).unwrap();
+
+            let server_key = KeyPair::generate().unwrap();
+            let server = CertificateParams::new(vec!["localhost".to_string()])
+                .unwrap()
+                .signed_by(&server_key, &ca, &ca_key)
+                .unwrap();
+
+            let client_key = KeyPair::generate().unwrap();
+            let mut client_params = CertificateParams::new(vec!["client".to_string()]).unwrap();
+            client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+            let client = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+            std::fs::write(dir.join("ca.crt"), ca.pem()).unwrap();
+            std::fs::write(dir.join("server.crt"), server.pem()).unwrap();
+            std::fs::write(dir.join("server.key"), server_key.serialize_pem()).unwrap();
+            Self {
+                dir,
+                ca: ca.pem(),
+                client_cert: client.pem(),
+                client_key: client_key.serialize_pem(),
+            }
+        }
+
+        fn path(&self, file: &str) -> String {
+            self.dir.join(file).to_string_lossy().into_owned()
+        }
+
+        fn acceptor(&self, auth_clients: &str) -> TlsAcceptor {
+            build_acceptor(
+                &self.path("server.crt"),
+                &self.path("server.key"),
+                &self.path("ca.crt"),
+                auth_clients,
+            )
+            .unwrap()
+        }
+
+        fn connector(&self, with_cert: bool) -> TlsConnector {
+            let mut roots = RootCertStore::empty();
+            for cert in rustls_pemfile::certs(&mut self.ca.as_bytes()) {
+                roots.add(cert.unwrap()).unwrap();
+            }
+            let builder = ClientConfig::builder().with_root_certificates(roots);
+            let config = if with_cert {
+                let certs = rustls_pemfile::certs(&mut self.client_cert.as_bytes())
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                let key = rustls_pemfile::private_key(&mut self.client_key.as_bytes())
+                    .unwrap()
+                    .unwrap();
+                builder.with_client_auth_cert(certs, key).unwrap()
+            } else {
+                builder.with_no_client_auth()
+            };
+            TlsConnector::from(Arc::new(config))
+        }
+    }
+
+    impl Drop for Pki {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// 启动只处理一个 TLS 连接的服务端，返回监听地址
+    async fn serve_once(acceptor: TlsAcceptor) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            if let Some(stream) = handshake(&acceptor, socket, HANDSHAKE_TIMEOUT).await {
+                let db = Arc::new(Mutex::new(Database::new()));
+                RedisServer::serve(stream, db).await;
+            }
+        });
+        addr
+    }
+
+    /// 发送 PING，返回读到的回复；握手或读取失败时返回 None
+    async fn ping(connector: TlsConnector, addr: std::net::SocketAddr) -> Option<Vec<u8>> {
+        let socket = TcpStream::connect(addr).await.unwrap();
+        let name = ServerName::try_from("localhost").unwrap();
+        let mut stream = connector.connect(name, socket).await.ok()?;
+        stream.write_all(b"*1\r\n$4\r\nPING\r\n").await.ok()?;
+        let mut buf = [0u8; 64];
+        let n = stream.read(&mut buf).await.ok()?;
+        Some(buf[..n].to_vec())
+    }
+
+    #[tokio::test]
+    async fn f1_tls_ping() {
+        let pki = Pki::generate("ping");
+        let addr = serve_once(pki.acceptor("no")).await;
+        assert_eq!(
+            ping(pki.connector(false), addr).await.as_deref(),
+            Some(&b"+PONG\r\n"[..])
+        );
+    }
+
+    #[tokio::test]
+    async fn f2_mutual_auth() {
+        let pki = Pki::generate("mutual");
+        let addr = serve_once(pki.acceptor("yes")).await;
+        assert_eq!(
+            ping(pki.connector(true), addr).await.as_deref(),
+            Some(&b"+PONG\r\n"[..])
+        );
+
+        // 没有客户端证书时握手失败
+        let addr = serve_once(pki.acceptor("yes")).await;
+        assert_ne!(
+            ping(pki.connector(false), addr).await.as_deref(),
+            Some(&b"+PONG\r\n"[..])
+        );
+
+        let addr = serve_once(pki.acceptor("optional")).await;
+        assert_eq!(
+            ping(pki.connector(false), addr).await.as_deref(),
+            Some(&b"+PONG\r\n"[..])
+        );
+    }
+
+    #[tokio::test]
+    async fn f3_handshake_timeout() {
+        let pki = Pki::generate("timeout");
+        let acceptor = pki.acceptor("no");
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            handshake(&acceptor, socket, Duration::from_millis(50))
+                .await
+                .is_some()
+        });
+        // 建立连接后不发送 ClientHello，服务端超时后关闭连接
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        assert!(!server.await.unwrap());
+        let mut buf = [0u8; 16];
+        assert_eq!(socket.read(&mut buf).await.unwrap_or(0), 0);
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use tokio::sync::oneshot;
+
+use crate::protocol::RespValue;
+
+/// 被阻塞的客户端在数据就绪后要执行的操作
+#[derive(Debug, Clone)]
+pub enum BlockedOp {
+    // BLPOP / BRPOP
+    Pop {
+        left: bool,
+    },
+    // BLMOVE
+    Move {
+        dest: String,
+        from_left: bool,
+        to_left: bool,
+    },
+    // BLMPOP
+    MPop {
+        left: bool,
+        count: usize,
+    },
+}
+
+#[derive(Debug)]
+pub struct BlockedClient {
+    pub keys: Vec<String>,
+    pub op: BlockedOp,
+    pub reply: oneshot::Sender<RespValue>,
+}
+
+/// 阻塞等待登记表：以 client id 登记，每个 key 上的客户端按先来先服务排队
+#[derive(Debug, Default)]
+pub struct BlockingRegistry {
+    clients: HashMap<u64, BlockedClient>,
+    waiting: HashMap<String, VecDeque<u64>>,
+    ready: Vec<String>,
+    ready_set: HashSet<String>,
+}
+
+impl BlockingRegistry {
+    pub fn block(
+        &mut self,
+        id: u64,
+        keys: Vec<String>,
+        op: BlockedOp,
+    ) -> oneshot::Receiver<RespValue> {
+        let (tx, rx) = oneshot::channel();
+        for key in &keys {
+            let queue = self.waiting.entry(key.clone()).or_default();
+            if !queue.contains(&id) {
+                queue.push_back(id);
+            }
+        }
+        self.clients.insert(
+            id,
+            BlockedClient {
+                keys,
+                op,
+                reply: tx,
+            },
+        );
+        rx
+    }
+
+    pub fn unblock(&mut self, id: u64) -> Option<BlockedClient> {
+        let client = self.clients.remove(&id)?;
+        for key in &client.keys {
+            if let Some(queue) = self.waiting.get_mut(key) {
+                queue.retain(|&i| i != id);
+                if queue.is_empty() {
+                    self.waiting.remove(key);
+                }
+            }
+        }
+        Some(client)
+    }
+
+    /// 返回 key 上排在最前面且连接仍然存活的客户端
+    pub fn first_waiter(&mut self, key: &str) -> Option<(u64, &BlockedOp)> {
+        loop {
+            let id = *self.waiting.get(key)?.front()?;
+            if self.clients.get(&id).is_some_and(|c| !c.reply.is_closed()) {
+                return self.clients.get(&id).map(|c| (id, &c.op));
+            }
+            self.unblock(id);
+        }
+    }
+
+    pub fn signal_key_ready(&mut self, key: &str) {
+        if self.waiting.contains_key(key) && self.ready_set.insert(key.to_string()) {
+            self.ready.push(key.to_string());
+        }
+    }
+
+    pub fn take_ready_keys(&mut self) -> Vec<String> {
+        self.ready_set.clear();
+        std::mem::take(&mut self.ready)
+    }
+
+    /// 关闭服务器时以同一个回复唤醒所有阻塞的客户端
+    pub fn unblock_all(&mut self, reply: RespValue) {
+        self.waiting.clear();
+        self.take_ready_keys();
+        for (_, client) in self.clients.drain() {
+            let _ = client.reply.send(reply.clone());
+        }
+    }
+
+    /// 正在阻塞等待的客户端数
+    pub fn blocked_count(&self) -> usize {
+        self.clients.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_fifo_and_unblock() {
+        let mut registry = BlockingRegistry::default();
+        let op = BlockedOp::Pop { left: true };
+        let (id1, id2) = (1, 2);
+        let _rx1 = registry.block(id1, vec!["a".into(), "b".into()], op.clone());
+        let _rx2 = registry.block(id2, vec!["b".into()], op.clone());
+        assert_eq!(registry.first_waiter("b").map(|(id, _)| id), Some(id1));
+
+        // 被唤醒后从所有 key 的队列中移除
+        registry.unblock(id1);
+        assert!(registry.first_waiter("a").is_none());
+        assert_eq!(registry.first_waiter("b").map(|(id, _)| id), Some(id2));
+
+        // 连接已经断开的客户端会被跳过
+        let rx3 = registry.block(3, vec!["c".into()], op.clone());
+        drop(rx3);
+        let id4 = 4;
+        let _rx4 = registry.block(id4, vec!["c".into()], op);
+        assert_eq!(registry.first_waiter("c").map(|(id, _)| id), Some(id4));
+
+        registry.signal_key_ready("c");
+        registry.signal_key_ready("c");
+        registry.signal_key_ready("nobody");
+        assert_eq!(registry.take_ready_keys(), vec!["c".to_string()]);
+    }
+
+    #[test]
+    fn f2_unblock_all() {
+        let mut registry = BlockingRegistry::default();
+        let mut rx1 = registry.block(1, vec!["a".into()], BlockedOp::Pop { left: true });
+        let mut rx2 = registry.block(
+            2,
+            vec!["a".into(), "b".into()],
+            BlockedOp::Pop { left: false },
+        );
+        registry.signal_key_ready("a");
+        registry.unblock_all(RespValue::Null);
+        assert_eq!(rx1.try_recv().unwrap(), RespValue::Null);
+        assert_eq!(rx2.try_recv().unwrap(), RespValue::Null);
+        assert_eq!(registry.blocked_count(), 0);
+        assert!(registry.first_waiter("a").is_none());
+        assert!(registry.take_ready_keys().is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::Outbox;
+
+/// 登记在 Database 中的连接，CLIENT LIST / KILL 与 INFO clients 通过它查看其他连接
+#[derive(Debug)]
+pub struct Client {
+    pub id: u64,
+    /// 客户端与本端地址，Unix socket 为 "路径:0"
+    pub addr: String,
+    pub laddr: String,
+    pub outbox: Arc<Outbox>,
+    created: Instant,
+    /// 已读取但还没有解析成命令的字节数
+    query_buffer: AtomicUsize,
+    state: Mutex<ClientState>,
+}
+
+/// 由连接在执行命令时更新的状态
+#[derive(Debug, Clone)]
+pub struct ClientState {
+    pub name: String,
+    pub lib_name: String,
+    pub lib_ver: String,
+    pub user: String,
+    /// 最近执行的命令，带子命令时为 "client|list" 的形式
+    pub last_cmd: String,
+    pub last_interaction: Instant,
+    /// 事务中排队的命令数，不在事务中为 -1
+    pub multi: i64,
+    pub watch: usize,
+    pub sub: usize,
+    pub psub: usize,
+    pub ssub: usize,
+    pub blocked: bool,
+    pub monitor: bool,
+    pub no_evict: bool,
+}
+
+impl Client {
+    pub fn new(id: u64, addr: String, laddr: String, outbox: Arc<Outbox>) -> Self {
+        let now = Instant::now();
+        Self {
+            id,
+            addr,
+            laddr,
+            outbox,
+            created: now,
+            query_buffer: AtomicUsize::new(0),
+            state: Mutex::new(ClientState {
+                name: String::new(),
+                lib_name: String::new(),
+                lib_ver: String::new(),
+                user: "default".to_string(),
+                last_cmd: "NULL".to_string(),
+                last_interaction: now,
+                multi: -1,
+                watch: 0,
+                sub: 0,
+                psub: 0,
+                ssub: 0,
+                blocked: false,
+                monitor: false,
+                no_evict: false,
+            }),
+        }
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, ClientState> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn age(&self) -> Duration {
+        self.created.elapsed()
+    }
+
+    pub fn query_buffer(&self) -> usize {
+        self.query_buffer.load(Ordering::Relaxed)
+    }
+
+    pub fn set_query_buffer(&self, size: usize) {
+        self.query_buffer.store(size, Ordering::Relaxed);
+    }
+
+    /// CLIENT LIST 中的 type
+    pub fn kind(&self) -> &'static str {
+        let state = self.state();
+        if state.sub + state.psub + state.ssub > 0 {
+            "pubsub"
+        } else {
+            "normal"
+        }
+    }
+}
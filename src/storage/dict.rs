@@ -0,0 +1,160 @@
+use std::hash::{BuildHasher, RandomState};
+
+use rand::Rng;
+
+const MIN_BUCKETS: usize = 4;
+
+/// 链式哈希表实现的字符串集合，桶数为 2 的幂，
+/// 支持 O(1) 随机取成员，以及与 Redis dictScan 相同的反向二进制游标遍历
+#[derive(Debug, Clone)]
+pub struct Dict {
+    buckets: Vec<Vec<String>>,
+    len: usize,
+    hasher: RandomState,
+}
+
+impl Default for Dict {
+    fn default() -> Self {
+        Self {
+            buckets: vec![Vec::new(); MIN_BUCKETS],
+            len: 0,
+            hasher: RandomState::new(),
+        }
+    }
+}
+
+impl Dict {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 桶的个数
+    pub fn buckets(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn bucket(&self, member: &str) -> usize {
+        self.hasher.hash_one(member) as usize & (self.buckets.len() - 1)
+    }
+
+    pub fn contains(&self, member: &str) -> bool {
+        self.buckets[self.bucket(member)]
+            .iter()
+            .any(|m| m == member)
+    }
+
+    pub fn insert(&mut self, member: String) -> bool {
+        if self.contains(&member) {
+            return false;
+        }
+        let bucket = self.bucket(&member);
+        self.buckets[bucket].push(member);
+        self.len += 1;
+        if self.len > self.buckets.len() {
+            self.resize(self.buckets.len() * 2);
+        }
+        true
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        let bucket = self.bucket(member);
+        let Some(pos) = self.buckets[bucket].iter().position(|m| m == member) else {
+            return false;
+        };
+        self.buckets[bucket].swap_remove(pos);
+        self.len -= 1;
+        // 负载低于 1/8 时缩小，随机取成员时平均最多尝试 8 个桶
+        if self.buckets.len() > MIN_BUCKETS && self.len * 8 < self.buckets.len() {
+            self.resize(self.len.next_power_of_two().max(MIN_BUCKETS));
+        }
+        true
+    }
+
+    fn resize(&mut self, size: usize) {
+        let old = std::mem::replace(&mut self.buckets, vec![Vec::new(); size]);
+        for member in old.into_iter().flatten() {
+            let bucket = self.bucket(&member);
+            self.buckets[bucket].push(member);
+        }
+    }
+
+    /// 随机选一个非空的桶，再从桶中随机选一个成员
+    pub fn random(&self, rng: &mut impl Rng) -> Option<&str> {
+        if self.len == 0 {
+            return None;
+        }
+        loop {
+            let bucket = &self.buckets[rng.random_range(0..self.buckets.len())];
+            if !bucket.is_empty() {
+                return Some(&bucket[rng.random_range(0..bucket.len())]);
+            }
+        }
+    }
+
+    /// 遍历游标所指的一个桶，返回下一个游标，0 表示遍历结束。
+    /// 游标按高位递增，两次调用之间扩容或缩容时，一直存在的成员也不会被漏掉
+    pub fn scan(&self, cursor: u64, mut f: impl FnMut(&str)) -> u64 {
+        let mask = (self.buckets.len() - 1) as u64;
+        for member in &self.buckets[(cursor & mask) as usize] {
+            f(member);
+        }
+        ((cursor | !mask).reverse_bits().wrapping_add(1)).reverse_bits()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.buckets.iter().flatten().map(String::as_str)
+    }
+}
+
+impl FromIterator<String> for Dict {
+    fn from_iter<T: IntoIterator<Item = String>>(iter: T) -> Self {
+        let mut dict = Self::default();
+        for member in iter {
+            dict.insert(member);
+        }
+        dict
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn f1_scan_across_resize() {
+        let mut dict: Dict = (0..100).map(|n| n.to_string()).collect();
+        assert_eq!(dict.len(), 100);
+        assert_eq!(dict.buckets(), 128);
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        for round in 0.. {
+            cursor = dict.scan(cursor, |m| {
+                seen.insert(m.to_string());
+            });
+            if cursor == 0 {
+                break;
+            }
+            // 遍历途中先扩容再缩容
+            match round {
+                10 => (100..1000).for_each(|n| {
+                    dict.insert(n.to_string());
+                }),
+                50 => (100..1000).for_each(|n| {
+                    dict.remove(&n.to_string());
+                }),
+                _ => {}
+            }
+        }
+        assert!((0..100).all(|n| seen.contains(&n.to_string())));
+        assert_eq!(dict.buckets(), 128);
+
+        assert!(dict.remove("5"));
+        assert!(!dict.remove("5"));
+        assert!(!dict.contains("5"));
+        let mut rng = rand::rng();
+        assert!(dict.random(&mut rng).is_some_and(|m| dict.contains(m)));
+        assert_eq!(dict.iter().count(), 99);
+    }
+}
@@ -0,0 +1,566 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+
+use rand::Rng;
+
+use super::dict::Dict;
+use super::listpack::Listpack;
+
+/// 小集合编码的转换阈值，可通过 CONFIG SET 修改
+pub struct EncodingConfig {
+    pub hash_max_listpack_entries: AtomicUsize,
+    pub hash_max_listpack_value: AtomicUsize,
+    pub set_max_intset_entries: AtomicUsize,
+    pub set_max_listpack_entries: AtomicUsize,
+    pub set_max_listpack_value: AtomicUsize,
+    // 正数为元素个数上限，-1..-5 表示 4KB..64KB 的字节上限
+    pub list_max_listpack_size: AtomicI64,
+}
+
+pub static ENCODING_CONFIG: EncodingConfig = EncodingConfig {
+    hash_max_listpack_entries: AtomicUsize::new(128),
+    hash_max_listpack_value: AtomicUsize::new(64),
+    set_max_intset_entries: AtomicUsize::new(512),
+    set_max_listpack_entries: AtomicUsize::new(128),
+    set_max_listpack_value: AtomicUsize::new(64),
+    list_max_listpack_size: AtomicI64::new(-2),
+};
+
+fn limit(value: &AtomicUsize) -> usize {
+    value.load(Ordering::Relaxed)
+}
+
+/// 只有规范形式的十进制整数才能存入 intset
+fn as_intset_member(s: &str) -> Option<i64> {
+    s.parse::<i64>().ok().filter(|n| n.to_string() == s)
+}
+
+#[derive(Debug, Clone)]
+pub enum HashValue {
+    // field 与 value 交替存放
+    Listpack(Listpack),
+    Table(HashMap<String, String>),
+}
+
+impl Default for HashValue {
+    fn default() -> Self {
+        Self::Listpack(Listpack::new())
+    }
+}
+
+impl HashValue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Self::Listpack(_) => "listpack",
+            Self::Table(_) => "hashtable",
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Listpack(lp) => lp.len() / 2,
+            Self::Table(map) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn listpack_index(lp: &Listpack, field: &str) -> Option<usize> {
+        lp.iter().step_by(2).position(|f| f == field).map(|i| i * 2)
+    }
+
+    pub fn get(&self, field: &str) -> Option<&str> {
+        match self {
+            Self::Listpack(lp) => Self::listpack_index(lp, field).and_then(|i| lp.get(i + 1)),
+            Self::Table(map) => map.get(field).map(String::as_str),
+        }
+    }
+
+    pub fn contains_key(&self, field: &str) -> bool {
+        self.get(field).is_some()
+    }
+
+    pub fn insert(&mut self, field: String, value: String) -> Option<String> {
+        if let Self::Listpack(_) = self {
+            let max_value = limit(&ENCODING_CONFIG.hash_max_listpack_value);
+            let max_entries = limit(&ENCODING_CONFIG.hash_max_listpack_entries);
+            if field.len() > max_value
+                || value.len() > max_value
+                || (self.len() >= max_entries && !self.contains_key(&field))
+            {
+                self.convert_to_table();
+            }
+        }
+        match self {
+            Self::Listpack(lp) => match Self::listpack_index(lp, &field) {
+                Some(i) => {
+                    let old = lp.get(i + 1).map(str::to_string);
+                    lp.replace(i + 1, &value);
+                    old
+                }
+                None => {
+                    lp.push_back(&field);
+                    lp.push_back(&value);
+                    None
+                }
+            },
+            Self::Table(map) => map.insert(field, value),
+        }
+    }
+
+    pub fn remove(&mut self, field: &str) -> Option<String> {
+        match self {
+            Self::Listpack(lp) => {
+                let i = Self::listpack_index(lp, field)?;
+                let value = lp.remove(i + 1);
+                lp.remove(i);
+                value
+            }
+            Self::Table(map) => map.remove(field),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (&str, &str)> + '_> {
+        match self {
+            Self::Listpack(lp) => Box::new(lp.iter().step_by(2).zip(lp.iter().skip(1).step_by(2))),
+            Self::Table(map) => Box::new(map.iter().map(|(k, v)| (k.as_str(), v.as_str()))),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    fn convert_to_table(&mut self) {
+        if let Self::Listpack(lp) = self {
+            let map = lp
+                .iter()
+                .step_by(2)
+                .zip(lp.iter().skip(1).step_by(2))
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            *self = Self::Table(map);
+        }
+    }
+}
+
+impl FromIterator<(String, String)> for HashValue {
+    fn from_iter<T: IntoIterator<Item = (String, String)>>(iter: T) -> Self {
+        let mut hash = Self::new();
+        for (k, v) in iter {
+            hash.insert(k, v);
+        }
+        hash
+    }
+}
+
+impl PartialEq for HashValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl Eq for HashValue {}
+
+#[derive(Debug, Clone)]
+pub enum SetValue {
+    // 有序整数数组
+    IntSet(Vec<i64>),
+    Listpack(Listpack),
+    Table(Dict),
+}
+
+impl Default for SetValue {
+    fn default() -> Self {
+        Self::IntSet(Vec::new())
+    }
+}
+
+impl SetValue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Self::IntSet(_) => "intset",
+            Self::Listpack(_) => "listpack",
+            Self::Table(_) => "hashtable",
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::IntSet(ints) => ints.len(),
+            Self::Listpack(lp) => lp.len(),
+            Self::Table(set) => set.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, member: &str) -> bool {
+        match self {
+            Self::IntSet(ints) => {
+                as_intset_member(member).is_some_and(|n| ints.binary_search(&n).is_ok())
+            }
+            Self::Listpack(lp) => lp.position(member).is_some(),
+            Self::Table(set) => set.contains(member),
+        }
+    }
+
+    pub fn insert(&mut self, member: String) -> bool {
+        if self.contains(&member) {
+            return false;
+        }
+        let len = self.len();
+        if let Self::IntSet(ints) = self {
+            match as_intset_member(&member) {
+                Some(n) if len < limit(&ENCODING_CONFIG.set_max_intset_entries) => {
+                    let pos = ints.binary_search(&n).unwrap_err();
+                    ints.insert(pos, n);
+                    return true;
+                }
+                _ => self.convert_to_listpack(),
+            }
+        }
+        if let Self::Listpack(_) = self
+            && (len >= limit(&ENCODING_CONFIG.set_max_listpack_entries)
+                || member.len() > limit(&ENCODING_CONFIG.set_max_listpack_value))
+        {
+            self.convert_to_table();
+        }
+        match self {
+            Self::Listpack(lp) => lp.push_back(&member),
+            Self::Table(set) => {
+                set.insert(member);
+            }
+            Self::IntSet(_) => unreachable!(),
+        }
+        true
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        match self {
+            Self::IntSet(ints) => {
+                match as_intset_member(member).and_then(|n| ints.binary_search(&n).ok()) {
+                    Some(pos) => {
+                        ints.remove(pos);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Self::Listpack(lp) => match lp.position(member) {
+                Some(pos) => {
+                    lp.remove(pos);
+                    true
+                }
+                None => false,
+            },
+            Self::Table(set) => set.remove(member),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = Cow<'_, str>> + '_> {
+        match self {
+            Self::IntSet(ints) => Box::new(ints.iter().map(|n| Cow::Owned(n.to_string()))),
+            Self::Listpack(lp) => Box::new(lp.iter().map(Cow::Borrowed)),
+            Self::Table(set) => Box::new(set.iter().map(Cow::Borrowed)),
+        }
+    }
+
+    /// 随机返回一个成员，intset 与 listpack 按下标选取，哈希表按桶选取
+    pub fn random(&self, rng: &mut impl Rng) -> Option<Cow<'_, str>> {
+        if self.is_empty() {
+            return None;
+        }
+        match self {
+            Self::IntSet(ints) => Some(Cow::Owned(
+                ints[rng.random_range(0..ints.len())].to_string(),
+            )),
+            Self::Listpack(lp) => lp.get(rng.random_range(0..lp.len())).map(Cow::Borrowed),
+            Self::Table(dict) => dict.random(rng).map(Cow::Borrowed),
+        }
+    }
+
+    /// SSCAN 的一次遍历，返回下一个游标。intset 与 listpack 的大小受编码阈值限制，
+    /// 与 Redis 一样一次返回全部成员；哈希表每次遍历若干个桶，直到取得 count 个成员
+    pub fn scan(&self, mut cursor: u64, count: usize, mut f: impl FnMut(Cow<'_, str>)) -> u64 {
+        let Self::Table(dict) = self else {
+            self.iter().for_each(f);
+            return 0;
+        };
+        let mut found = 0;
+        // 连续的空桶最多遍历 count * 10 个
+        let mut budget = count.saturating_mul(10);
+        loop {
+            cursor = dict.scan(cursor, |m| {
+                found += 1;
+                f(Cow::Borrowed(m));
+            });
+            budget = budget.saturating_sub(1);
+            if cursor == 0 || found >= count || budget == 0 {
+                return cursor;
+            }
+        }
+    }
+
+    fn convert_to_listpack(&mut self) {
+        if let Self::IntSet(ints) = self {
+            let mut lp = Listpack::new();
+            for n in ints.iter() {
+                lp.push_back(&n.to_string());
+            }
+            *self = Self::Listpack(lp);
+            // intset 上限可能大于 listpack 上限
+            if self.len() > limit(&ENCODING_CONFIG.set_max_listpack_entries) {
+                self.convert_to_table();
+            }
+        }
+    }
+
+    fn convert_to_table(&mut self) {
+        let set = self.iter().map(Cow::into_owned).collect();
+        *self = Self::Table(set);
+    }
+}
+
+impl FromIterator<String> for SetValue {
+    fn from_iter<T: IntoIterator<Item = String>>(iter: T) -> Self {
+        let mut set = Self::new();
+        for m in iter {
+            set.insert(m);
+        }
+        set
+    }
+}
+
+impl PartialEq for SetValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|m| other.contains(&m))
+    }
+}
+
+impl Eq for SetValue {}
+
+#[derive(Debug, Clone)]
+pub enum ListValue {
+    Listpack(Listpack),
+    // 大列表直接使用 VecDeque，编码名沿用 Redis 的 quicklist
+    Quicklist(VecDeque<String>),
+}
+
+impl Default for ListValue {
+    fn default() -> Self {
+        Self::Listpack(Listpack::new())
+    }
+}
+
+impl ListValue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Self::Listpack(_) => "listpack",
+            Self::Quicklist(_) => "quicklist",
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Listpack(lp) => lp.len(),
+            Self::Quicklist(list) => list.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Listpack(lp) => lp.is_empty(),
+            Self::Quicklist(list) => list.is_empty(),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        match self {
+            Self::Listpack(lp) => lp.get(index),
+            Self::Quicklist(list) => list.get(index).map(String::as_str),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self {
+            Self::Listpack(lp) => Box::new(lp.iter()),
+            Self::Quicklist(list) => Box::new(list.iter().map(String::as_str)),
+        }
+    }
+
+    pub fn position(&self, element: &str) -> Option<usize> {
+        self.iter().position(|e| e == element)
+    }
+
+    /// 新元素加入前检查 listpack 是否超出阈值
+    fn prepare_insert(&mut self, element: &str) {
+        if let Self::Listpack(lp) = self {
+            let size = ENCODING_CONFIG
+                .list_max_listpack_size
+                .load(Ordering::Relaxed);
+            let exceeded = if size > 0 {
+                lp.len() >= size as usize
+            } else {
+                let max_bytes = 4096usize << (size.clamp(-5, -1).unsigned_abs() - 1);
+                lp.bytes() + element.len() > max_bytes
+            };
+            if exceeded {
+                let list = lp.iter().map(str::to_string).collect();
+                *self = Self::Quicklist(list);
+            }
+        }
+    }
+
+    pub fn push_front(&mut self, element: String) {
+        self.prepare_insert(&element);
+        match self {
+            Self::Listpack(lp) => lp.push_front(&element),
+            Self::Quicklist(list) => list.push_front(element),
+        }
+    }
+
+    pub fn push_back(&mut self, element: String) {
+        self.prepare_insert(&element);
+        match self {
+            Self::Listpack(lp) => lp.push_back(&element),
+            Self::Quicklist(list) => list.push_back(element),
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, element: String) {
+        self.prepare_insert(&element);
+        match self {
+            Self::Listpack(lp) => lp.insert(index, &element),
+            Self::Quicklist(list) => list.insert(index, element),
+        }
+    }
+
+    pub fn set(&mut self, index: usize, element: String) {
+        self.prepare_insert(&element);
+        match self {
+            Self::Listpack(lp) => lp.replace(index, &element),
+            Self::Quicklist(list) => list[index] = element,
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<String> {
+        match self {
+            Self::Listpack(lp) => lp.pop_front(),
+            Self::Quicklist(list) => list.pop_front(),
+        }
+    }
+
+    pub fn pop_back(&mut self) -> Option<String> {
+        match self {
+            Self::Listpack(lp) => lp.pop_back(),
+            Self::Quicklist(list) => list.pop_back(),
+        }
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&str) -> bool) {
+        match self {
+            Self::Listpack(lp) => lp.retain(f),
+            Self::Quicklist(list) => list.retain(|e| f(e)),
+        }
+    }
+
+    /// 只保留 [start, end) 范围内的元素
+    pub fn trim(&mut self, start: usize, end: usize) {
+        let mut index = 0;
+        self.retain(|_| {
+            index += 1;
+            (start..end).contains(&(index - 1))
+        });
+    }
+}
+
+impl FromIterator<String> for ListValue {
+    fn from_iter<T: IntoIterator<Item = String>>(iter: T) -> Self {
+        let mut list = Self::new();
+        for e in iter {
+            list.push_back(e);
+        }
+        list
+    }
+}
+
+impl PartialEq for ListValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for ListValue {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_hash_upgrade() {
+        let mut hash = HashValue::new();
+        hash.insert("f1".into(), "v1".into());
+        assert_eq!(hash.encoding(), "listpack");
+        assert_eq!(
+            hash.insert("f1".into(), "v2".into()),
+            Some("v1".to_string())
+        );
+        assert_eq!(hash.get("f1"), Some("v2"));
+        hash.insert("long".into(), "x".repeat(65));
+        assert_eq!(hash.encoding(), "hashtable");
+        assert_eq!(hash.len(), 2);
+        assert_eq!(hash.remove("f1"), Some("v2".to_string()));
+    }
+
+    #[test]
+    fn f2_set_upgrade() {
+        let mut set: SetValue = (0..10).map(|n| n.to_string()).collect();
+        assert_eq!(set.encoding(), "intset");
+        assert!(set.contains("3"));
+        assert!(!set.contains("03"));
+        assert!(set.insert("03".into()));
+        assert_eq!(set.encoding(), "listpack");
+        assert!(set.contains("3") && set.contains("03"));
+        for n in 0..200 {
+            set.insert(format!("m{}", n));
+        }
+        assert_eq!(set.encoding(), "hashtable");
+        assert_eq!(set.len(), 211);
+        assert!(set.remove("3"));
+        assert!(!set.remove("3"));
+    }
+
+    #[test]
+    fn f3_list_upgrade() {
+        let mut list: ListValue = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(list.encoding(), "listpack");
+        list.trim(1, 3);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec!["b", "c"]);
+        list.push_front("x".repeat(9000));
+        assert_eq!(list.encoding(), "quicklist");
+        assert_eq!(list.get(1), Some("b"));
+        assert_eq!(list.pop_front().map(|s| s.len()), Some(9000));
+    }
+}
@@ -0,0 +1,250 @@
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU8, AtomicU64, AtomicUsize, Ordering};
+
+use rand::Rng;
+
+use super::{Database, MAXMEMORY, NotifyClass, now_millis};
+use crate::util::used_memory;
+
+/// 超过 maxmemory 时选择淘汰 key 的策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    NoEviction,
+    AllKeysLru,
+    VolatileLru,
+    AllKeysLfu,
+    VolatileLfu,
+    AllKeysRandom,
+    VolatileRandom,
+    VolatileTtl,
+}
+
+const POLICIES: &[(&str, EvictionPolicy)] = &[
+    ("noeviction", EvictionPolicy::NoEviction),
+    ("allkeys-lru", EvictionPolicy::AllKeysLru),
+    ("volatile-lru", EvictionPolicy::VolatileLru),
+    ("allkeys-lfu", EvictionPolicy::AllKeysLfu),
+    ("volatile-lfu", EvictionPolicy::VolatileLfu),
+    ("allkeys-random", EvictionPolicy::AllKeysRandom),
+    ("volatile-random", EvictionPolicy::VolatileRandom),
+    ("volatile-ttl", EvictionPolicy::VolatileTtl),
+];
+
+impl EvictionPolicy {
+    pub fn name(self) -> &'static str {
+        POLICIES.iter().find(|(_, p)| *p == self).unwrap().0
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        POLICIES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(_, p)| *p)
+    }
+
+    /// LFU 策略下 OBJECT FREQ 可用，否则 OBJECT IDLETIME 可用
+    pub fn is_lfu(self) -> bool {
+        matches!(self, Self::AllKeysLfu | Self::VolatileLfu)
+    }
+
+    /// volatile-* 只淘汰设置了过期时间的 key
+    fn volatile(self) -> bool {
+        matches!(
+            self,
+            Self::VolatileLru | Self::VolatileLfu | Self::VolatileRandom | Self::VolatileTtl
+        )
+    }
+}
+
+/// 淘汰相关配置，可通过 CONFIG SET 修改
+pub struct EvictionConfig {
+    pub policy: RwLock<EvictionPolicy>,
+    /// 每次淘汰随机抽样的 key 数
+    pub samples: AtomicUsize,
+    pub lfu_log_factor: AtomicU64,
+    /// LFU 计数每经过多少分钟减一，0 表示不衰减
+    pub lfu_decay_time: AtomicU64,
+}
+
+pub static EVICTION_CONFIG: EvictionConfig = EvictionConfig {
+    policy: RwLock::new(EvictionPolicy::NoEviction),
+    samples: AtomicUsize::new(5),
+    lfu_log_factor: AtomicU64::new(10),
+    lfu_decay_time: AtomicU64::new(1),
+};
+
+impl EvictionConfig {
+    pub fn policy(&self) -> EvictionPolicy {
+        *self.policy.read().unwrap()
+    }
+
+    pub fn set_policy(&self, value: &str) -> Result<(), String> {
+        let policy = EvictionPolicy::parse(value).ok_or_else(|| {
+            let names: Vec<&str> = POLICIES.iter().map(|(name, _)| *name).collect();
+            format!(
+                "argument(s) must be one of the following: {}",
+                names.join(", ")
+            )
+        })?;
+        *self.policy.write().unwrap() = policy;
+        Ok(())
+    }
+}
+
+/// 新 key 的 LFU 初始计数，避免刚写入就被淘汰
+const LFU_INIT_VAL: u8 = 5;
+
+/// 每个 key 的元信息：访问信息在读取时也会更新，因此使用原子类型
+#[derive(Debug)]
+pub struct KeyMeta {
+    /// 最近一次访问的时间（毫秒）
+    last_access: AtomicU64,
+    /// 对数增长的访问频率计数，最大 255
+    counter: AtomicU8,
+    /// 估算的内存占用，已计入 Database 的 dataset 总量
+    pub size: usize,
+}
+
+impl KeyMeta {
+    pub fn new() -> Self {
+        Self {
+            last_access: AtomicU64::new(now_millis() as u64),
+            counter: AtomicU8::new(LFU_INIT_VAL),
+            size: 0,
+        }
+    }
+
+    /// 记录一次访问：先按空闲时间衰减计数，再以 1/((counter-5)*lfu-log-factor+1) 的概率加一
+    pub fn record(&self) {
+        let mut counter = self.frequency();
+        if counter < u8::MAX {
+            let base = counter.saturating_sub(LFU_INIT_VAL) as f64;
+            let factor = EVICTION_CONFIG.lfu_log_factor.load(Ordering::Relaxed) as f64;
+            if rand::rng().random::<f64>() < 1.0 / (base * factor + 1.0) {
+                counter += 1;
+            }
+        }
+        self.counter.store(counter, Ordering::Relaxed);
+        self.last_access
+            .store(now_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// 距最近一次访问的毫秒数
+    pub fn idle_millis(&self) -> u64 {
+        (now_millis() as u64).saturating_sub(self.last_access.load(Ordering::Relaxed))
+    }
+
+    /// 经过衰减后的访问频率
+    pub fn frequency(&self) -> u8 {
+        let counter = self.counter.load(Ordering::Relaxed);
+        let decay = EVICTION_CONFIG.lfu_decay_time.load(Ordering::Relaxed);
+        if decay == 0 {
+            return counter;
+        }
+        let periods = self.idle_millis() / 60_000 / decay;
+        counter.saturating_sub(periods.min(u8::MAX as u64) as u8)
+    }
+}
+
+impl Database {
+    /// 内存超过 maxmemory 时按策略淘汰 key
+    ///
+    /// 返回淘汰的 key 数；淘汰后仍超过上限（或策略为 noeviction）时返回 Err
+    pub fn free_memory(&mut self) -> Result<usize, usize> {
+        let limit = MAXMEMORY.load(Ordering::Relaxed) as usize;
+        let policy = EVICTION_CONFIG.policy();
+        let samples = EVICTION_CONFIG.samples.load(Ordering::Relaxed).max(1);
+        let mut evicted = 0;
+        while limit > 0 && used_memory() > limit {
+            if policy == EvictionPolicy::NoEviction {
+                return Err(evicted);
+            }
+            let Some(key) = self.eviction_candidate(policy, samples) else {
+                return Err(evicted);
+            };
+            self.touch(&key);
+            self.remove_entry(&key);
+            self.notify(NotifyClass::Evicted, "evicted", &key);
+            evicted += 1;
+        }
+        Ok(evicted)
+    }
+
+    /// 随机抽样 samples 个 key，选出最适合淘汰的一个
+    fn eviction_candidate(&self, policy: EvictionPolicy, samples: usize) -> Option<String> {
+        let index = if policy.volatile() {
+            &self.volatile
+        } else {
+            &self.keys
+        };
+        let sampled = index
+            .sample(samples)
+            .filter_map(|key| self.data.get_key_value(key));
+        // 分数越大越先淘汰
+        let score = |(_, expire, meta): &(_, Option<u128>, KeyMeta)| match policy {
+            EvictionPolicy::AllKeysLru | EvictionPolicy::VolatileLru => meta.idle_millis() as u128,
+            EvictionPolicy::AllKeysLfu | EvictionPolicy::VolatileLfu => {
+                (u8::MAX - meta.frequency()) as u128
+            }
+            EvictionPolicy::VolatileTtl => u128::MAX - expire.unwrap_or(u128::MAX),
+            _ => 0,
+        };
+        sampled
+            .max_by_key(|(_, entry)| score(entry))
+            .map(|(key, _)| key.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::Value;
+    use std::time::Duration;
+
+    fn db_with_keys() -> Database {
+        let mut db = Database::new();
+        for (i, key) in ["a", "b", "c"].into_iter().enumerate() {
+            let ttl = (key != "a").then(|| Duration::from_secs(10 * (4 - i as u64)));
+            db.set_with_duration(key.into(), Value::String("v".into()), ttl);
+            let (_, _, meta) = db.data.get_mut(key).unwrap();
+            // a 最久未访问，c 访问最频繁
+            meta.last_access.store(
+                now_millis() as u64 - 1000 * (3 - i as u64),
+                Ordering::Relaxed,
+            );
+            meta.counter.store(10 * (i as u8 + 1), Ordering::Relaxed);
+        }
+        db
+    }
+
+    #[test]
+    fn f1_eviction_candidate() {
+        let db = db_with_keys();
+        let pick = |policy| db.eviction_candidate(policy, 10);
+        assert_eq!(pick(EvictionPolicy::AllKeysLru).as_deref(), Some("a"));
+        assert_eq!(pick(EvictionPolicy::VolatileLru).as_deref(), Some("b"));
+        assert_eq!(pick(EvictionPolicy::AllKeysLfu).as_deref(), Some("a"));
+        assert_eq!(pick(EvictionPolicy::VolatileTtl).as_deref(), Some("c"));
+        assert_ne!(pick(EvictionPolicy::VolatileRandom).as_deref(), Some("a"));
+
+        let mut db = Database::new();
+        db.set("k".into(), Value::String("v".into()));
+        assert_eq!(db.eviction_candidate(EvictionPolicy::VolatileLru, 5), None);
+        assert_eq!(
+            EvictionPolicy::parse("ALLKEYS-LFU"),
+            Some(EvictionPolicy::AllKeysLfu)
+        );
+        assert_eq!(EvictionPolicy::VolatileTtl.name(), "volatile-ttl");
+    }
+
+    #[test]
+    fn f2_no_touch() {
+        let mut db = db_with_keys();
+        db.no_touch = true;
+        db.get("a");
+        assert!(db.peek("a").unwrap().1.idle_millis() >= 3000);
+        db.no_touch = false;
+        db.get("a");
+        assert!(db.peek("a").unwrap().1.idle_millis() < 1000);
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use rand::seq::index;
+
+/// 支持 O(1) 插入、删除与随机抽样的 key 集合，用于主动过期与淘汰时抽样
+#[derive(Debug, Default)]
+pub struct KeyIndex {
+    keys: Vec<String>,
+    positions: HashMap<String, usize>,
+}
+
+impl KeyIndex {
+    pub fn insert(&mut self, key: &str) {
+        if !self.positions.contains_key(key) {
+            self.positions.insert(key.to_string(), self.keys.len());
+            self.keys.push(key.to_string());
+        }
+    }
+
+    /// 与末尾元素交换后删除
+    pub fn remove(&mut self, key: &str) {
+        let Some(pos) = self.positions.remove(key) else {
+            return;
+        };
+        self.keys.swap_remove(pos);
+        if let Some(moved) = self.keys.get(pos) {
+            self.positions.insert(moved.clone(), pos);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// 不重复地随机抽取最多 n 个 key
+    pub fn sample(&self, n: usize) -> impl Iterator<Item = &String> {
+        let amount = n.min(self.keys.len());
+        index::sample(&mut rand::rng(), self.keys.len(), amount)
+            .into_iter()
+            .map(|i| &self.keys[i])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.keys.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.positions.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_key_index() {
+        let mut index = KeyIndex::default();
+        for key in ["a", "b", "c"] {
+            index.insert(key);
+        }
+        index.insert("a");
+        assert_eq!(index.len(), 3);
+        index.remove("a");
+        index.remove("x");
+        index.remove("c");
+        assert_eq!(index.iter().collect::<Vec<_>>(), ["b"]);
+        index.insert("d");
+        let mut sampled: Vec<&String> = index.sample(10).collect();
+        sampled.sort();
+        assert_eq!(sampled, ["b", "d"]);
+        index.clear();
+        assert_eq!(index.sample(10).count(), 0);
+    }
+}
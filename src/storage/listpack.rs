@@ -0,0 +1,188 @@
+/// 紧凑的字符串序列：所有元素连续存放在同一块内存中，
+/// 每个元素由 LEB128 编码的长度前缀与 UTF-8 内容组成
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Listpack {
+    buf: Vec<u8>,
+    len: usize,
+}
+
+fn encode_len(buf: &mut Vec<u8>, mut n: usize) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+/// 返回 (内容长度, 长度前缀占用的字节数)
+fn decode_len(buf: &[u8]) -> (usize, usize) {
+    let mut n = 0usize;
+    for (i, byte) in buf.iter().enumerate() {
+        n |= ((byte & 0x7f) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            return (n, i + 1);
+        }
+    }
+    unreachable!("corrupted listpack entry")
+}
+
+impl Listpack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 占用的字节数
+    pub fn bytes(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn iter(&self) -> ListpackIter<'_> {
+        ListpackIter {
+            buf: &self.buf,
+            pos: 0,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.iter().nth(index)
+    }
+
+    /// 第 index 个元素的起始偏移，index == len 时返回末尾
+    fn offset_of(&self, index: usize) -> usize {
+        let mut pos = 0;
+        for _ in 0..index {
+            let (n, width) = decode_len(&self.buf[pos..]);
+            pos += width + n;
+        }
+        pos
+    }
+
+    fn entry_end(&self, offset: usize) -> usize {
+        let (n, width) = decode_len(&self.buf[offset..]);
+        offset + width + n
+    }
+
+    pub fn insert(&mut self, index: usize, element: &str) {
+        let offset = self.offset_of(index);
+        let mut entry = Vec::with_capacity(element.len() + 2);
+        encode_len(&mut entry, element.len());
+        entry.extend_from_slice(element.as_bytes());
+        self.buf.splice(offset..offset, entry);
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, element: &str) {
+        encode_len(&mut self.buf, element.len());
+        self.buf.extend_from_slice(element.as_bytes());
+        self.len += 1;
+    }
+
+    pub fn push_front(&mut self, element: &str) {
+        self.insert(0, element);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<String> {
+        if index >= self.len {
+            return None;
+        }
+        let offset = self.offset_of(index);
+        let end = self.entry_end(offset);
+        let (_, width) = decode_len(&self.buf[offset..]);
+        let element = std::str::from_utf8(&self.buf[offset + width..end])
+            .expect("listpack holds valid utf-8")
+            .to_string();
+        self.buf.drain(offset..end);
+        self.len -= 1;
+        Some(element)
+    }
+
+    pub fn replace(&mut self, index: usize, element: &str) {
+        if index < self.len {
+            self.remove(index);
+            self.insert(index, element);
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<String> {
+        self.remove(0)
+    }
+
+    pub fn pop_back(&mut self) -> Option<String> {
+        self.len.checked_sub(1).and_then(|last| self.remove(last))
+    }
+
+    pub fn position(&self, element: &str) -> Option<usize> {
+        self.iter().position(|e| e == element)
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&str) -> bool) {
+        let mut kept = Listpack::new();
+        for e in self.iter() {
+            if f(e) {
+                kept.push_back(e);
+            }
+        }
+        *self = kept;
+    }
+}
+
+pub struct ListpackIter<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Iterator for ListpackIter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.buf.len() {
+            return None;
+        }
+        let (n, width) = decode_len(&self.buf[self.pos..]);
+        let start = self.pos + width;
+        self.pos = start + n;
+        Some(std::str::from_utf8(&self.buf[start..start + n]).expect("listpack holds valid utf-8"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_listpack() {
+        let mut lp = Listpack::new();
+        lp.push_back("b");
+        lp.push_front("a");
+        lp.push_back(&"x".repeat(300));
+        lp.insert(2, "c");
+        assert_eq!(lp.len(), 4);
+        assert_eq!(lp.get(1), Some("b"));
+        assert_eq!(lp.get(2), Some("c"));
+        assert_eq!(lp.get(3).map(str::len), Some(300));
+
+        assert_eq!(lp.pop_back().map(|s| s.len()), Some(300));
+        lp.replace(0, "A");
+        assert_eq!(lp.iter().collect::<Vec<_>>(), vec!["A", "b", "c"]);
+        assert_eq!(lp.position("c"), Some(2));
+
+        lp.retain(|e| e != "b");
+        assert_eq!(lp.pop_front(), Some("A".to_string()));
+        assert_eq!(lp.pop_front(), Some("c".to_string()));
+        assert_eq!(lp.pop_front(), None);
+        assert!(lp.is_empty());
+        assert_eq!(lp.bytes(), 0);
+    }
+}
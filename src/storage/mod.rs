@@ -1,23 +1,87 @@
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+mod blocking;
+mod client;
+mod dict;
+mod encoding;
+mod eviction;
+mod keyindex;
+mod listpack;
+mod notify;
+mod pubsub;
+mod tracking;
+mod usage;
+pub use blocking::{BlockedOp, BlockingRegistry};
+pub use client::Client;
+pub use encoding::{ENCODING_CONFIG, HashValue, ListValue, SetValue};
+pub use eviction::{EVICTION_CONFIG, KeyMeta};
+pub use notify::{NOTIFY_KEYSPACE_EVENTS, NotifyClass, format_notify_flags, parse_notify_flags};
+pub use pubsub::{Outbox, PUBSUB_OUTPUT_LIMIT, PubSub, SubKind};
+pub use tracking::{Tracking, TrackingOptions};
+pub use usage::{USAGE_SAMPLES, key_overhead};
+
+use keyindex::KeyIndex;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::protocol::RespValue;
+
+/// RESP2 客户端通过订阅此频道接收缓存失效消息
+pub const INVALIDATE_CHANNEL: &str = "__redis__:invalidate";
+
+/// 主动过期每轮随机检查的 key 数
+const ACTIVE_EXPIRE_SAMPLES: usize = 20;
+
+/// 内存上限（字节），0 表示不限制
+pub static MAXMEMORY: AtomicU64 = AtomicU64::new(0);
+
+pub fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Value {
     String(String),
-    List(VecDeque<String>),
-    Hash(HashMap<String, String>),
-    Set(HashSet<String>),
+    List(ListValue),
+    Hash(HashValue),
+    Set(SetValue),
+    #[allow(dead_code)]
     Null,
 }
 
+impl Value {
+    /// 与 OBJECT ENCODING 返回的名称保持一致
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            // "+5"、"007" 等无法原样还原的数字不能按整数保存
+            Self::String(s)
+                if s.len() <= 20 && s.parse::<i64>().is_ok_and(|n| n.to_string() == *s) =>
+            {
+                "int"
+            }
+            Self::String(s) if s.len() <= 44 => "embstr",
+            Self::String(_) => "raw",
+            Self::List(list) => list.encoding(),
+            Self::Hash(hash) => hash.encoding(),
+            Self::Set(set) => set.encoding(),
+            Self::Null => "raw",
+        }
+    }
+}
+
+#[allow(clippy::to_string_trait_impl)]
 impl ToString for Value {
     fn to_string(&self) -> String {
         match &self {
             Self::String(s) => s.to_owned(),
-            Self::List(list) => list.iter().cloned().collect::<Vec<_>>().join(" "),
+            Self::List(list) => list.iter().collect::<Vec<_>>().join(" "),
             Self::Hash(hash) => {
                 let mut s = String::new();
-                for (k, v) in hash {
+                for (k, v) in hash.iter() {
                     s.push_str(k);
                     s.push(':');
                     s.push_str(v);
@@ -25,7 +89,7 @@ impl ToString for Value {
                 }
                 s
             }
-            Self::Set(set) => set.iter().cloned().collect::<Vec<_>>().join(" "),
+            Self::Set(set) => set.iter().collect::<Vec<_>>().join(" "),
             Self::Null => "".to_string(),
         }
     }
@@ -33,18 +97,128 @@ impl ToString for Value {
 
 #[derive(Debug)]
 pub struct Database {
-    pub data: HashMap<String, (Value, Option<u128>)>, // (value, expire_time)
+    pub data: HashMap<String, (Value, Option<u128>, KeyMeta)>, // (value, expire_time, meta)
+    // 哈希字段级过期时间: key -> (field -> expire_time)
+    pub field_expires: HashMap<String, HashMap<String, u128>>,
+    // 所有 key 与设置了过期时间的 key，淘汰与主动过期时从中抽样
+    keys: KeyIndex,
+    volatile: KeyIndex,
+    // 含有过期字段的哈希 key
+    volatile_fields: KeyIndex,
+    // 主动过期抽样估算的平均剩余存活毫秒数
+    avg_ttl: u128,
+    pub blocking: BlockingRegistry,
+    pub pubsub: PubSub,
+    pub tracking: Tracking,
+    // 已连接的客户端: client id -> client
+    pub clients: HashMap<u64, Arc<Client>>,
+    // 正在执行命令的客户端，用于 NOLOOP
+    pub current_client: Option<u64>,
+    // 正在执行的命令不更新 key 的访问信息（OBJECT 等内省命令）
+    pub no_touch: bool,
+    // WATCH 使用的 key 版本号: key -> (version, watchers)
+    watched: HashMap<String, (u64, usize)>,
+    next_version: u64,
+    // 所有 key 估算内存占用之和
+    dataset_bytes: usize,
+    // 被修改过、需要重新估算大小的 key
+    dirty_sizes: HashSet<String>,
 }
 
 impl Database {
     pub fn new() -> Self {
         Self {
             data: HashMap::new(),
+            field_expires: HashMap::new(),
+            keys: KeyIndex::default(),
+            volatile: KeyIndex::default(),
+            volatile_fields: KeyIndex::default(),
+            avg_ttl: 0,
+            blocking: BlockingRegistry::default(),
+            pubsub: PubSub::default(),
+            tracking: Tracking::default(),
+            clients: HashMap::new(),
+            current_client: None,
+            no_touch: false,
+            watched: HashMap::new(),
+            next_version: 0,
+            dataset_bytes: 0,
+            dirty_sizes: HashSet::new(),
         }
     }
 
+    /// 标记 key 已被修改，使 WATCH 了该 key 的事务失效，并通知缓存了该 key 的客户端
+    pub fn touch(&mut self, key: &str) {
+        if let Some((version, _)) = self.watched.get_mut(key) {
+            self.next_version += 1;
+            *version = self.next_version;
+        }
+        for id in self.tracking.invalidate(key, self.current_client) {
+            self.send_invalidation(id, Some(key));
+        }
+    }
+
+    /// 向追踪客户端（或其重定向目标）发送失效消息，key 为 None 表示全部失效
+    fn send_invalidation(&self, id: u64, key: Option<&str>) {
+        let Some(options) = self.tracking.options(id) else {
+            return;
+        };
+        let target = options.redirect.unwrap_or(id);
+        let Some(outbox) = self.clients.get(&target).map(|client| &client.outbox) else {
+            // 重定向目标已断开，RESP3 客户端会收到提示
+            if let Some(own) = self.clients.get(&id).map(|client| &client.outbox)
+                && own.protocol() >= 3
+            {
+                own.push(RespValue::Push(vec![
+                    RespValue::BulkString(Some("tracking-redir-broken".to_string())),
+                    RespValue::Integer(target as i64),
+                ]));
+            }
+            return;
+        };
+        let keys = match key {
+            Some(key) => RespValue::Array(vec![RespValue::BulkString(Some(key.to_string()))]),
+            None => RespValue::Null,
+        };
+        if outbox.protocol() >= 3 {
+            outbox.push(RespValue::Push(vec![
+                RespValue::BulkString(Some("invalidate".to_string())),
+                keys,
+            ]));
+        } else if self
+            .pubsub
+            .is_subscribed(SubKind::Channel, INVALIDATE_CHANNEL, target)
+        {
+            outbox.push(RespValue::Push(vec![
+                RespValue::BulkString(Some("message".to_string())),
+                RespValue::BulkString(Some(INVALIDATE_CHANNEL.to_string())),
+                keys,
+            ]));
+        }
+    }
+
+    /// 开始监视 key，返回当前版本号
+    pub fn watch(&mut self, key: &str) -> u64 {
+        let entry = self.watched.entry(key.to_string()).or_insert((0, 0));
+        entry.1 += 1;
+        entry.0
+    }
+
+    pub fn unwatch(&mut self, key: &str) {
+        if let Some((_, watchers)) = self.watched.get_mut(key) {
+            *watchers -= 1;
+            if *watchers == 0 {
+                self.watched.remove(key);
+            }
+        }
+    }
+
+    pub fn version(&self, key: &str) -> u64 {
+        self.watched.get(key).map_or(0, |(version, _)| *version)
+    }
+
     pub fn get(&self, key: &str) -> Option<&Value> {
-        self.data.get(key).and_then(|(value, expire)| {
+        self.data.get(key).and_then(|(value, expire, meta)| {
             if let Some(expire_time) = expire {
                 let now = SystemTime::now()
                     .duration_since(UNIX_EPOCH)
@@ -54,12 +228,20 @@ impl Database {
                     return None;
                 }
             }
+            if self.fields_all_expired(key, value) {
+                return None;
+            }
+            if !self.no_touch {
+                meta.record();
+            }
             Some(value)
         })
     }
 
+    /// 取得可变引用，确实修改了值之后需要调用 modified
     pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
-        self.data.get_mut(key).and_then(|(value, expire)| {
+        self.expire_fields(key);
+        self.data.get_mut(key).and_then(|(value, expire, meta)| {
             if let Some(expire_time) = expire {
                 let now = SystemTime::now()
                     .duration_since(UNIX_EPOCH)
@@ -69,16 +251,96 @@ impl Database {
                     return None;
                 }
             }
+            if !self.no_touch {
+                meta.record();
+            }
             Some(value)
         })
     }
 
+    /// 命令修改了 key 的值：使 WATCH 失效、通知缓存的客户端，并重新估算大小
+    pub fn modified(&mut self, key: &str) {
+        self.touch(key);
+        self.dirty_sizes.insert(key.to_string());
+    }
+
+    /// 发送键空间通知，按 notify-keyspace-events 过滤
+    pub fn notify(&self, class: NotifyClass, event: &str, key: &str) {
+        let (keyspace, keyevent) = notify::notify_targets(class);
+        if keyspace {
+            self.pubsub
+                .publish(&format!("__keyspace@0__:{}", key), event);
+        }
+        if keyevent {
+            self.pubsub
+                .publish(&format!("__keyevent@0__:{}", event), key);
+        }
+    }
+
+    fn insert(&mut self, key: String, value: Value, expire_time: Option<u128>) {
+        self.touch(&key);
+        let is_new = !self.exists(&key);
+        if is_new {
+            self.notify(NotifyClass::New, "new", &key);
+        }
+        // 覆盖已有 key 时保留访问信息，与 Redis 一致
+        let meta = match self.remove_entry(&key) {
+            Some((_, _, meta)) if !is_new => meta,
+            _ => KeyMeta::new(),
+        };
+        meta.record();
+        self.dirty_sizes.insert(key.clone());
+        self.keys.insert(&key);
+        if expire_time.is_some() {
+            self.volatile.insert(&key);
+        }
+        self.data.insert(key, (value, expire_time, meta));
+    }
+
+    /// 删除 key 及其字段过期时间，并从 dataset 总量中扣除它的大小
+    fn remove_entry(&mut self, key: &str) -> Option<(Value, Option<u128>, KeyMeta)> {
+        self.keys.remove(key);
+        self.volatile.remove(key);
+        self.volatile_fields.remove(key);
+        self.field_expires.remove(key);
+        let mut entry = self.data.remove(key)?;
+        self.dataset_bytes -= entry.2.size;
+        entry.2.size = 0;
+        Some(entry)
+    }
+
+    /// 不更新访问信息地读取 key，供 OBJECT、MEMORY 等内省命令使用
+    pub fn peek(&self, key: &str) -> Option<(&Value, &KeyMeta)> {
+        if !self.exists(key) {
+            return None;
+        }
+        self.data.get(key).map(|(value, _, meta)| (value, meta))
+    }
+
+    /// 重新估算被修改过的 key 的大小，在每条命令执行后调用
+    pub fn update_sizes(&mut self) {
+        for key in std::mem::take(&mut self.dirty_sizes) {
+            if let Some((value, _, meta)) = self.data.get_mut(&key) {
+                let size = value.memory_usage(USAGE_SAMPLES) + key_overhead(&key);
+                self.dataset_bytes = self.dataset_bytes - meta.size + size;
+                meta.size = size;
+            }
+        }
+    }
+
+    /// 所有 key 估算的内存占用之和
+    pub fn dataset_bytes(&mut self) -> usize {
+        self.update_sizes();
+        self.dataset_bytes
+    }
+
     pub fn set(&mut self, key: String, value: Value) {
-        self.data.insert(key, (value, None));
+        self.insert(key, value, None);
     }
 
+    #[allow(dead_code)]
     fn set_with_expiretime(&mut self, key: String, value: Value, expire_in: Option<u128>) {
-        self.data.insert(key, (value, expire_in));
+        self.insert(key, value, expire_in);
     }
 
     pub fn set_with_duration(&mut self, key: String, value: Value, duration: Option<Duration>) {
@@ -90,27 +352,129 @@ impl Database {
             now + duration_inner.as_millis()
         });
 
-        self.data.insert(key, (value, expire_time));
+        self.insert(key, value, expire_time);
     }
 
     pub fn del(&mut self, key: &str) -> Option<(Value, Option<u128>)> {
-        self.data.remove(key)
+        // 字段全部过期的哈希先被回收，不计入删除的 key
+        self.expire_fields(key);
+        if self.data.contains_key(key) {
+            self.touch(key);
+        }
+        if self.exists(key) {
+            self.notify(NotifyClass::Generic, "del", key);
+        }
+        self.remove_entry(key)
+            .map(|(value, expire, _)| (value, expire))
+    }
+
+    pub fn clear(&mut self) {
+        let keys: Vec<String> = self
+            .watched
+            .keys()
+            .filter(|k| self.data.contains_key(*k))
+            .cloned()
+            .collect();
+        for k in keys {
+            self.touch(&k);
+        }
+        self.tracking.forget_all();
+        for id in self.tracking.client_ids() {
+            self.send_invalidation(id, None);
+        }
+        self.data.clear();
+        self.field_expires.clear();
+        self.keys.clear();
+        self.volatile.clear();
+        self.volatile_fields.clear();
+        self.avg_ttl = 0;
+        self.dataset_bytes = 0;
+        self.dirty_sizes.clear();
+    }
+
+    pub fn get_field_expire(&self, key: &str, field: &str) -> Option<u128> {
+        self.field_expires
+            .get(key)
+            .and_then(|fields| fields.get(field))
+            .copied()
+    }
+
+    pub fn set_field_expire(&mut self, key: &str, field: &str, expire_time: u128) {
+        self.touch(key);
+        self.volatile_fields.insert(key);
+        self.field_expires
+            .entry(key.to_string())
+            .or_default()
+            .insert(field.to_string(), expire_time);
+    }
+
+    pub fn persist_field(&mut self, key: &str, field: &str) -> bool {
+        let Some(fields) = self.field_expires.get_mut(key) else {
+            return false;
+        };
+        let removed = fields.remove(field).is_some();
+        if fields.is_empty() {
+            self.field_expires.remove(key);
+            self.volatile_fields.remove(key);
+        }
+        if removed {
+            self.touch(key);
+        }
+        removed
+    }
+
+    /// 惰性回收哈希中已过期的字段，字段全部过期时删除整个 key，返回是否有字段过期
+    pub fn expire_fields(&mut self, key: &str) -> bool {
+        let Some(fields) = self.field_expires.get_mut(key) else {
+            return false;
+        };
+        let now = now_millis();
+        let expired: Vec<String> = fields
+            .iter()
+            .filter(|&(_, &t)| t <= now)
+            .map(|(f, _)| f.clone())
+            .collect();
+        if expired.is_empty() {
+            return false;
+        }
+        for f in &expired {
+            fields.remove(f);
+        }
+        if fields.is_empty() {
+            self.field_expires.remove(key);
+            self.volatile_fields.remove(key);
+        }
+        self.touch(key);
+        self.dirty_sizes.insert(key.to_string());
+        if let Some((Value::Hash(hash), _, _)) = self.data.get_mut(key) {
+            for f in &expired {
+                hash.remove(f);
+            }
+            let empty = hash.is_empty();
+            self.notify(NotifyClass::Hash, "hexpired", key);
+            if empty {
+                self.remove_entry(key);
+                self.notify(NotifyClass::Generic, "del", key);
+            }
+        }
+        true
     }
 
+    #[allow(dead_code)]
     fn exists_include_expired(&self, key: &str) -> bool {
         self.data.contains_key(key)
     }
 
     pub fn exists(&self, key: &str) -> bool {
-        if let Some((_, expire_time)) = self.data.get(key) {
+        if let Some((value, expire_time, _)) = self.data.get(key) {
             match expire_time {
-                None => true,
+                None => !self.fields_all_expired(key, value),
                 &Some(time) => {
                     let now_time = SystemTime::now()
                         .duration_since(UNIX_EPOCH)
                         .unwrap()
                         .as_millis();
-                    time > now_time
+                    time > now_time && !self.fields_all_expired(key, value)
                 }
             }
         } else {
@@ -118,29 +482,109 @@ impl Database {
         }
     }
 
-    pub fn clean_expired(&mut self) {
-        let now_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
-        // 直接函数式清理
+    /// 哈希的字段是否已全部过期，只读的查找据此把它视为不存在，直到被回收
+    fn fields_all_expired(&self, key: &str, value: &Value) -> bool {
+        let (Value::Hash(hash), Some(fields)) = (value, self.field_expires.get(key)) else {
+            return false;
+        };
+        let now = now_millis();
+        fields.len() >= hash.len() && fields.values().all(|&t| t <= now)
+    }
+
+    fn expire_key(&mut self, key: &str) {
+        self.touch(key);
+        self.remove_entry(key);
+        self.notify(NotifyClass::Expired, "expired", key);
+    }
+
+    fn is_expired(&self, key: &str, now: u128) -> bool {
         self.data
-            .retain(|_, (_, t)| t.is_none_or(|time| time > now_time));
-        /* 另一种方法：先收集再清理
-        let mut tmp_vec = Vec::new();
-        for (key, (_, t)) in &self.data {
-            if let Some(expire_time) = t {
-                if now_time > *expire_time {
-                    tmp_vec.push(key.clone());
+            .get(key)
+            .is_some_and(|(_, t, _)| t.is_some_and(|time| time <= now))
+    }
+
+    /// 清理所有已过期的 key 与哈希字段，返回过期的 key 数
+    pub fn clean_expired(&mut self) -> usize {
+        let now_time = now_millis();
+        // 先收集再清理，以便为每个 key 发送过期通知
+        let expired: Vec<String> = self
+            .volatile
+            .iter()
+            .filter(|k| self.is_expired(k, now_time))
+            .cloned()
+            .collect();
+        let count = expired.len();
+        for k in expired {
+            self.expire_key(&k);
+        }
+        let keys: Vec<String> = self.volatile_fields.iter().cloned().collect();
+        for k in keys {
+            self.expire_fields(&k);
+        }
+        count
+    }
+
+    /// 主动过期：每轮随机检查 ACTIVE_EXPIRE_SAMPLES 个带过期时间的 key（及含过期字段的哈希），
+    /// 过期比例超过 25% 时在时间预算内继续下一轮，返回过期的 key 数
+    pub fn active_expire_cycle(&mut self, budget: Duration) -> usize {
+        let start = Instant::now();
+        let mut count = 0;
+        loop {
+            let now = now_millis();
+            let keys: Vec<String> = self
+                .volatile
+                .sample(ACTIVE_EXPIRE_SAMPLES)
+                .cloned()
+                .collect();
+            let sampled = keys.len();
+            let mut expired = 0;
+            let (mut ttl_sum, mut ttl_samples) = (0, 0);
+            for key in keys {
+                if self.is_expired(&key, now) {
+                    self.expire_key(&key);
+                    expired += 1;
+                } else if let Some((_, Some(time), _)) = self.data.get(&key) {
+                    ttl_sum += time - now;
+                    ttl_samples += 1;
                 }
             }
+            // 与 Redis 一样，新样本的平均值只占 2% 的权重
+            if let Some(avg) = ttl_sum.checked_div(ttl_samples) {
+                self.avg_ttl = match self.avg_ttl {
+                    0 => avg,
+                    old => old / 50 * 49 + avg / 50,
+                };
+            }
+            let keys: Vec<String> = self
+                .volatile_fields
+                .sample(ACTIVE_EXPIRE_SAMPLES)
+                .cloned()
+                .collect();
+            let fields_sampled = keys.len();
+            let mut fields_expired = 0;
+            for key in keys {
+                if self.expire_fields(&key) {
+                    fields_expired += 1;
+                }
+            }
+            count += expired;
+            let repeat = expired * 4 > sampled || fields_expired * 4 > fields_sampled;
+            if !repeat || start.elapsed() >= budget {
+                return count;
+            }
         }
-        let num = tmp_vec.len();
-        for k in tmp_vec {
-            self.data.remove(&k);
-        }*/
     }
 
+    /// INFO keyspace 使用：(key 数, 设置了过期时间的 key 数, 平均剩余存活毫秒数)
+    ///
+    /// 平均存活时间由主动过期时抽样估算，没有带过期时间的 key 时为 0
+    pub fn keyspace_stats(&self) -> (usize, usize, u128) {
+        let expires = self.volatile.len();
+        let avg_ttl = if expires == 0 { 0 } else { self.avg_ttl };
+        (self.data.len(), expires, avg_ttl)
+    }
+
+    #[allow(dead_code)]
     fn len_include_expired(&self) -> usize {
         self.data.len()
     }
@@ -194,19 +638,130 @@ mod test {
         let val = Value::String("".into());
         let duration = Some(Duration::from_millis(100));
         db.set("k1".into(), val.clone());
-        db.set_with_duration("k2".into(), val.clone(), duration.clone());
-        db.set_with_duration("k3".into(), val.clone(), duration.clone());
+        db.set_with_duration("k2".into(), val.clone(), duration);
+        db.set_with_duration("k3".into(), val.clone(), duration);
         assert_eq!(db.len_include_expired(), 3);
         sleep(Duration::from_millis(200));
         assert_eq!(db.len_include_expired(), 3);
-        assert_eq!(db.exists("k2"), false);
-        assert_eq!(db.exists_include_expired("k2"), true);
+        assert!(!db.exists("k2"));
+        assert!(db.exists_include_expired("k2"));
         db.clean_expired();
-        assert_eq!(db.exists_include_expired("k2"), false);
+        assert!(!db.exists_include_expired("k2"));
         assert_eq!(db.len_include_expired(), 1);
 
         assert_eq!(db.del("k2"), None);
         assert!(db.del("k1").is_some());
         assert_eq!(db.len_include_expired(), 0);
     }
+
+    #[test]
+    fn f4_hash_field_expire() {
+        let mut db = Database::new();
+        let hash = HashValue::from_iter([
+            ("f1".to_string(), "v1".to_string()),
+            ("f2".to_string(), "v2".to_string()),
+        ]);
+        db.set("h".into(), Value::Hash(hash));
+        db.set_field_expire("h", "f1", now_millis() + 100);
+        assert!(db.get_field_expire("h", "f1").is_some());
+        assert!(db.get_field_expire("h", "f2").is_none());
+
+        sleep(Duration::from_millis(200));
+        db.expire_fields("h");
+        match db.get("h") {
+            Some(Value::Hash(hash)) => {
+                assert!(!hash.contains_key("f1"));
+                assert!(hash.contains_key("f2"));
+            }
+            _ => panic!("hash should still exist"),
+        }
+        assert!(db.field_expires.is_empty());
+
+        // 最后一个字段过期后整个 key 被删除
+        db.set_field_expire("h", "f2", now_millis());
+        db.clean_expired();
+        assert!(!db.exists("h"));
+        assert!(db.field_expires.is_empty());
+
+        // 查找 key 时即可发现字段全部过期，不必等待主动过期
+        let hash = HashValue::from_iter([("f".to_string(), "v".to_string())]);
+        for key in ["a", "b", "c"] {
+            db.set(key.into(), Value::Hash(hash.clone()));
+            db.set_field_expire(key, "f", now_millis());
+        }
+        sleep(Duration::from_millis(5));
+        assert!(!db.exists("a"));
+        assert_eq!(db.get("a"), None);
+        assert!(db.peek("a").is_none());
+        assert_eq!(db.get_mut("b"), None);
+        assert!(!db.exists_include_expired("b"));
+        assert_eq!(db.del("c"), None);
+        assert!(!db.exists_include_expired("c"));
+        assert!(db.volatile_fields.iter().all(|k| k == "a"));
+    }
+
+    #[test]
+    fn f5_watch_version() {
+        let mut db = Database::new();
+        let v0 = db.watch("k");
+        db.set("other".into(), Value::String("1".into()));
+        assert_eq!(db.version("k"), v0);
+
+        db.set("k".into(), Value::String("1".into()));
+        let v1 = db.version("k");
+        assert_ne!(v1, v0);
+        // 只取得可变引用而没有修改时版本号不变
+        db.get_mut("k");
+        assert_eq!(db.version("k"), v1);
+        db.modified("k");
+        assert_ne!(db.version("k"), v1);
+
+        // 过期与 FLUSHDB 同样会修改版本号
+        let v2 = db.version("k");
+        db.set_with_duration("k".into(), Value::String("1".into()), Some(Duration::ZERO));
+        let v3 = db.version("k");
+        assert_ne!(v3, v2);
+        sleep(Duration::from_millis(5));
+        db.clean_expired();
+        assert_ne!(db.version("k"), v3);
+        db.set("k".into(), Value::String("1".into()));
+        let v4 = db.version("k");
+        db.clear();
+        assert_ne!(db.version("k"), v4);
+
+        db.unwatch("k");
+        assert_eq!(db.version("k"), 0);
+    }
+
+    #[test]
+    fn f6_active_expire_cycle() {
+        let mut db = Database::new();
+        let val = Value::String("".into());
+        for i in 0..100 {
+            db.set(format!("p{}", i), val.clone());
+            db.set_with_duration(format!("v{}", i), val.clone(), Some(Duration::ZERO));
+        }
+        db.set_with_duration("live".into(), val.clone(), Some(Duration::from_secs(100)));
+        sleep(Duration::from_millis(5));
+        // 过期比例一直很高，会持续抽样直到只剩未过期的 key
+        assert_eq!(db.active_expire_cycle(Duration::from_secs(10)), 100);
+        assert_eq!(db.len_include_expired(), 101);
+        assert_eq!(db.volatile.len(), 1);
+        assert_eq!(db.active_expire_cycle(Duration::from_secs(10)), 0);
+        let (keys, expires, avg_ttl) = db.keyspace_stats();
+        assert_eq!((keys, expires), (101, 1));
+        assert!(avg_ttl > 90_000 && avg_ttl <= 100_000);
+
+        db.del("live");
+        assert_eq!(db.keyspace_stats(), (100, 0, 0));
+    }
+
+    #[test]
+    fn f7_string_encoding() {
+        let encoding = |s: &str| Value::String(s.into()).encoding();
+        assert_eq!(encoding("-123"), "int");
+        assert_eq!(encoding("+5"), "embstr");
+        assert_eq!(encoding("007"), "embstr");
+        assert_eq!(encoding(&"x".repeat(45)), "raw");
+    }
 }
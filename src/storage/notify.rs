@@ -0,0 +1,132 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// 键空间通知的事件类别，与 notify-keyspace-events 中的字符一一对应
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyClass {
+    Generic,
+    String,
+    List,
+    Set,
+    Hash,
+    Expired,
+    Evicted,
+    New,
+}
+
+impl NotifyClass {
+    fn flag(self) -> u32 {
+        match self {
+            Self::Generic => GENERIC,
+            Self::String => STRING,
+            Self::List => LIST,
+            Self::Set => SET,
+            Self::Hash => HASH,
+            Self::Expired => EXPIRED,
+            Self::Evicted => EVICTED,
+            Self::New => NEW,
+        }
+    }
+}
+
+const KEYSPACE: u32 = 1 << 0;
+const KEYEVENT: u32 = 1 << 1;
+const GENERIC: u32 = 1 << 2;
+const STRING: u32 = 1 << 3;
+const LIST: u32 = 1 << 4;
+const SET: u32 = 1 << 5;
+const HASH: u32 = 1 << 6;
+const ZSET: u32 = 1 << 7;
+const EXPIRED: u32 = 1 << 8;
+const EVICTED: u32 = 1 << 9;
+const STREAM: u32 = 1 << 10;
+const KEY_MISS: u32 = 1 << 11;
+const NEW: u32 = 1 << 12;
+// A 是 g$lshzxet 的别名，不包含 m 与 n
+const ALL: u32 = GENERIC | STRING | LIST | SET | HASH | ZSET | EXPIRED | EVICTED | STREAM;
+
+const FLAG_CHARS: &[(char, u32)] = &[
+    ('g', GENERIC),
+    ('$', STRING),
+    ('l', LIST),
+    ('s', SET),
+    ('h', HASH),
+    ('z', ZSET),
+    ('x', EXPIRED),
+    ('e', EVICTED),
+    ('t', STREAM),
+    ('m', KEY_MISS),
+    ('n', NEW),
+];
+
+/// 当前生效的 notify-keyspace-events，默认关闭
+pub static NOTIFY_KEYSPACE_EVENTS: AtomicU32 = AtomicU32::new(0);
+
+pub fn parse_notify_flags(s: &str) -> Option<u32> {
+    let mut flags = 0;
+    for c in s.chars() {
+        flags |= match c {
+            'A' => ALL,
+            'K' => KEYSPACE,
+            'E' => KEYEVENT,
+            _ => FLAG_CHARS.iter().find(|(ch, _)| *ch == c)?.1,
+        };
+    }
+    // 既没有 K 也没有 E 时不会发送任何通知
+    if flags & (KEYSPACE | KEYEVENT) == 0 {
+        flags = 0;
+    }
+    Some(flags)
+}
+
+pub fn format_notify_flags(flags: u32) -> String {
+    let mut s = String::new();
+    if flags & ALL == ALL {
+        s.push('A');
+    } else {
+        for (c, flag) in FLAG_CHARS {
+            if flags & flag != 0 && flag & ALL != 0 {
+                s.push(*c);
+            }
+        }
+    }
+    for (c, flag) in FLAG_CHARS {
+        if flags & flag != 0 && flag & ALL == 0 {
+            s.push(*c);
+        }
+    }
+    if flags & KEYSPACE != 0 {
+        s.push('K');
+    }
+    if flags & KEYEVENT != 0 {
+        s.push('E');
+    }
+    s
+}
+
+/// 返回 (是否发送 keyspace 通知, 是否发送 keyevent 通知)
+pub fn notify_targets(class: NotifyClass) -> (bool, bool) {
+    let flags = NOTIFY_KEYSPACE_EVENTS.load(Ordering::Relaxed);
+    if flags & class.flag() == 0 {
+        return (false, false);
+    }
+    (flags & KEYSPACE != 0, flags & KEYEVENT != 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_notify_flags() {
+        assert_eq!(parse_notify_flags(""), Some(0));
+        assert_eq!(parse_notify_flags("g$"), Some(0));
+        assert_eq!(parse_notify_flags("Q"), None);
+
+        let flags = parse_notify_flags("KEA").unwrap();
+        assert_eq!(format_notify_flags(flags), "AKE");
+        let flags = parse_notify_flags("Exl").unwrap();
+        assert_eq!(format_notify_flags(flags), "lxE");
+        let flags = parse_notify_flags("KAn").unwrap();
+        assert_eq!(format_notify_flags(flags), "AnK");
+    }
+}
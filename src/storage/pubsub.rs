@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, AtomicUsize, Ordering};
+
+use tokio::sync::{Notify, mpsc};
+
+use super::now_millis;
+use crate::protocol::{RespParser, RespValue};
+use crate::util::glob_match;
+
+/// pubsub 客户端的输出缓冲区限制，对应 client-output-buffer-limit 的 pubsub 类别，0 表示不限制
+pub struct OutputBufferLimit {
+    pub hard: AtomicUsize,
+    pub soft: AtomicUsize,
+    pub soft_seconds: AtomicU64,
+}
+
+pub static PUBSUB_OUTPUT_LIMIT: OutputBufferLimit = OutputBufferLimit {
+    hard: AtomicUsize::new(32 * 1024 * 1024),
+    soft: AtomicUsize::new(8 * 1024 * 1024),
+    soft_seconds: AtomicU64::new(60),
+};
+
+/// 服务端主动推送给某个连接的消息队列，消息入队时即按连接的协议版本序列化
+#[derive(Debug)]
+pub struct Outbox {
+    tx: mpsc::UnboundedSender<Vec<u8>>,
+    // 连接使用的 RESP 协议版本，由 HELLO 切换
+    protocol: AtomicU8,
+    // 已入队但尚未写出到 socket 的字节数
+    pending: AtomicUsize,
+    // 开始超过软限制的时间（毫秒），0 表示未超过
+    soft_since: AtomicU64,
+    killed: AtomicBool,
+    closed: AtomicBool,
+    kill: Notify,
+}
+
+impl Outbox {
+    pub fn new() -> (Arc<Self>, mpsc::UnboundedReceiver<Vec<u8>>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let outbox = Self {
+            tx,
+            protocol: AtomicU8::new(2),
+            pending: AtomicUsize::new(0),
+            soft_since: AtomicU64::new(0),
+            killed: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            kill: Notify::new(),
+        };
+        (Arc::new(outbox), rx)
+    }
+
+    pub fn protocol(&self) -> u8 {
+        self.protocol.load(Ordering::Relaxed)
+    }
+
+    pub fn set_protocol(&self, protocol: u8) {
+        self.protocol.store(protocol, Ordering::Relaxed);
+    }
+
+    /// 入队一条消息，超出输出缓冲区限制时断开该连接并返回 false
+    pub fn push(&self, message: RespValue) -> bool {
+        if self.is_killed() || self.is_closed() {
+            return false;
+        }
+        let bytes = if self.protocol() >= 3 {
+            RespParser::serializer(message)
+        } else {
+            RespParser::serializer(message.into_resp2())
+        };
+        let size = bytes.len();
+        let pending = self.pending.fetch_add(size, Ordering::Relaxed) + size;
+        if self.over_limit(pending) {
+            self.kill();
+            return false;
+        }
+        self.tx.send(bytes).is_ok()
+    }
+
+    fn over_limit(&self, pending: usize) -> bool {
+        let hard = PUBSUB_OUTPUT_LIMIT.hard.load(Ordering::Relaxed);
+        if hard > 0 && pending > hard {
+            return true;
+        }
+        let soft = PUBSUB_OUTPUT_LIMIT.soft.load(Ordering::Relaxed);
+        if soft == 0 || pending <= soft {
+            self.soft_since.store(0, Ordering::Relaxed);
+            return false;
+        }
+        let now = now_millis() as u64;
+        match self
+            .soft_since
+            .compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed)
+        {
+            Ok(_) => false,
+            Err(since) => {
+                now - since >= PUBSUB_OUTPUT_LIMIT.soft_seconds.load(Ordering::Relaxed) * 1000
+            }
+        }
+    }
+
+    /// 已入队但尚未写出的字节数
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// 连接把消息写出后归还占用的字节数
+    pub fn written(&self, size: usize) {
+        self.pending.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    pub fn kill(&self) {
+        self.killed.store(true, Ordering::Relaxed);
+        self.kill.notify_one();
+    }
+
+    /// 输出缓冲区超限而被断开
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::Relaxed)
+    }
+
+    /// CLIENT KILL 等主动断开连接，不视为错误
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.kill.notify_one();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+
+    /// 等待连接被断开（超限或被关闭）
+    pub async fn killed(&self) {
+        self.kill.notified().await
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubKind {
+    Channel,
+    Pattern,
+    // 分片频道，SSUBSCRIBE / SPUBLISH 使用
+    Shard,
+}
+
+/// 频道、模式与分片频道的订阅表：名称 -> (客户端 id -> 推送队列)
+#[derive(Debug, Default)]
+pub struct PubSub {
+    channels: HashMap<String, HashMap<u64, Arc<Outbox>>>,
+    patterns: HashMap<String, HashMap<u64, Arc<Outbox>>>,
+    shard_channels: HashMap<String, HashMap<u64, Arc<Outbox>>>,
+}
+
+impl PubSub {
+    fn table(&mut self, kind: SubKind) -> &mut HashMap<String, HashMap<u64, Arc<Outbox>>> {
+        match kind {
+            SubKind::Channel => &mut self.channels,
+            SubKind::Pattern => &mut self.patterns,
+            SubKind::Shard => &mut self.shard_channels,
+        }
+    }
+
+    pub fn subscribe(&mut self, kind: SubKind, name: &str, id: u64, outbox: Arc<Outbox>) {
+        self.table(kind)
+            .entry(name.to_string())
+            .or_default()
+            .insert(id, outbox);
+    }
+
+    pub fn unsubscribe(&mut self, kind: SubKind, name: &str, id: u64) {
+        let table = self.table(kind);
+        if let Some(subscribers) = table.get_mut(name) {
+            subscribers.remove(&id);
+            if subscribers.is_empty() {
+                table.remove(name);
+            }
+        }
+    }
+
+    /// 向频道及匹配的模式订阅者推送消息，返回接收到消息的客户端数
+    pub fn publish(&self, channel: &str, message: &str) -> usize {
+        let bulk = |s: &str| RespValue::BulkString(Some(s.to_string()));
+        let mut receivers = 0;
+        if let Some(subscribers) = self.channels.get(channel) {
+            for outbox in subscribers.values() {
+                let push = RespValue::Push(vec![bulk("message"), bulk(channel), bulk(message)]);
+                if outbox.push(push) {
+                    receivers += 1;
+                }
+            }
+        }
+        for (pattern, subscribers) in &self.patterns {
+            if !glob_match(pattern, channel) {
+                continue;
+            }
+            for outbox in subscribers.values() {
+                let push = RespValue::Push(vec![
+                    bulk("pmessage"),
+                    bulk(pattern),
+                    bulk(channel),
+                    bulk(message),
+                ]);
+                if outbox.push(push) {
+                    receivers += 1;
+                }
+            }
+        }
+        receivers
+    }
+
+    /// 向分片频道的订阅者推送消息，分片频道不参与模式匹配
+    pub fn spublish(&self, channel: &str, message: &str) -> usize {
+        let bulk = |s: &str| RespValue::BulkString(Some(s.to_string()));
+        self.shard_channels.get(channel).map_or(0, |subscribers| {
+            subscribers
+                .values()
+                .filter(|outbox| {
+                    outbox.push(RespValue::Push(vec![
+                        bulk("smessage"),
+                        bulk(channel),
+                        bulk(message),
+                    ]))
+                })
+                .count()
+        })
+    }
+
+    pub fn is_subscribed(&self, kind: SubKind, name: &str, id: u64) -> bool {
+        let table = match kind {
+            SubKind::Channel => &self.channels,
+            SubKind::Pattern => &self.patterns,
+            SubKind::Shard => &self.shard_channels,
+        };
+        table
+            .get(name)
+            .is_some_and(|subscribers| subscribers.contains_key(&id))
+    }
+
+    /// 至少有一个订阅者的频道，kind 为 Channel 或 Shard
+    pub fn channels(&self, kind: SubKind, pattern: Option<&str>) -> Vec<String> {
+        let table = match kind {
+            SubKind::Shard => &self.shard_channels,
+            _ => &self.channels,
+        };
+        table
+            .keys()
+            .filter(|channel| pattern.is_none_or(|p| glob_match(p, channel)))
+            .cloned()
+            .collect()
+    }
+
+    pub fn numsub(&self, kind: SubKind, channel: &str) -> usize {
+        let table = match kind {
+            SubKind::Shard => &self.shard_channels,
+            _ => &self.channels,
+        };
+        table.get(channel).map_or(0, HashMap::len)
+    }
+
+    pub fn numpat(&self) -> usize {
+        self.patterns.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_publish() {
+        let mut pubsub = PubSub::default();
+        let (a, mut rx_a) = Outbox::new();
+        let (b, mut rx_b) = Outbox::new();
+        pubsub.subscribe(SubKind::Channel, "news", 1, a.clone());
+        pubsub.subscribe(SubKind::Pattern, "n*", 1, a.clone());
+        pubsub.subscribe(SubKind::Pattern, "n*", 2, b.clone());
+        assert_eq!(pubsub.numsub(SubKind::Channel, "news"), 1);
+        assert_eq!(pubsub.numpat(), 1);
+
+        assert_eq!(pubsub.publish("news", "hi"), 3);
+        assert!(rx_a.try_recv().is_ok());
+        assert!(rx_a.try_recv().is_ok());
+        assert!(rx_b.try_recv().is_ok());
+        assert_eq!(pubsub.publish("other", "hi"), 0);
+
+        pubsub.unsubscribe(SubKind::Channel, "news", 1);
+        assert!(pubsub.channels(SubKind::Channel, None).is_empty());
+
+        // 分片频道与普通频道互不影响
+        pubsub.subscribe(SubKind::Shard, "news", 2, b.clone());
+        assert_eq!(pubsub.spublish("news", "hi"), 1);
+        assert_eq!(pubsub.publish("news", "hi"), 2);
+        assert_eq!(pubsub.numsub(SubKind::Channel, "news"), 0);
+        assert_eq!(
+            pubsub.channels(SubKind::Shard, None),
+            vec!["news".to_string()]
+        );
+    }
+
+    #[test]
+    fn f2_output_buffer_limit() {
+        let (outbox, _rx) = Outbox::new();
+        let big = RespValue::BulkString(Some("x".repeat(20 * 1024 * 1024)));
+        assert!(outbox.push(big.clone()));
+        // 第二条消息使积压超过硬限制
+        assert!(!outbox.push(big));
+        assert!(outbox.is_killed());
+    }
+
+    #[test]
+    fn f3_spublish() {
+        let mut pubsub = PubSub::default();
+        let (a, mut rx_a) = Outbox::new();
+        let (b, mut rx_b) = Outbox::new();
+        let (c, mut rx_c) = Outbox::new();
+        pubsub.subscribe(SubKind::Channel, "news", 1, a.clone());
+        pubsub.subscribe(SubKind::Pattern, "*", 1, a.clone());
+        pubsub.subscribe(SubKind::Shard, "news", 2, b.clone());
+        pubsub.subscribe(SubKind::Shard, "news", 3, c.clone());
+        c.set_protocol(3);
+
+        assert_eq!(pubsub.spublish("news", "hi"), 2);
+        assert_eq!(
+            rx_b.try_recv().unwrap(),
+            b"*3\r\n$8\r\nsmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n"
+        );
+        assert_eq!(
+            rx_c.try_recv().unwrap(),
+            b">3\r\n$8\r\nsmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n"
+        );
+        // 普通频道与模式订阅者收不到分片消息
+        assert!(rx_a.try_recv().is_err());
+        assert_eq!(pubsub.spublish("other", "hi"), 0);
+
+        pubsub.unsubscribe(SubKind::Shard, "news", 2);
+        assert_eq!(pubsub.spublish("news", "hi"), 1);
+        assert!(rx_b.try_recv().is_err());
+        assert!(rx_c.try_recv().is_ok());
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::{HashMap, HashSet};
+
+/// CLIENT TRACKING 的选项
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackingOptions {
+    // 失效消息改为发送给该客户端
+    pub redirect: Option<u64>,
+    // 广播模式：不记录读取的 key，按前缀通知所有修改
+    pub bcast: bool,
+    pub prefixes: Vec<String>,
+    pub optin: bool,
+    pub optout: bool,
+    // 不通知客户端自己修改的 key
+    pub noloop: bool,
+}
+
+/// 客户端缓存的失效追踪表
+#[derive(Debug, Default)]
+pub struct Tracking {
+    clients: HashMap<u64, TrackingOptions>,
+    // 默认模式下 key -> 可能缓存了该 key 的客户端
+    keys: HashMap<String, HashSet<u64>>,
+}
+
+impl Tracking {
+    pub fn enable(&mut self, id: u64, options: TrackingOptions) {
+        self.clients.insert(id, options);
+    }
+
+    pub fn disable(&mut self, id: u64) {
+        if self.clients.remove(&id).is_some() {
+            self.keys.retain(|_, ids| {
+                ids.remove(&id);
+                !ids.is_empty()
+            });
+        }
+    }
+
+    pub fn options(&self, id: u64) -> Option<&TrackingOptions> {
+        self.clients.get(&id)
+    }
+
+    pub fn client_ids(&self) -> Vec<u64> {
+        self.clients.keys().copied().collect()
+    }
+
+    /// 记录客户端读取过的 key
+    pub fn remember(&mut self, id: u64, key: String) {
+        self.keys.entry(key).or_default().insert(id);
+    }
+
+    /// key 被修改时返回需要通知的客户端，默认模式下的记录随之清除
+    pub fn invalidate(&mut self, key: &str, current: Option<u64>) -> Vec<u64> {
+        if self.clients.is_empty() {
+            return Vec::new();
+        }
+        let skip = |id: u64, options: &TrackingOptions| options.noloop && current == Some(id);
+        let mut targets: Vec<u64> = self
+            .keys
+            .remove(key)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|id| self.clients.get(id).is_some_and(|o| !skip(*id, o)))
+            .collect();
+        for (&id, options) in &self.clients {
+            if options.bcast
+                && !skip(id, options)
+                && (options.prefixes.is_empty()
+                    || options.prefixes.iter().any(|p| key.starts_with(p.as_str())))
+                && !targets.contains(&id)
+            {
+                targets.push(id);
+            }
+        }
+        targets
+    }
+
+    /// FLUSHDB 之后所有记录都失效
+    pub fn forget_all(&mut self) {
+        self.keys.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_invalidate() {
+        let mut tracking = Tracking::default();
+        tracking.enable(1, TrackingOptions::default());
+        tracking.enable(
+            2,
+            TrackingOptions {
+                bcast: true,
+                prefixes: vec!["user:".into()],
+                noloop: true,
+                ..Default::default()
+            },
+        );
+        tracking.remember(1, "user:1".into());
+
+        let mut targets = tracking.invalidate("user:1", None);
+        targets.sort();
+        assert_eq!(targets, vec![1, 2]);
+        // 默认模式通知一次后需要重新读取才会再次追踪
+        assert_eq!(tracking.invalidate("user:1", None), vec![2]);
+        // NOLOOP 不通知自己的修改
+        assert!(tracking.invalidate("user:1", Some(2)).is_empty());
+        assert!(tracking.invalidate("order:1", None).is_empty());
+
+        tracking.remember(1, "k".into());
+        tracking.disable(1);
+        assert!(tracking.invalidate("k", None).is_empty());
+    }
+}
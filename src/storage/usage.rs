@@ -0,0 +1,96 @@
+use std::collections::{HashMap, VecDeque};
+use std::mem::size_of;
+
+use super::dict::Dict;
+use super::{HashValue, KeyMeta, ListValue, SetValue, Value};
+
+/// 跟踪 dataset 大小与 MEMORY USAGE 默认抽样的元素个数
+pub const USAGE_SAMPLES: usize = 5;
+
+const STRING: usize = size_of::<String>();
+
+/// 按前 samples 个元素的平均大小估算全部元素，samples 为 0 时逐个累加
+fn sampled(sizes: impl Iterator<Item = usize>, len: usize, samples: usize) -> usize {
+    if samples == 0 {
+        return sizes.sum();
+    }
+    let (count, total) = sizes
+        .take(samples)
+        .fold((0, 0), |(count, total), size| (count + 1, total + size));
+    (total * len).checked_div(count).unwrap_or(0)
+}
+
+/// key 本身与哈希表中一个条目的开销
+pub fn key_overhead(key: &str) -> usize {
+    size_of::<(String, (Value, Option<u128>, KeyMeta))>() + 1 + key.len()
+}
+
+impl Value {
+    /// 估算值在堆上占用的字节数，集合类型按 samples 个元素抽样
+    pub fn memory_usage(&self, samples: usize) -> usize {
+        match self {
+            Self::String(s) => s.capacity(),
+            Self::List(ListValue::Listpack(listpack))
+            | Self::Hash(HashValue::Listpack(listpack))
+            | Self::Set(SetValue::Listpack(listpack)) => listpack.bytes(),
+            Self::List(ListValue::Quicklist(list)) => deque_bytes(list, samples),
+            Self::Hash(HashValue::Table(map)) => map_bytes(map, samples),
+            Self::Set(SetValue::IntSet(ints)) => ints.capacity() * size_of::<i64>(),
+            Self::Set(SetValue::Table(set)) => set_bytes(set, samples),
+            Self::Null => 0,
+        }
+    }
+}
+
+fn deque_bytes(list: &VecDeque<String>, samples: usize) -> usize {
+    list.capacity() * STRING + sampled(list.iter().map(String::capacity), list.len(), samples)
+}
+
+fn map_bytes(map: &HashMap<String, String>, samples: usize) -> usize {
+    // 每个槽位还有 1 字节控制信息
+    map.capacity() * (2 * STRING + 1)
+        + sampled(
+            map.iter().map(|(k, v)| k.capacity() + v.capacity()),
+            map.len(),
+            samples,
+        )
+}
+
+fn set_bytes(set: &Dict, samples: usize) -> usize {
+    // 每个桶是一个 Vec，成员本身按 String 计算
+    set.buckets() * size_of::<Vec<String>>()
+        + set.len() * STRING
+        + sampled(set.iter().map(str::len), set.len(), samples)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::Database;
+
+    #[test]
+    fn f1_memory_usage() {
+        assert_eq!(Value::String("x".repeat(100)).memory_usage(5), 100);
+        // 元素大小一致时抽样估算与逐个累加的结果相同
+        let list = Value::List(ListValue::Quicklist(
+            (0..1000).map(|_| "y".repeat(10)).collect(),
+        ));
+        assert_eq!(list.memory_usage(5), list.memory_usage(0));
+
+        let mut db = Database::new();
+        db.set("a".into(), Value::String("x".repeat(100)));
+        db.set("b".into(), list.clone());
+        let expected =
+            100 + key_overhead("a") + list.memory_usage(USAGE_SAMPLES) + key_overhead("b");
+        assert_eq!(db.dataset_bytes(), expected);
+
+        if let Some(Value::String(s)) = db.get_mut("a") {
+            s.push_str(&"x".repeat(1000));
+        }
+        db.modified("a");
+        assert!(db.dataset_bytes() > expected + 900);
+        db.del("a");
+        db.del("b");
+        assert_eq!(db.dataset_bytes(), 0);
+    }
+}
@@ -0,0 +1,7 @@
+/// 先写临时文件再改名，写到一半时中断也不会留下不完整的文件
+pub fn write_atomic(path: &str, content: &str) -> Result<(), String> {
+    let temp = format!("{}.tmp", path);
+    std::fs::write(&temp, content)
+        .and_then(|_| std::fs::rename(&temp, path))
+        .map_err(|e| format!("{}: {}", path, e))
+}
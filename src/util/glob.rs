@@ -0,0 +1,142 @@
+/// Redis 风格的通配符匹配，支持 `*`、`?`、`[abc]`、`[^a-z]` 与 `\` 转义
+pub fn glob_match(pattern: &str, string: &str) -> bool {
+    match_bytes(pattern.as_bytes(), string.as_bytes(), false)
+}
+
+/// 逐个匹配模式中的记号，遇到 `*` 时只记住最近一个 `*` 的位置，失配时回到那里多吞一个字符
+///
+/// 回溯只发生在最近的 `*`，时间为 O(模式长度 × 字符串长度)，不会因为 `*` 很多而指数增长
+fn match_bytes(p: &[u8], s: &[u8], nocase: bool) -> bool {
+    let (mut pi, mut si) = (0, 0);
+    // 最近一个 * 之后的模式位置，以及它当前吞到的字符串位置
+    let mut star: Option<(usize, usize)> = None;
+    loop {
+        if pi < p.len() && p[pi] == b'*' {
+            // 合并连续的 *
+            while pi < p.len() && p[pi] == b'*' {
+                pi += 1;
+            }
+            if pi == p.len() {
+                return true;
+            }
+            star = Some((pi, si));
+            continue;
+        }
+        if pi < p.len() {
+            if let Some(len) = match_token(&p[pi..], s.get(si).copied(), nocase) {
+                pi += len;
+                si += 1;
+                continue;
+            }
+        } else if si == s.len() {
+            return true;
+        }
+        match star {
+            Some((star_p, star_s)) if star_s < s.len() => {
+                star = Some((star_p, star_s + 1));
+                pi = star_p;
+                si = star_s + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// 用模式开头的一个记号（不是 `*`）匹配字符 c，匹配时返回记号在模式中占用的字节数
+fn match_token(p: &[u8], c: Option<u8>, nocase: bool) -> Option<usize> {
+    let c = c?;
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+    match p[0] {
+        b'?' => Some(1),
+        b'[' => {
+            let mut i = 1;
+            let negate = p.get(i) == Some(&b'^');
+            if negate {
+                i += 1;
+            }
+            let mut matched = false;
+            loop {
+                match &p[i..] {
+                    [] => break,
+                    [b']', ..] => {
+                        i += 1;
+                        break;
+                    }
+                    [b'\\', e, ..] => {
+                        if eq(*e, c) {
+                            matched = true;
+                        }
+                        i += 2;
+                    }
+                    [lo, b'-', hi, ..] if *hi != b']' => {
+                        let (mut lo, mut hi, mut c) = (*lo, *hi, c);
+                        if lo > hi {
+                            std::mem::swap(&mut lo, &mut hi);
+                        }
+                        if nocase {
+                            lo = lo.to_ascii_lowercase();
+                            hi = hi.to_ascii_lowercase();
+                            c = c.to_ascii_lowercase();
+                        }
+                        if lo <= c && c <= hi {
+                            matched = true;
+                        }
+                        i += 3;
+                    }
+                    [e, ..] => {
+                        if eq(*e, c) {
+                            matched = true;
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            // 未闭合的 [ 一直延伸到模式末尾
+            (matched != negate).then_some(i)
+        }
+        b'\\' if p.len() >= 2 => eq(p[1], c).then_some(2),
+        e => eq(e, c).then_some(1),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("h?llo", "hello"));
+        assert!(glob_match("h*llo", "heeeello"));
+        assert!(glob_match("h[ae]llo", "hallo"));
+        assert!(!glob_match("h[^e]llo", "hello"));
+        assert!(glob_match("h[a-b]llo", "hbllo"));
+        assert!(glob_match("news.*", "news.sport"));
+        assert!(!glob_match("news.*", "new.sport"));
+        assert!(glob_match("a\\*b", "a*b"));
+        assert!(!glob_match("a\\*b", "axb"));
+        assert!(glob_match("__keyspace@0__:*", "__keyspace@0__:foo"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+        assert!(!glob_match("*a*b", "xxaxxbxx"));
+        assert!(glob_match("a*[xy]", "abcy"));
+        assert!(glob_match("*[abc", "xa"));
+        assert!(glob_match("a?*c", "abbbc"));
+        assert!(!glob_match("a?*c", "ac"));
+    }
+
+    #[test]
+    fn f2_many_stars() {
+        // CVE-2022-36021：递归回溯时这里的耗时是指数级的
+        let pattern = format!("{}b", "a*".repeat(40));
+        let string = "a".repeat(200);
+        let start = std::time::Instant::now();
+        assert!(!glob_match(&pattern, &string));
+        assert!(start.elapsed() < std::time::Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,127 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// 统计已分配字节数的分配器，相当于 Redis 的 used_memory
+struct CountingAllocator;
+
+static USED_MEMORY: AtomicUsize = AtomicUsize::new(0);
+static PEAK_MEMORY: AtomicUsize = AtomicUsize::new(0);
+static STARTUP_MEMORY: AtomicUsize = AtomicUsize::new(0);
+
+fn allocated(size: usize) {
+    let used = USED_MEMORY.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK_MEMORY.fetch_max(used, Ordering::Relaxed);
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        USED_MEMORY.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            allocated(new_size);
+            USED_MEMORY.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+/// 当前通过分配器申请且尚未释放的字节数
+pub fn used_memory() -> usize {
+    USED_MEMORY.load(Ordering::Relaxed)
+}
+
+/// used_memory 的历史最大值
+pub fn used_memory_peak() -> usize {
+    PEAK_MEMORY.load(Ordering::Relaxed)
+}
+
+/// 服务启动完成（开始接受连接）时的 used_memory
+pub fn used_memory_startup() -> usize {
+    STARTUP_MEMORY.load(Ordering::Relaxed)
+}
+
+pub fn record_startup_memory() {
+    STARTUP_MEMORY.store(used_memory(), Ordering::Relaxed);
+}
+
+/// 进程实际占用的物理内存，从 /proc/self/status 读取，不可用时为 0
+pub fn resident_memory() -> usize {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+            let kb = line.split_whitespace().nth(1)?.parse::<usize>().ok()?;
+            Some(kb * 1024)
+        })
+        .unwrap_or(0)
+}
+
+/// 转换为 Redis 的可读格式，如 "1.50M"
+pub fn format_memory(bytes: usize) -> String {
+    const UNITS: &[(usize, &str)] = &[(1 << 30, "G"), (1 << 20, "M"), (1 << 10, "K")];
+    for &(unit, suffix) in UNITS {
+        if bytes >= unit {
+            return format!("{:.2}{}", bytes as f64 / unit as f64, suffix);
+        }
+    }
+    format!("{}B", bytes)
+}
+
+/// 解析带单位的内存大小，如 "32mb"、"1gb"、"100"，k/m/g 为 1000 进制，kb/mb/gb 为 1024 进制
+pub fn parse_memory(s: &str) -> Option<u64> {
+    let s = s.to_lowercase();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let multiplier: u64 = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    num.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn f1_parse_memory() {
+        assert_eq!(parse_memory("100"), Some(100));
+        assert_eq!(parse_memory("1k"), Some(1000));
+        assert_eq!(parse_memory("32MB"), Some(32 * 1024 * 1024));
+        assert_eq!(parse_memory("2gb"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_memory("mb"), None);
+        assert_eq!(parse_memory("10tb"), None);
+        assert_eq!(parse_memory("-1"), None);
+        assert_eq!(format_memory(100), "100B");
+        assert_eq!(format_memory(1536 * 1024), "1.50M");
+    }
+}
@@ -0,0 +1,11 @@
+mod file;
+mod glob;
+mod memory;
+mod process;
+pub use file::write_atomic;
+pub use glob::glob_match;
+pub use memory::{
+    format_memory, parse_memory, record_startup_memory, resident_memory, used_memory,
+    used_memory_peak, used_memory_startup,
+};
+pub use process::cpu_times;
@@ -0,0 +1,19 @@
+/// 从 /proc/self/stat 读取的 CPU 时间（秒）：
+/// (用户态, 内核态, 子进程用户态, 子进程内核态)
+pub fn cpu_times() -> [f64; 4] {
+    // 时钟频率在 Linux 上固定为 100
+    const CLK_TCK: f64 = 100.0;
+    std::fs::read_to_string("/proc/self/stat")
+        .ok()
+        .and_then(|stat| {
+            // 进程名可能包含空格，从最后一个 ')' 之后开始按字段切分，utime 是其后第 12 个字段
+            let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+            let ticks = fields.get(11..15)?;
+            let mut times = [0.0; 4];
+            for (time, tick) in times.iter_mut().zip(ticks) {
+                *time = tick.parse::<f64>().ok()? / CLK_TCK;
+            }
+            Some(times)
+        })
+        .unwrap_or_default()
+}